@@ -0,0 +1,131 @@
+#[cfg(feature = "audio")]
+use sdl2::mixer::{Channel, Chunk, LoaderRWops};
+use std::cell::Cell;
+
+// Sounds are built into the binary so they play wherever it is run from
+#[cfg(feature = "audio")]
+const SPAWN_WAV: &[u8] = include_bytes!("../../assets/sounds/spawn.wav");
+#[cfg(feature = "audio")]
+const CLOSE_CALL_WAV: &[u8] = include_bytes!("../../assets/sounds/close_call.wav");
+#[cfg(feature = "audio")]
+const HUM_WAV: &[u8] = include_bytes!("../../assets/sounds/hum.wav");
+// The hum loops on a channel of its own, kept out of the pool the effects play on
+#[cfg(feature = "audio")]
+const HUM_CHANNEL: Channel = Channel(0);
+// The hum is at its loudest, a quarter of full volume, from this many vehicles on
+#[cfg(feature = "audio")]
+const HUM_FULL_VEHICLES: usize = 30;
+
+#[cfg(feature = "audio")]
+struct Sounds {
+    _subsystem: sdl2::AudioSubsystem,
+    spawn: Option<Chunk>,
+    close_call: Option<Chunk>,
+    hum: Option<Chunk>,
+}
+
+/// Plays the simulation's sound effects and the engine hum. Built without the `audio`
+/// feature, or without an audio device, every method is a no-op.
+pub struct AudioEngine {
+    #[cfg(feature = "audio")]
+    sounds: Option<Sounds>,
+    muted: Cell<bool>,
+}
+
+impl AudioEngine {
+    pub fn silent() -> Self {
+        Self {
+            #[cfg(feature = "audio")]
+            sounds: None,
+            muted: Cell::new(false),
+        }
+    }
+
+    /// Opens the audio device and starts the hum, silent until there is traffic.
+    #[cfg(feature = "audio")]
+    pub fn open(sdl_context: &sdl2::Sdl) -> Result<Self, String> {
+        use sdl2::mixer::{DEFAULT_CHANNELS, DEFAULT_FORMAT, DEFAULT_FREQUENCY};
+
+        let subsystem = sdl_context.audio()?;
+        sdl2::mixer::open_audio(DEFAULT_FREQUENCY, DEFAULT_FORMAT, DEFAULT_CHANNELS, 1024)?;
+        sdl2::mixer::reserve_channels(1);
+
+        let hum = Self::load("hum", HUM_WAV);
+        if let Some(hum) = &hum {
+            HUM_CHANNEL.set_volume(0);
+            HUM_CHANNEL.play(hum, -1)?;
+        }
+        Ok(Self {
+            sounds: Some(Sounds {
+                _subsystem: subsystem,
+                spawn: Self::load("spawn", SPAWN_WAV),
+                close_call: Self::load("close call", CLOSE_CALL_WAV),
+                hum,
+            }),
+            muted: Cell::new(false),
+        })
+    }
+
+    #[cfg(not(feature = "audio"))]
+    pub fn open(_sdl_context: &sdl2::Sdl) -> Result<Self, String> {
+        Ok(Self::silent())
+    }
+
+    #[cfg(feature = "audio")]
+    fn load(name: &str, wav: &'static [u8]) -> Option<Chunk> {
+        sdl2::rwops::RWops::from_bytes(wav)
+            .and_then(|rwops| rwops.load_wav())
+            .map_err(|e| eprintln!("Failed to load the {} sound: {}", name, e))
+            .ok()
+    }
+
+    #[cfg(feature = "audio")]
+    fn play(&self, chunk: impl Fn(&Sounds) -> &Option<Chunk>) {
+        if self.muted.get() {
+            return;
+        }
+        if let Some(chunk) = self.sounds.as_ref().and_then(|sounds| chunk(sounds).as_ref()) {
+            // Every channel may be busy when many events fire at once; dropping the sound is fine
+            let _ = Channel::all().play(chunk, 0);
+        }
+    }
+
+    pub fn play_spawn(&self) {
+        #[cfg(feature = "audio")]
+        self.play(|sounds| &sounds.spawn);
+    }
+
+    pub fn play_close_call(&self) {
+        #[cfg(feature = "audio")]
+        self.play(|sounds| &sounds.close_call);
+    }
+
+    /// Sets the hum's volume from the number of vehicles on the road.
+    pub fn set_traffic(&self, vehicles: usize) {
+        #[cfg(not(feature = "audio"))]
+        let _ = vehicles;
+        #[cfg(feature = "audio")]
+        if self.sounds.as_ref().is_some_and(|sounds| sounds.hum.is_some()) {
+            let volume = if self.muted.get() {
+                0
+            } else {
+                let max = sdl2::mixer::MAX_VOLUME / 4;
+                max * vehicles.min(HUM_FULL_VEHICLES) as i32 / HUM_FULL_VEHICLES as i32
+            };
+            HUM_CHANNEL.set_volume(volume);
+        }
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted.get()
+    }
+
+    /// Mutes or unmutes every sound. An unmuted hum comes back on the next `set_traffic`.
+    pub fn set_muted(&self, muted: bool) {
+        self.muted.set(muted);
+        #[cfg(feature = "audio")]
+        if muted && self.sounds.is_some() {
+            HUM_CHANNEL.set_volume(0);
+        }
+    }
+}
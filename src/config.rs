@@ -0,0 +1,417 @@
+use crate::constants::*;
+use crate::direction::{Direction, TurnDirection};
+use crate::geometry::layout::RoadLayout;
+use crate::simulation::grading::GradingWeights;
+use crate::simulation::route_distribution::RouteDistribution;
+use crate::simulation::spawn_scheduler::SpawnModel;
+use crate::simulation::statistics::SAFE_DISTANCE;
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::time::Duration;
+
+// Accepted ranges, so a typo in the file is reported instead of producing a road that
+// spawns vehicles on top of each other or never flags a close call
+const SPAWN_MS_RANGE: std::ops::RangeInclusive<u64> = 100..=10_000;
+const SAFE_DISTANCE_RANGE: std::ops::RangeInclusive<f32> = 10.0..=200.0;
+const MAX_VEHICLES_RANGE: std::ops::RangeInclusive<usize> = 1..=200;
+const DECORATION_DENSITY_RANGE: std::ops::RangeInclusive<f32> = 0.0..=5.0;
+const WINDOW_SIZE_RANGE: std::ops::RangeInclusive<u32> = 400..=2000;
+const LANES_PER_DIRECTION_RANGE: std::ops::RangeInclusive<i32> = 2..=MAX_LANES_PER_DIRECTION;
+
+/// Relative weights of the routes taken by randomly spawned vehicles.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteWeights {
+    pub left: f64,
+    pub straight: f64,
+    pub right: f64,
+    pub u_turn: f64,
+}
+
+impl RouteWeights {
+    pub fn to_weights(self) -> [(TurnDirection, f64); 4] {
+        [
+            (TurnDirection::Left, self.left),
+            (TurnDirection::Straight, self.straight),
+            (TurnDirection::Right, self.right),
+            (TurnDirection::UTurn, self.u_turn),
+        ]
+    }
+}
+
+impl Default for RouteWeights {
+    fn default() -> Self {
+        let weight = |route| {
+            ROUTE_WEIGHTS
+                .iter()
+                .find(|(turn, _)| *turn == route)
+                .map_or(0.0, |&(_, weight)| weight)
+        };
+        Self {
+            left: weight(TurnDirection::Left),
+            straight: weight(TurnDirection::Straight),
+            right: weight(TurnDirection::Right),
+            u_turn: weight(TurnDirection::UTurn),
+        }
+    }
+}
+
+/// Relative share of randomly generated vehicles entering from each arm.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DirectionWeights {
+    pub north: f64,
+    pub east: f64,
+    pub south: f64,
+    pub west: f64,
+}
+
+impl DirectionWeights {
+    pub fn to_weights(self) -> [(Direction, f64); 4] {
+        [
+            (Direction::Up, self.north),
+            (Direction::Right, self.east),
+            (Direction::Down, self.south),
+            (Direction::Left, self.west),
+        ]
+    }
+
+    fn validate(&self, closed_arm: Option<Direction>) -> Result<(), String> {
+        let weights = self.to_weights();
+        if let Some((direction, weight)) = weights
+            .iter()
+            .find(|(_, weight)| !weight.is_finite() || *weight < 0.0)
+        {
+            return Err(format!("Invalid weight {} for the {} arm", weight, direction.compass_name()));
+        }
+        if weights
+            .iter()
+            .all(|&(direction, weight)| weight == 0.0 || closed_arm == Some(direction))
+        {
+            return Err("Direction weights must not all be zero on the open arms".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Default for DirectionWeights {
+    fn default() -> Self {
+        Self {
+            north: 1.0,
+            east: 1.0,
+            south: 1.0,
+            west: 1.0,
+        }
+    }
+}
+
+/// Phase lengths of the `--traffic-light` controller, per axis.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrafficLightTiming {
+    pub green_ms: u64,
+    /// Between green and red; vehicles already too close to stop carry on through.
+    pub amber_ms: u64,
+    /// All red after the amber, so the intersection can clear.
+    pub clearance_ms: u64,
+}
+
+impl TrafficLightTiming {
+    /// Green, amber and clearance lengths in simulation ticks.
+    pub fn to_ticks(self) -> (u64, u64, u64) {
+        let step = SIMULATION_STEP.as_millis() as u64;
+        (self.green_ms / step, self.amber_ms / step, self.clearance_ms / step)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let step = SIMULATION_STEP.as_millis() as u64;
+        if self.green_ms < step {
+            return Err(format!(
+                "traffic_light green_ms must be at least {}, got {}",
+                step, self.green_ms
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for TrafficLightTiming {
+    fn default() -> Self {
+        let step = SIMULATION_STEP.as_millis() as u64;
+        Self {
+            green_ms: TRAFFIC_LIGHT_GREEN_TICKS * step,
+            amber_ms: TRAFFIC_LIGHT_AMBER_TICKS * step,
+            clearance_ms: TRAFFIC_LIGHT_CLEARANCE_TICKS * step,
+        }
+    }
+}
+
+/// Simulation settings read at startup. Fields missing from the file keep their
+/// defaults, which are the values the simulation was tuned with.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimulationConfig {
+    /// Width and height of the window when it opens, in pixels. The scene is scaled to
+    /// fit, so this sets how big the road looks rather than how much of it there is.
+    pub window_size: u32,
+    /// Lanes in each direction of travel on every road.
+    pub lanes_per_direction: i32,
+    /// How far the intersection sits right of and below the middle of the world, for an
+    /// off-centre junction. Both must be even, like the vehicle margins, and leave every
+    /// approach long enough to change lanes on.
+    pub intersection_offset: (i32, i32),
+    /// Side of the intersection with no road, such as `"north"`, turning it into a
+    /// T-intersection. All four arms are open when not set.
+    pub closed_arm: Option<Direction>,
+    /// Shortest time between two spawns from the same direction.
+    pub spawn_cooldown_ms: u64,
+    /// Time between spawns while random generation is on, with the fixed interval model.
+    pub spawn_interval_ms: u64,
+    /// When random generation spawns vehicles.
+    pub spawn_model: SpawnModel,
+    /// Seed of random generation's arrivals and of every vehicle's route, lane and
+    /// driver, so a run can be repeated.
+    pub spawn_seed: u64,
+    pub direction_weights: DirectionWeights,
+    /// Distance below which two vehicles count as a close call, in clear weather.
+    pub safe_distance: f32,
+    /// Most vehicles on the road at once; further spawns queue until others leave.
+    pub max_vehicles: usize,
+    /// Most vehicles in the intersection at once, to model a small junction. Unlimited
+    /// when not set.
+    pub max_in_intersection: Option<usize>,
+    pub route_weights: RouteWeights,
+    /// How many trees and bushes stand on the grass, 1 for the default amount and 0 for none.
+    pub decoration_density: f32,
+    /// Points the end-of-run grade loses per incident.
+    pub grading: GradingWeights,
+    pub traffic_light: TrafficLightTiming,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            window_size: WORLD_SIZE,
+            lanes_per_direction: DEFAULT_LANES_PER_DIRECTION,
+            intersection_offset: (0, 0),
+            closed_arm: None,
+            spawn_cooldown_ms: SPAWN_COOLDOWN.as_millis() as u64,
+            spawn_interval_ms: VEHICLE_SPAWN_INTERVAL.as_millis() as u64,
+            spawn_model: SpawnModel::default(),
+            spawn_seed: 0,
+            direction_weights: DirectionWeights::default(),
+            safe_distance: SAFE_DISTANCE,
+            max_vehicles: MAX_VEHICLES,
+            max_in_intersection: None,
+            route_weights: RouteWeights::default(),
+            decoration_density: 1.0,
+            grading: GradingWeights::default(),
+            traffic_light: TrafficLightTiming::default(),
+        }
+    }
+}
+
+impl SimulationConfig {
+    pub fn spawn_cooldown(&self) -> Duration {
+        Duration::from_millis(self.spawn_cooldown_ms)
+    }
+
+    pub fn spawn_interval(&self) -> Duration {
+        Duration::from_millis(self.spawn_interval_ms)
+    }
+
+    /// How the roads are laid out, on a square world until `RoadLayout::for_window`
+    /// shapes it to a window.
+    pub fn layout(&self) -> RoadLayout {
+        RoadLayout {
+            lanes_per_direction: self.lanes_per_direction,
+            intersection_offset: self.intersection_offset,
+            closed_arm: self.closed_arm,
+            ..RoadLayout::DEFAULT
+        }
+    }
+
+    /// Lane counts the roads have room for around the configured offset, for the menu
+    /// to offer.
+    pub fn lane_counts(&self) -> std::ops::RangeInclusive<i32> {
+        let (x, y) = self.intersection_offset;
+        let room = WORLD_SIZE as i32 / 2 - MIN_APPROACH_LENGTH - x.abs().max(y.abs());
+        *LANES_PER_DIRECTION_RANGE.start()..=(room / LINE_SPACING).min(*LANES_PER_DIRECTION_RANGE.end())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !WINDOW_SIZE_RANGE.contains(&self.window_size) {
+            return Err(format!(
+                "window_size must be between {} and {}, got {}",
+                WINDOW_SIZE_RANGE.start(),
+                WINDOW_SIZE_RANGE.end(),
+                self.window_size
+            ));
+        }
+        if !LANES_PER_DIRECTION_RANGE.contains(&self.lanes_per_direction) {
+            return Err(format!(
+                "lanes_per_direction must be between {} and {}, got {}",
+                LANES_PER_DIRECTION_RANGE.start(),
+                LANES_PER_DIRECTION_RANGE.end(),
+                self.lanes_per_direction
+            ));
+        }
+        let (x, y) = self.intersection_offset;
+        if x % 2 != 0 || y % 2 != 0 {
+            return Err(format!("intersection_offset must be even, got ({}, {})", x, y));
+        }
+        let max_offset = WORLD_SIZE as i32 / 2 - self.lanes_per_direction * LINE_SPACING - MIN_APPROACH_LENGTH;
+        if x.abs() > max_offset || y.abs() > max_offset {
+            return Err(format!(
+                "intersection_offset must be between -{} and {} with {} lanes, got ({}, {})",
+                max_offset, max_offset, self.lanes_per_direction, x, y
+            ));
+        }
+        for (name, value) in [
+            ("spawn_cooldown_ms", self.spawn_cooldown_ms),
+            ("spawn_interval_ms", self.spawn_interval_ms),
+        ] {
+            if !SPAWN_MS_RANGE.contains(&value) {
+                return Err(format!(
+                    "{} must be between {} and {}, got {}",
+                    name,
+                    SPAWN_MS_RANGE.start(),
+                    SPAWN_MS_RANGE.end(),
+                    value
+                ));
+            }
+        }
+        if !SAFE_DISTANCE_RANGE.contains(&self.safe_distance) {
+            return Err(format!(
+                "safe_distance must be between {} and {}, got {}",
+                SAFE_DISTANCE_RANGE.start(),
+                SAFE_DISTANCE_RANGE.end(),
+                self.safe_distance
+            ));
+        }
+        if !MAX_VEHICLES_RANGE.contains(&self.max_vehicles) {
+            return Err(format!(
+                "max_vehicles must be between {} and {}, got {}",
+                MAX_VEHICLES_RANGE.start(),
+                MAX_VEHICLES_RANGE.end(),
+                self.max_vehicles
+            ));
+        }
+        if self.max_in_intersection == Some(0) {
+            return Err("max_in_intersection must be at least 1".to_string());
+        }
+        if !DECORATION_DENSITY_RANGE.contains(&self.decoration_density) {
+            return Err(format!(
+                "decoration_density must be between {} and {}, got {}",
+                DECORATION_DENSITY_RANGE.start(),
+                DECORATION_DENSITY_RANGE.end(),
+                self.decoration_density
+            ));
+        }
+        self.spawn_model.validate()?;
+        self.direction_weights.validate(self.closed_arm)?;
+        self.grading.validate()?;
+        self.traffic_light.validate()?;
+        RouteDistribution::new(&self.route_weights.to_weights()).map(|_| ())
+    }
+}
+
+/// Reads the JSON config at `path`, falling back to the defaults when there is no
+/// such file.
+pub fn load_config(path: &str) -> Result<SimulationConfig, String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(SimulationConfig::default()),
+        Err(e) => return Err(format!("{}: {}", path, e)),
+    };
+    let config: SimulationConfig =
+        serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?;
+    config.validate().map_err(|e| format!("{}: {}", path, e))?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::AudioEngine;
+    use crate::geometry::layout::lanes_per_direction;
+    use crate::intersection::IntersectionBounds;
+    use crate::simulation::vehicle_manager::{SpawnRejection, VehicleManager};
+    use std::rc::Rc;
+
+    // Writes `contents` to a file of its own and loads it
+    fn load(name: &str, contents: &str) -> Result<SimulationConfig, String> {
+        let path = std::env::temp_dir().join(format!("smart-road-{}-{}.json", name, std::process::id()));
+        std::fs::write(&path, contents).expect("the temp dir is writable");
+        let config = load_config(path.to_str().expect("the temp dir has a UTF-8 path"));
+        let _ = std::fs::remove_file(&path);
+        config
+    }
+
+    #[test]
+    fn a_sample_config_sets_the_window_size_and_lane_count() {
+        let config = load(
+            "sample",
+            r#"{ "window_size": 1000, "lanes_per_direction": 4, "spawn_seed": 7 }"#,
+        )
+        .expect("the sample is valid");
+        assert_eq!((config.window_size, config.lanes_per_direction, config.spawn_seed), (1000, 4, 7));
+        assert_eq!(config.max_vehicles, MAX_VEHICLES, "fields left out keep their defaults");
+
+        let mut manager = VehicleManager::new_with_config(Rc::new(AudioEngine::silent()), &config);
+        assert_eq!(lanes_per_direction(), 4);
+        let (left, _, right, _) = IntersectionBounds::edges();
+        assert_eq!(right - left, 8 * LINE_SPACING);
+        assert_eq!(manager.toggle_lane(Direction::Up, 3), Ok(true));
+        assert_eq!(manager.toggle_lane(Direction::Up, 4), Err(SpawnRejection::InvalidLane(4)));
+    }
+
+    #[test]
+    fn a_closed_arm_in_the_config_makes_a_t_intersection() {
+        let config = load("closed-arm", r#"{ "closed_arm": "north" }"#).expect("north is an arm");
+        assert_eq!(config.closed_arm, Some(Direction::Up));
+        let mut manager = VehicleManager::new_with_config(Rc::new(AudioEngine::silent()), &config);
+        assert!(!Direction::Up.is_open());
+        assert_eq!(manager.try_spawn_vehicle(Direction::Up), Err(SpawnRejection::ClosedArm(Direction::Up)));
+
+        for _ in 0..20 {
+            [Direction::Left, Direction::Down, Direction::Right]
+                .into_iter()
+                .for_each(|direction| manager.spawn_unthrottled(direction));
+            for _ in 0..30 {
+                manager.update_vehicles();
+                let vehicles = manager.get_vehicles();
+                assert!(vehicles.iter().all(|vehicle| vehicle.target_direction != Direction::Up));
+            }
+        }
+    }
+
+    #[test]
+    fn an_offset_intersection_is_read_from_the_config() {
+        let config = load("offset", r#"{ "intersection_offset": [100, -60] }"#).expect("the offset fits");
+        assert_eq!(config.lane_counts(), 2..=3, "a fourth lane leaves the west approach too short");
+        VehicleManager::new_with_config(Rc::new(AudioEngine::silent()), &config);
+        let (left, top, _, _) = IntersectionBounds::edges();
+        let half_road = config.lanes_per_direction * LINE_SPACING;
+        assert_eq!((left, top), (WORLD_SIZE as i32 / 2 + 100 - half_road, WORLD_SIZE as i32 / 2 - 60 - half_road));
+    }
+
+    #[test]
+    fn a_road_layout_that_does_not_fit_is_rejected() {
+        for (contents, field) in [
+            (r#"{ "window_size": 100 }"#, "window_size"),
+            (r#"{ "lanes_per_direction": 1 }"#, "lanes_per_direction"),
+            (r#"{ "lanes_per_direction": 5 }"#, "lanes_per_direction"),
+            (r#"{ "intersection_offset": [11, 0] }"#, "intersection_offset"),
+            (r#"{ "lanes_per_direction": 4, "intersection_offset": [0, -60] }"#, "intersection_offset"),
+            (r#"{ "closed_arm": "up" }"#, "unknown variant `up`"),
+            (
+                r#"{ "closed_arm": "west", "direction_weights": { "north": 0, "east": 0, "south": 0 } }"#,
+                "Direction weights",
+            ),
+        ] {
+            let error = load("out-of-range", contents).expect_err(contents);
+            assert!(error.contains(field), "{}", error);
+        }
+    }
+}
@@ -1,19 +1,105 @@
-use crate::geometry::Position;
+use crate::direction::TurnDirection;
+use sdl2::pixels::Color;
 
-pub const WINDOW_SIZE: u32 = 800;
+// Side of the square the simulation is laid out in. The window can be any size; the
+// scene is scaled to fit it
+pub const WORLD_SIZE: u32 = 800;
 //changed this to try and accomedate the 6 lanes
-pub const LINE_SPACING: i32 = (WINDOW_SIZE / 16) as i32;
+pub const LINE_SPACING: i32 = (WORLD_SIZE / 16) as i32;
 pub const VEHICLE_SIZE: u32 = LINE_SPACING as u32;
+// Car images in the assets directory, one car per file facing up, and the colour of the generated car drawn
+// instead when a file cannot be loaded. Each vehicle picks one at random
+pub const CAR_SPRITES: [(&str, Color); 3] = [
+    ("cars.png", Color::RGB(200, 50, 50)),
+    ("cars-4.png", Color::RGB(50, 90, 200)),
+    ("green-car.png", Color::RGB(40, 160, 60)),
+];
+// Lanes in each direction of travel on every road unless the config sets another
+// count, which can be from 2 up to the most that fit
+pub const DEFAULT_LANES_PER_DIRECTION: i32 = 3;
+pub const MAX_LANES_PER_DIRECTION: i32 = 4;
+// An off-centre intersection must leave every approach at least this long, so that
+// vehicles can change lanes before the stop line
+pub const MIN_APPROACH_LENGTH: i32 = LANE_CHANGE_DISTANCE + LINE_SPACING;
+// Smaller vehicles are centered in their lane. The leftover space on each side
+// must stay even so 2px steps still land exactly on the turn coordinates.
+pub const COMPACT_VEHICLE_SIZE: u32 = VEHICLE_SIZE - 12;
+// Trucks are twice a compact car, plus the 2px that keep their leftover even. They are
+// wider than a lane, so they overhang the lanes beside theirs
+pub const TRUCK_VEHICLE_SIZE: u32 = 2 * COMPACT_VEHICLE_SIZE + 2;
+pub const VEHICLE_SIZE_CLASSES: [u32; 3] = [VEHICLE_SIZE, COMPACT_VEHICLE_SIZE, TRUCK_VEHICLE_SIZE];
 pub const FRAME_DURATION: std::time::Duration = std::time::Duration::from_millis(1000 / 60);
+// Frames the profiler's rolling averages in the debug view cover, two seconds at 60 fps
+pub const PROFILE_WINDOW: usize = 120;
+// Paths are sampled per tick, so the simulation advances in fixed steps independent of rendering
+pub const SIMULATION_STEP: std::time::Duration = std::time::Duration::from_millis(1000 / 60);
+pub const MAX_STEPS_PER_FRAME: u32 = 5;
 pub const VEHICLE_SPAWN_INTERVAL: std::time::Duration = std::time::Duration::from_millis(700);
 pub const SPAWN_COOLDOWN: std::time::Duration = std::time::Duration::from_millis(700);
-
-// Define intersection bounds
-pub const INTERSECTION_TOP_LEFT: Position = Position {
-    x: 5 * LINE_SPACING,
-    y: 5 * LINE_SPACING,
-};
-pub const INTERSECTION_BOTTOM_RIGHT: Position = Position {
-    x: 11 * LINE_SPACING,
-    y: 11 * LINE_SPACING,
-};
\ No newline at end of file
+// Most vehicles on the road at once. Spawns requested beyond it wait in a queue
+pub const MAX_VEHICLES: usize = 48;
+// Vehicles in a platoon, spawned in one lane by Shift and an arrow key
+pub const PLATOON_SIZE: usize = 3;
+// How long on-screen feedback messages stay up
+pub const TOAST_DURATION: std::time::Duration = std::time::Duration::from_millis(2000);
+// Relative weights of the routes taken by spawned vehicles
+pub const ROUTE_WEIGHTS: [(TurnDirection, f64); 4] = [
+    (TurnDirection::Left, 0.3),
+    (TurnDirection::Straight, 0.3),
+    (TurnDirection::Right, 0.3),
+    (TurnDirection::UTurn, 0.1),
+];
+// Chance that a spawned vehicle starts in a lane next to its route's lanes
+pub const LANE_CHANGE_PROBABILITY: f64 = 0.15;
+// How far into the road a vehicle in the wrong lane moves over, well before the intersection
+pub const LANE_CHANGE_DISTANCE: i32 = 2 * LINE_SPACING;
+// A vehicle moves to a neighbouring lane on its route when the vehicle ahead is within
+// the first cells of its own lane and that many cells of the neighbouring lane are clear
+pub const LANE_CHANGE_QUEUE_CELLS: i32 = 3;
+pub const LANE_CHANGE_CLEAR_CELLS: i32 = 5;
+// Vehicles that have not moved for this many ticks outside the intersection are removed
+pub const STUCK_TICK_LIMIT: u32 = 600;
+// Pixels per tick on the approach to the intersection
+pub const APPROACH_SPEED: i32 = 2;
+// Pixels per tick once out of the intersection, in good weather
+pub const EXIT_SPEED: i32 = 3;
+// Thickness of the stop lines drawn at the intersection entries
+pub const STOP_LINE_WIDTH: u32 = 4;
+// Only every Nth path point is drawn by the debug path overlay
+pub const PATH_RENDER_STRIDE: usize = 4;
+// Vehicles remember this many past positions for their trails, spaced this many pixels
+// apart, so a trail covers the last 128 pixels travelled
+pub const TRAIL_LENGTH: usize = 16;
+pub const TRAIL_SPACING: u64 = 8;
+// Vehicles are drawn facing the point of their path this many pixels ahead, so they
+// rotate smoothly through turns
+pub const HEADING_LOOKAHEAD: i32 = LINE_SPACING / 2;
+// Turns inside the intersection are rounded off to quarter circles of this radius, or
+// less where the path turns again sooner
+pub const TURN_ARC_RADIUS: i32 = LINE_SPACING / 2;
+// Space the most cautious drivers keep to the vehicle ahead and to crossing traffic on
+// top of the weather's gap. Fully aggressive drivers keep none of it
+pub const CAUTIOUS_FOLLOWING_GAP: i32 = LINE_SPACING / 5;
+pub const CAUTIOUS_CROSSING_GAP: i32 = LINE_SPACING / 5;
+// How long a close call stays marked on screen, and its vehicles stay tinted, in ticks
+pub const CLOSE_CALL_MARKER_TICKS: u64 = 60;
+pub const CLOSE_CALL_TINT_TICKS: u64 = 20;
+// Fixed-cycle light used with `--traffic-light`: green per axis, amber, then all red so
+// the intersection can clear
+pub const TRAFFIC_LIGHT_GREEN_TICKS: u64 = 240;
+pub const TRAFFIC_LIGHT_AMBER_TICKS: u64 = 60;
+pub const TRAFFIC_LIGHT_CLEARANCE_TICKS: u64 = 120;
+// Vehicles this close to the stop line when the light turns amber carry on through, for
+// a driver of middling aggression; cautious drivers stop from closer, aggressive ones
+// from further out
+pub const AMBER_NO_RETURN_DISTANCE: i32 = 2 * LINE_SPACING;
+// A windowed `--stress` run ends after this long unless `--duration` says otherwise
+pub const STRESS_DURATION: std::time::Duration = std::time::Duration::from_secs(60);
+// Each `--compare` run stops after this long in simulated time, even if the road has
+// not cleared
+pub const COMPARE_TIME_LIMIT: std::time::Duration = std::time::Duration::from_secs(600);
+// Statistics and the event timeline are written here when the simulation ends
+pub const STATS_EXPORT_PATH: &str = "stats.json";
+pub const TIMELINE_EXPORT_PATH: &str = "timeline.csv";
+// Simulation settings are read from here at startup, if it exists
+pub const CONFIG_PATH: &str = "config.json";
@@ -2,28 +2,44 @@ use crate::core::vehicle_data::Vehicle;
 use crate::direction::TurnDirection;
 use crate::geometry::position::Position;
 
-pub struct CollisionDetector;
+/// Decides which other vehicles a vehicle's path is planned around. Shared with the
+/// planning thread, so it has to be thread safe.
+pub trait RelevanceStrategy: Send + Sync {
+    /// Whether `other_vehicle` has to be checked against `self_vehicle` at
+    /// `current_position` on tick `time`.
+    fn is_relevant_for_collision(
+        &self,
+        self_vehicle: &Vehicle,
+        other_vehicle: &Vehicle,
+        current_position: &Position,
+        time: &u64,
+    ) -> bool;
+}
 
-impl CollisionDetector {
-    pub fn is_relevant_for_collision(
+/// Skips vehicles whose routes cannot meet: right turns into different roads, vehicles
+/// from the same side in other lanes and straight-through traffic from opposite sides.
+/// Other traffic only matters in the intersection.
+pub struct RouteRelevance;
+
+impl RelevanceStrategy for RouteRelevance {
+    fn is_relevant_for_collision(
+        &self,
         self_vehicle: &Vehicle,
         other_vehicle: &Vehicle,
         current_position: &Position,
         time: &u64,
     ) -> bool {
-        let same_lane = self_vehicle.initial_position == other_vehicle.initial_position
-            && self_vehicle.target_direction == other_vehicle.target_direction;
+        let same_lane = self_vehicle.shares_lane_with(other_vehicle);
 
         if (self_vehicle.turn_direction == TurnDirection::Right
             || other_vehicle.turn_direction == TurnDirection::Right)
             && !same_lane
+            && self_vehicle.target_direction != other_vehicle.target_direction
         {
             return false;
         }
 
-        if self_vehicle.start_direction == other_vehicle.start_direction
-            && self_vehicle.target_direction != other_vehicle.target_direction
-        {
+        if self_vehicle.start_direction == other_vehicle.start_direction && !same_lane {
             return false;
         }
 
@@ -34,14 +50,26 @@ impl CollisionDetector {
             return false;
         }
 
-        if !same_lane && !current_position.is_in_intersection() {
+        if !same_lane && !current_position.is_in_intersection(self_vehicle.rect.width()) {
             return false;
         }
 
-        if !other_vehicle.path.iter().any(|tp| tp.time == *time) {
-            return false;
-        }
+        other_vehicle.path.iter().any(|tp| tp.time == *time)
+    }
+}
 
-        true
+/// Checks every vehicle still on the road at that tick, whatever its route. Slower than
+/// `RouteRelevance`, but it skips nothing, so it shows what the shortcuts leave out.
+pub struct AlwaysRelevant;
+
+impl RelevanceStrategy for AlwaysRelevant {
+    fn is_relevant_for_collision(
+        &self,
+        _self_vehicle: &Vehicle,
+        other_vehicle: &Vehicle,
+        _current_position: &Position,
+        time: &u64,
+    ) -> bool {
+        other_vehicle.path.iter().any(|tp| tp.time == *time)
     }
 }
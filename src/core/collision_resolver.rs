@@ -5,30 +5,43 @@ use sdl2::rect::Rect;
 pub struct CollisionResolver;
 
 impl CollisionResolver {
+    /// Moves the path back so the vehicle waits clear of `other_vehicle_rect`, returning
+    /// the time to carry on planning from. Every pass of the resolver's loops is added
+    /// to `iterations`.
     pub fn resolve_collision(
         vehicle: &Vehicle,
-        path: &mut Vec<TimedPosition>,
+        path: &mut [TimedPosition],
         current_position: &Position,
         other_vehicle_rect: &Rect,
+        wait_at_stop_line: bool,
+        iterations: &mut u64,
     ) -> u64 {
-        let new_position = Self::find_non_colliding_position(vehicle, path, other_vehicle_rect);
-        let steps = current_position.calculate_steps_to(&new_position);
+        let new_index = Self::find_non_colliding_index(
+            vehicle,
+            path,
+            other_vehicle_rect,
+            wait_at_stop_line,
+            iterations,
+        );
+        let new_position = path[new_index].position;
+        // Measured along the path rather than straight across, since a U-turn doubles back
+        let steps: u64 = path[new_index..]
+            .windows(2)
+            .map(|pair| pair[0].position.calculate_steps_to(&pair[1].position))
+            .sum();
         if steps == 0 {
             panic!("Error: Steps cannot be zero.");
         }
-        let (mut fix_index, mut reached_steps) = Self::find_position(path, steps);
+        let (mut fix_index, mut reached_steps) = Self::find_position(path, steps, iterations);
         let print_fix_index = fix_index;
         let mut tmp_position = path[fix_index].position;
-        let mut current_direction = if tmp_position.is_after_turn(&vehicle.turn_position) {
-            vehicle.target_direction
-        } else {
-            vehicle.start_direction
-        };
+        let mut current_direction = vehicle.direction_at(&tmp_position);
         let mut collision_time_index = path[path.len() - 1].time;
 
         if reached_steps != steps {
             let first_position = path.first().unwrap().position;
             while reached_steps < steps {
+                *iterations += 1;
                 path[fix_index].position = first_position;
                 reached_steps += 1;
                 fix_index += 1;
@@ -36,19 +49,16 @@ impl CollisionResolver {
         }
 
         while tmp_position != new_position {
+            *iterations += 1;
             if fix_index >= path.len() {
                 panic!("Error: Unable to resolve collision, path fixing failed.");
             }
             path[fix_index].position = tmp_position;
-            if tmp_position.is_in_intersection() {
+            if tmp_position.is_in_intersection(vehicle.rect.width()) {
                 collision_time_index = path[fix_index].time;
             }
             tmp_position = tmp_position.move_in_direction(&current_direction, 1);
-            current_direction.update_direction(
-                &vehicle.target_direction,
-                &tmp_position,
-                &vehicle.turn_position,
-            );
+            vehicle.update_direction(&mut current_direction, &tmp_position);
 
             fix_index += 1;
         }
@@ -69,10 +79,11 @@ impl CollisionResolver {
         collision_time_index
     }
 
-    fn find_position(path: &Vec<TimedPosition>, steps: u64) -> (usize, u64) {
+    fn find_position(path: &[TimedPosition], steps: u64, iterations: &mut u64) -> (usize, u64) {
         let mut reached_steps: u64 = 0;
         let mut next_position = path[path.len() - 1].position;
         for index in (0..path.len() - 1).rev() {
+            *iterations += 1;
             let diff_x = (next_position.x - path[index].position.x).abs();
             let diff_y = (next_position.y - path[index].position.y).abs();
             let diff = diff_x + diff_y;
@@ -90,6 +101,7 @@ impl CollisionResolver {
             next_position = path[index].position;
         }
         for index in (0..path.len()).rev() {
+            *iterations += 1;
             if path[index].position == path[0].position {
                 return (index, reached_steps);
             }
@@ -97,23 +109,33 @@ impl CollisionResolver {
         (0, reached_steps)
     }
 
-    fn find_non_colliding_position(
+    /// Finds the last point on the path clear of the other vehicle. When waiting at the
+    /// stop line, points inside the intersection do not count, so the vehicle holds
+    /// before entering instead of stopping in the middle of it.
+    fn find_non_colliding_index(
         vehicle: &Vehicle,
-        path: &Vec<TimedPosition>,
+        path: &[TimedPosition],
         other_vehicle_rect: &Rect,
-    ) -> Position {
-        let mut temp_rect = vehicle.rect.clone();
+        wait_at_stop_line: bool,
+        iterations: &mut u64,
+    ) -> usize {
+        let mut temp_rect = vehicle.rect;
         for path_index in (0..path.len()).rev() {
-            temp_rect.set_x(path[path_index].position.x);
-            temp_rect.set_y(path[path_index].position.y);
+            *iterations += 1;
+            let position = path[path_index].position;
+            temp_rect.set_x(position.x);
+            temp_rect.set_y(position.y);
+            if wait_at_stop_line && position.is_in_intersection(vehicle.rect.width()) {
+                continue;
+            }
             if !other_vehicle_rect.has_intersection(temp_rect) {
-                return path[path_index].position;
+                return path_index;
             }
         }
         if path.is_empty() {
             panic!("Error: Path is empty, cannot find non-colliding position.");
         } else {
-            path[0].position
+            0
         }
     }
 }
@@ -3,4 +3,5 @@ pub mod path_calculator;
 pub mod collision_detector;
 pub mod collision_resolver;
 
-pub use vehicle_data::Vehicle;
+pub use collision_detector::{AlwaysRelevant, RelevanceStrategy, RouteRelevance};
+pub use vehicle_data::{PassageState, RoadConditions, Vehicle, VehicleTraits};
@@ -1,6 +1,11 @@
 use crate::constants::*;
-use crate::core::vehicle_data::Vehicle;
+use crate::core::collision_detector::RelevanceStrategy;
+use crate::core::vehicle_data::{RoadConditions, Vehicle};
+use crate::direction::{Direction, TurnDirection};
+use crate::geometry::arc::turn_arc;
+use crate::geometry::layout::world_size;
 use crate::geometry::position::{Position, TimedPosition};
+use crate::intersection::{Controller, IntersectionBounds};
 
 pub struct PathCalculator;
 
@@ -8,15 +13,15 @@ impl PathCalculator {
     pub fn calculate_path(
         vehicle: &Vehicle,
         start_position: &Position,
-        all_vehicles: &Vec<Vehicle>,
+        all_vehicles: &[Vehicle],
+        conditions: &RoadConditions,
+        iterations: &mut u64,
     ) -> Vec<TimedPosition> {
-        let mut temp_rect = vehicle.rect.clone();
-        let mut time = if all_vehicles.is_empty() || all_vehicles[0].path.is_empty() {
-            1
-        } else {
-            all_vehicles[0].path[0].time
-        };
-        let mut speed = 2;
+        let (controller, relevance) = (conditions.controller, conditions.relevance);
+        let mut temp_rect = vehicle.rect;
+        let mut time = vehicle.arrival;
+        let mut speed = APPROACH_SPEED;
+        let mut exited = false;
         let mut current_direction = vehicle.start_direction;
         let mut path = Vec::new();
 
@@ -26,14 +31,15 @@ impl PathCalculator {
         temp_rect.set_y(current_position.y);
 
         use crate::geometry::rect_extensions::RectExtensions;
-        while temp_rect.is_in_bounds(WINDOW_SIZE) {
-            current_direction.update_direction(
-                &vehicle.target_direction,
-                &current_position,
-                &vehicle.turn_position,
-            );
+        while temp_rect.is_in_bounds(world_size()) {
+            vehicle.update_direction(&mut current_direction, &current_position);
 
-            current_position = current_position.move_in_direction(&current_direction, speed);
+            let step = if !exited && current_position.is_in_intersection(vehicle.rect.width()) {
+                Self::intersection_step(vehicle.turn_direction, path.len())
+            } else {
+                speed
+            };
+            current_position = vehicle.advance(&current_position, &current_direction, step);
 
             path.push(TimedPosition {
                 position: current_position,
@@ -43,18 +49,53 @@ impl PathCalculator {
             temp_rect.set_x(current_position.x);
             temp_rect.set_y(current_position.y);
 
-            if current_position.is_out_of_intersection() && speed != 3 {
-                speed = 3;
+            if current_position.is_out_of_intersection(vehicle.rect.width()) && !exited {
+                exited = true;
+                speed = vehicle.exit_speed;
             }
 
-            use crate::core::collision_detector::CollisionDetector;
             while time <= path[path.len() - 1].time {
-                let relevant_vehicles: Vec<&Vehicle> = all_vehicles
+                // A closed approach is waited for at the stop line, like crossing traffic.
+                // Right turns go on red: their short path only meets traffic heading for
+                // the same road, which they are still planned around below
+                let held = Self::enters_intersection(vehicle, &path)
+                    && vehicle.turn_direction != TurnDirection::Right
+                    && !Self::may_enter(vehicle, &path, controller, time);
+                // So is a full intersection, for as long as the vehicle would be inside
+                let full = conditions.max_in_intersection.is_some_and(|cap| {
+                    current_position.is_in_intersection(vehicle.rect.width())
+                        && Self::vehicles_inside(all_vehicles, time) >= cap
+                });
+                // In fog, so is crossing traffic the driver can see inside
+                let unsighted = Self::enters_intersection(vehicle, &path)
+                    && Self::sees_crossing_traffic(vehicle, &current_position, all_vehicles, relevance, time);
+                if held || full || unsighted {
+                    use crate::core::collision_resolver::CollisionResolver;
+                    time = CollisionResolver::resolve_collision(
+                        vehicle,
+                        &mut path,
+                        &current_position,
+                        &IntersectionBounds::rect(),
+                        true,
+                        iterations,
+                    );
+
+                    if let Some(pos) = path.iter().position(|tp| tp.time == time) {
+                        path.truncate(pos + 1);
+                    }
+                    current_position = path.iter().find(|tp| tp.time == time).unwrap().position;
+                    temp_rect.set_x(current_position.x);
+                    temp_rect.set_y(current_position.y);
+                    current_direction = vehicle.direction_at(&current_position);
+                }
+
+                let mut relevant_vehicles: Vec<&Vehicle> = all_vehicles
                     .iter()
-                    .filter(|v| {
-                        CollisionDetector::is_relevant_for_collision(vehicle, v, &current_position, &time)
-                    })
+                    .filter(|v| relevance.is_relevant_for_collision(vehicle, v, &current_position, &time))
                     .collect();
+                // Resolve against the vehicle with the strongest right of way first,
+                // so the outcome does not depend on the order of `all_vehicles`
+                relevant_vehicles.sort_by_key(|v| v.right_of_way());
 
                 let mut iter = relevant_vehicles.iter();
                 while let Some(other_vehicle) = iter.next() {
@@ -64,19 +105,33 @@ impl PathCalculator {
                     }
                     let tp = collision_time_position.unwrap();
 
-                    let same_lane = vehicle.initial_position == other_vehicle.initial_position
-                        && vehicle.target_direction == other_vehicle.target_direction;
-                    if !tp.position.is_in_intersection() && !same_lane {
+                    let same_lane = vehicle.shares_lane_with(other_vehicle);
+                    // Out of the intersection, traffic in other lanes keeps to its own, unless
+                    // one of the two is wide enough to overhang into the next road at a corner
+                    let keeps_apart = !same_lane && !vehicle.overhangs_lane() && !other_vehicle.overhangs_lane();
+                    if !tp.position.is_in_intersection(other_vehicle.rect.width()) && keeps_apart {
                         continue;
                     }
-                    if !current_position.is_in_intersection() && !same_lane {
+                    if !current_position.is_in_intersection(vehicle.rect.width()) && keeps_apart {
                         continue;
                     }
+                    // Keep the driver's gap to a vehicle in the same lane, or their margin to crossing traffic
+                    let (space, gap) = if same_lane {
+                        (other_vehicle.lane_space_at(&tp.position, vehicle), vehicle.following_gap)
+                    } else {
+                        let rect = sdl2::rect::Rect::new(
+                            tp.position.x,
+                            tp.position.y,
+                            other_vehicle.rect.width(),
+                            other_vehicle.rect.height(),
+                        );
+                        (rect, vehicle.crossing_gap)
+                    };
                     let vehicle_rect = sdl2::rect::Rect::new(
-                        tp.position.x,
-                        tp.position.y,
-                        other_vehicle.rect.width(),
-                        other_vehicle.rect.height(),
+                        space.x() - gap,
+                        space.y() - gap,
+                        space.width() + 2 * gap as u32,
+                        space.height() + 2 * gap as u32,
                     );
                     if !vehicle_rect.has_intersection(temp_rect) {
                         continue;
@@ -92,7 +147,19 @@ impl PathCalculator {
                     }
 
                     use crate::core::collision_resolver::CollisionResolver;
-                    time = CollisionResolver::resolve_collision(vehicle, &mut path, &current_position, &vehicle_rect);
+                    // Crossing traffic is waited for at the stop line, queues just close up.
+                    // Closing up inside the intersection would move the vehicle through it
+                    // later than planned, into crossing traffic it has not been checked
+                    // against, so it waits at the stop line there too
+                    let wait_at_stop_line = !same_lane || current_position.is_in_intersection(vehicle.rect.width());
+                    time = CollisionResolver::resolve_collision(
+                        vehicle,
+                        &mut path,
+                        &current_position,
+                        &vehicle_rect,
+                        wait_at_stop_line,
+                        iterations,
+                    );
 
                     if let Some(pos) = path.iter().position(|tp| tp.time == time) {
                         path.truncate(pos + 1);
@@ -102,15 +169,592 @@ impl PathCalculator {
                     current_position = path.iter().find(|tp| tp.time == time).unwrap().position;
                     temp_rect.set_x(current_position.x);
                     temp_rect.set_y(current_position.y);
-                    current_direction = if current_position.is_after_turn(&vehicle.turn_position) {
-                        vehicle.target_direction
-                    } else {
-                        vehicle.start_direction
-                    };
+                    current_direction = vehicle.direction_at(&current_position);
                 }
                 time += 1;
             }
         }
+        Self::hold_at_stop_line(vehicle, &start_position, &mut path, all_vehicles);
+        Self::round_turns(vehicle, &mut path, all_vehicles);
         path
     }
+
+    /// Whether the controller lets the vehicle in on `time`, the last tick of `path`. On
+    /// amber it goes on only if, when the light turned, it was already within its point of
+    /// no return, measured along its path to the stop line.
+    fn may_enter(
+        vehicle: &Vehicle,
+        path: &[TimedPosition],
+        controller: &dyn Controller,
+        time: u64,
+    ) -> bool {
+        if controller.may_enter(vehicle.initial_position, time) {
+            return true;
+        }
+        let Some(onset) = controller.amber_since(vehicle.initial_position, time) else {
+            return false;
+        };
+        // Not on the road yet when the light turned, so far enough out to stop
+        let Some(at_onset) = path.iter().position(|tp| tp.time == onset) else {
+            return false;
+        };
+        let distance: u64 = path[at_onset..]
+            .windows(2)
+            .map(|pair| pair[0].position.calculate_steps_to(&pair[1].position))
+            .sum();
+        distance <= vehicle.amber_no_return_distance()
+    }
+
+    /// Waiting is planned by slowing the whole approach down, which keeps the path easy to
+    /// rewind but looks like crawling. Once the path is final, this drives the approach at
+    /// normal speed instead and holds at the stop line, the last point before the
+    /// intersection, until the planned entry. Each point only ever moves further along
+    /// the approach, and not into the gap kept to a vehicle ahead in the same lane, so the
+    /// timing through the intersection is unchanged.
+    fn hold_at_stop_line(
+        vehicle: &Vehicle,
+        start_position: &Position,
+        path: &mut [TimedPosition],
+        all_vehicles: &[Vehicle],
+    ) {
+        let size = vehicle.rect.width();
+        let Some(entry) = path.iter().position(|tp| tp.position.is_in_intersection(size)) else {
+            return;
+        };
+
+        let mut track = vec![*start_position];
+        for tp in &path[..entry] {
+            if track.last() != Some(&tp.position) {
+                track.push(tp.position);
+            }
+        }
+
+        let ahead: Vec<&Vehicle> = all_vehicles
+            .iter()
+            .filter(|other| vehicle.shares_lane_with(other))
+            .collect();
+        let blocked = |position: &Position, time: u64| {
+            let mut rect = vehicle.rect;
+            rect.set_x(position.x);
+            rect.set_y(position.y);
+            ahead.iter().any(|other| {
+                other.path.iter().find(|tp| tp.time == time).is_some_and(|tp| {
+                    let gap = vehicle.following_gap;
+                    let space = other.lane_space_at(&tp.position, vehicle);
+                    sdl2::rect::Rect::new(
+                        space.x() - gap,
+                        space.y() - gap,
+                        space.width() + 2 * gap as u32,
+                        space.height() + 2 * gap as u32,
+                    )
+                    .has_intersection(rect)
+                })
+            })
+        };
+
+        let (mut at, mut planned) = (0, 0);
+        for tp in path[..entry].iter_mut() {
+            while track[planned] != tp.position {
+                planned += 1;
+            }
+            let mut next = at;
+            let mut moved = 0;
+            while next + 1 < track.len() {
+                let step = track[next].calculate_steps_to(&track[next + 1]) as i32;
+                if moved + step > APPROACH_SPEED {
+                    break;
+                }
+                moved += step;
+                next += 1;
+            }
+            // Never behind the plan or backwards, whatever the vehicle ahead does
+            let floor = at.max(planned);
+            next = next.max(floor);
+            while next > floor && blocked(&track[next], tp.time) {
+                next -= 1;
+            }
+            tp.position = track[next];
+            at = next;
+        }
+    }
+
+    /// Turns are planned as a switch from one axis to the other on the turning
+    /// coordinate. Once the path is final, this takes each such corner inside the
+    /// intersection along a quarter circle instead. Every point keeps its distance along
+    /// the path, mapped onto the arc, so the timing of the turn is unchanged. Corners
+    /// where the arc would come too close to another vehicle are left square.
+    fn round_turns(vehicle: &Vehicle, path: &mut [TimedPosition], all_vehicles: &[Vehicle]) {
+        let Some(first) = path.first() else {
+            return;
+        };
+        // Distance along the path of every point, and the distinct points in order
+        let mut track = vec![(0, first.position)];
+        let mut along = Vec::with_capacity(path.len());
+        for tp in path.iter() {
+            let &(distance, last) = track.last().unwrap();
+            if tp.position != last {
+                track.push((distance + last.calculate_steps_to(&tp.position) as i32, tp.position));
+            }
+            along.push(track.last().unwrap().0);
+        }
+
+        let heading = |from: &Position, to: &Position| match (to.x - from.x, to.y - from.y) {
+            (dx, _) if dx > 0 => Direction::Right,
+            (dx, _) if dx < 0 => Direction::Left,
+            (_, dy) if dy > 0 => Direction::Down,
+            _ => Direction::Up,
+        };
+        // Corners as (distance, position, heading in, heading out), lane changes included
+        // so that arcs never run into them
+        let corners: Vec<(i32, Position, Direction, Direction)> = track
+            .windows(3)
+            .filter_map(|points| {
+                let [(_, before), (distance, at), (_, after)] = points else {
+                    return None;
+                };
+                let (entry_dir, exit_dir) = (heading(before, at), heading(at, after));
+                (entry_dir.is_horizontal() != exit_dir.is_horizontal())
+                    .then_some((*distance, *at, entry_dir, exit_dir))
+            })
+            .collect();
+
+        let end = track.last().unwrap().0;
+        let size = vehicle.rect.width();
+        for (index, &(distance, corner, entry_dir, exit_dir)) in corners.iter().enumerate() {
+            if !corner.is_in_intersection(size) {
+                continue;
+            }
+            // Neighbouring corners share the leg between them
+            let leg_in = match index.checked_sub(1) {
+                Some(previous) => (distance - corners[previous].0) / 2,
+                None => distance,
+            };
+            let leg_out = match corners.get(index + 1) {
+                Some(next) => (next.0 - distance) / 2,
+                None => end - distance,
+            };
+            let radius = TURN_ARC_RADIUS.min(leg_in).min(leg_out);
+            if radius < 2 {
+                continue;
+            }
+
+            let entry = corner.move_in_direction(&entry_dir.opposite(), radius);
+            let arc = turn_arc(entry, entry_dir, exit_dir, radius);
+            let rounded: Vec<(usize, Position)> = along
+                .iter()
+                .enumerate()
+                .filter_map(|(index, &at)| {
+                    let into_turn = at - (distance - radius);
+                    (0..=2 * radius).contains(&into_turn).then(|| (index, arc[into_turn as usize]))
+                })
+                .collect();
+            // The corner was planned clear of everyone else, but the arc cuts across it,
+            // so a turn that would come too close to another vehicle stays square
+            if rounded
+                .iter()
+                .all(|&(index, position)| Self::is_clear(vehicle, position, path[index].time, all_vehicles))
+            {
+                for (index, position) in rounded {
+                    path[index].position = position;
+                }
+            }
+        }
+    }
+
+    /// How many of `all_vehicles` are planned to be in the intersection on `time`.
+    fn vehicles_inside(all_vehicles: &[Vehicle], time: u64) -> usize {
+        all_vehicles
+            .iter()
+            .filter(|other| {
+                other
+                    .path
+                    .binary_search_by_key(&time, |tp| tp.time)
+                    .is_ok_and(|index| other.path[index].position.is_in_intersection(other.rect.width()))
+            })
+            .count()
+    }
+
+    /// Whether the vehicle at `position` on `time` has its sight limited, as in fog, and
+    /// can see traffic inside the intersection whose route may cross its own. A driver
+    /// who cannot see across the intersection waits for what it can see to clear rather
+    /// than trusting the gap behind it.
+    fn sees_crossing_traffic(
+        vehicle: &Vehicle,
+        position: &Position,
+        all_vehicles: &[Vehicle],
+        relevance: &dyn RelevanceStrategy,
+        time: u64,
+    ) -> bool {
+        let Some(sight_range) = vehicle.sight_range else {
+            return false;
+        };
+        all_vehicles
+            .iter()
+            .filter(|other| !vehicle.shares_lane_with(other))
+            .filter(|other| relevance.is_relevant_for_collision(vehicle, other, position, &time))
+            .any(|other| {
+                other
+                    .path
+                    .binary_search_by_key(&time, |tp| tp.time)
+                    .is_ok_and(|index| {
+                        let at = other.path[index].position;
+                        at.is_in_intersection(other.rect.width())
+                            && position.calculate_steps_to(&at) <= sight_range as u64
+                    })
+            })
+    }
+
+    /// Whether the vehicle at `position` on `time` keeps its gap to every other vehicle.
+    fn is_clear(vehicle: &Vehicle, position: Position, time: u64, all_vehicles: &[Vehicle]) -> bool {
+        let mut rect = vehicle.rect;
+        rect.set_x(position.x);
+        rect.set_y(position.y);
+        all_vehicles.iter().all(|other| {
+            let Ok(index) = other.path.binary_search_by_key(&time, |tp| tp.time) else {
+                return true;
+            };
+            let at = other.path[index].position;
+            let gap = if vehicle.shares_lane_with(other) {
+                vehicle.following_gap
+            } else {
+                vehicle.crossing_gap
+            };
+            !sdl2::rect::Rect::new(
+                at.x - gap,
+                at.y - gap,
+                other.rect.width() + 2 * gap as u32,
+                other.rect.height() + 2 * gap as u32,
+            )
+            .has_intersection(rect)
+        })
+    }
+
+    /// Pixels moved on one tick inside the intersection. Tighter turns are taken slower:
+    /// straight through at full speed, right turns at three quarters and left turns and
+    /// U-turns at half. Fractional speeds alternate between step sizes.
+    fn intersection_step(turn: TurnDirection, step_index: usize) -> i32 {
+        let pixels_per_two_ticks = match turn {
+            TurnDirection::Straight => 4,
+            TurnDirection::Right => 3,
+            TurnDirection::Left | TurnDirection::UTurn => 2,
+        };
+        pixels_per_two_ticks / 2 + pixels_per_two_ticks % 2 * (step_index % 2) as i32
+    }
+
+    /// Whether the last step of `path` is the one that takes the vehicle into the intersection.
+    fn enters_intersection(vehicle: &Vehicle, path: &[TimedPosition]) -> bool {
+        let size = vehicle.rect.width();
+        match path {
+            [.., before, last] => {
+                last.position.is_in_intersection(size) && !before.position.is_in_intersection(size)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PathCalculator;
+    use crate::geometry::Position;
+    use crate::constants::{COMPACT_VEHICLE_SIZE, LINE_SPACING, TRUCK_VEHICLE_SIZE, VEHICLE_SIZE};
+    use crate::core::{RoadConditions, RouteRelevance, Vehicle, VehicleTraits};
+    use crate::direction::{Direction, TurnDirection};
+    use crate::geometry::spawn::route_lanes;
+    use crate::intersection::{Controller, SmartController, TrafficLight};
+    use crate::weather::Weather;
+    use sdl2::pixels::Color;
+    use sdl2::rect::Rect;
+    use std::collections::HashSet;
+
+    fn traits() -> VehicleTraits {
+        VehicleTraits {
+            size: VEHICLE_SIZE,
+            color: Color::RGB(0, 0, 0),
+            aggression: 0.5,
+            velocity_type: 2,
+        }
+    }
+
+    /// Plans a vehicle straight through from the north, then one straight through from
+    /// the west reaching its stop line as the first is about to cross its lane, returning
+    /// both.
+    fn crossing_pair(weather: Weather) -> (Vehicle, Vehicle) {
+        let closed_lanes = HashSet::new();
+        let conditions = |tick: u64| RoadConditions {
+            weather,
+            controller: &SmartController,
+            relevance: &RouteRelevance,
+            tick,
+            closed_lanes: &closed_lanes,
+            max_in_intersection: None,
+        };
+        let lane = *route_lanes(TurnDirection::Straight).start();
+        let plan = |from: Direction, tick: u64, id: usize, others: &[Vehicle]| {
+            let target = Direction::target_for(from, TurnDirection::Straight);
+            Vehicle::new(from, target, lane, traits(), &conditions(tick), others, id)
+        };
+        let first = plan(Direction::Up, 0, 0, &[]);
+        let second = plan(Direction::Left, 100, 1, std::slice::from_ref(&first));
+        (first, second)
+    }
+
+    #[test]
+    fn in_fog_drivers_wait_for_crossing_traffic_they_can_see() {
+        let (_, clear) = crossing_pair(Weather::Clear);
+        let (first, foggy) = crossing_pair(Weather::Fog);
+        let (clear_entry, _) = clear.intersection_window().expect("it crosses");
+        let (fog_entry, _) = foggy.intersection_window().expect("it crosses");
+
+        assert!(fog_entry > clear_entry, "entered on tick {} in fog, {} in the clear", fog_entry, clear_entry);
+
+        // Nothing the second driver could see was still inside when it went in
+        let sight_range = Weather::Fog.sight_range().expect("fog limits sight") as u64;
+        let entry = foggy.path.iter().find(|tp| tp.time == fog_entry).expect("it enters").position;
+        let first_at = first.path.iter().find(|tp| tp.time == fog_entry).map(|tp| tp.position);
+        assert!(first_at.is_none_or(|at| {
+            !at.is_in_intersection(VEHICLE_SIZE) || entry.calculate_steps_to(&at) > sight_range
+        }));
+    }
+
+    #[test]
+    fn a_lane_changer_moves_over_before_its_turn_without_being_cut_off() {
+        // A long red for the west arm, so the queues in both lanes back up past where
+        // the lane changer moves over
+        let light = TrafficLight::new(1000, 10, 10);
+        let closed_lanes = HashSet::new();
+        let conditions = |tick: u64| RoadConditions {
+            weather: Weather::Clear,
+            controller: &light,
+            relevance: &RouteRelevance,
+            tick,
+            closed_lanes: &closed_lanes,
+            max_in_intersection: None,
+        };
+        let from = Direction::Left;
+        let straight_lane = *route_lanes(TurnDirection::Straight).start();
+        let left_lane = *route_lanes(TurnDirection::Left).start();
+        let [straight, left] = [TurnDirection::Straight, TurnDirection::Left].map(|turn| Direction::target_for(from, turn));
+
+        // Compact cars leave room between lanes, so only a lane change brings them together
+        let compact = VehicleTraits {
+            size: COMPACT_VEHICLE_SIZE,
+            ..traits()
+        };
+        let mut vehicles = Vec::new();
+        let mut plan = |target: Direction, lane: i32, tick: u64| {
+            let vehicle = Vehicle::new(from, target, lane, compact, &conditions(tick), &vehicles, vehicles.len());
+            vehicles.push(vehicle);
+            vehicles.len() - 1
+        };
+        for tick in [0, 30, 60] {
+            plan(straight, straight_lane, tick);
+        }
+        for tick in [15, 45] {
+            plan(left, left_lane, tick);
+        }
+        // Turning left from the straight-through lane, then more left turns behind it
+        let changer = plan(left, straight_lane, 120);
+        for tick in [125, 150, 175] {
+            plan(left, left_lane, tick);
+        }
+
+        let changer = &vehicles[changer];
+        let change = changer.lane_change.expect("it starts in the wrong lane");
+        let stop_line = changer.stop_line.expect("it stops at the light");
+        assert_eq!(stop_line.y, change.to, "in the left-turn lane at the stop line");
+
+        let rect_at = |vehicle: &Vehicle, time: u64| {
+            let point = vehicle.path.iter().find(|point| point.time == time)?;
+            Some(Rect::new(point.position.x, point.position.y, vehicle.rect.width(), vehicle.rect.height()))
+        };
+        for later in &vehicles[changer.id + 1..] {
+            for point in &changer.path {
+                let overlap = rect_at(changer, point.time)
+                    .zip(rect_at(later, point.time))
+                    .is_some_and(|(a, b)| a.has_intersection(b));
+                assert!(!overlap, "vehicle {} ran into the lane changer on tick {}", later.id, point.time);
+            }
+        }
+    }
+
+    /// Tick a lone vehicle from the north going straight through, planned on `tick`,
+    /// enters the intersection under `controller`.
+    fn entry_alone(controller: &dyn Controller, tick: u64) -> u64 {
+        let closed_lanes = HashSet::new();
+        let conditions = RoadConditions {
+            weather: Weather::Clear,
+            controller,
+            relevance: &RouteRelevance,
+            tick,
+            closed_lanes: &closed_lanes,
+            max_in_intersection: None,
+        };
+        let from = Direction::Up;
+        let target = Direction::target_for(from, TurnDirection::Straight);
+        let lane = *route_lanes(TurnDirection::Straight).start();
+        let vehicle = Vehicle::new(from, target, lane, traits(), &conditions, &[], 0);
+        vehicle.intersection_window().expect("it crosses").0
+    }
+
+    #[test]
+    fn at_amber_a_vehicle_too_close_to_stop_goes_on_and_one_further_back_stops() {
+        // Ticks from planning to entering with nothing in the way
+        let approach = entry_alone(&SmartController, 0);
+        let (green, amber, clearance) = (approach + 200, 150, 60);
+        let light = TrafficLight::new(green, amber, clearance);
+        let next_green = 2 * (green + amber + clearance);
+
+        // Amber begins 10 ticks, 20 pixels, before this one would reach the line
+        let close = entry_alone(&light, green + 10 - approach);
+        assert_eq!(close, green + 10, "it carries on through the amber");
+        assert!(light.amber_since(Direction::Up, close).is_some());
+
+        // And 100 ticks, 200 pixels, before this one would, well past its point of no return
+        let far = entry_alone(&light, green + 100 - approach);
+        assert!(far >= next_green, "entered on tick {}, the light is next green on {}", far, next_green);
+    }
+
+    #[test]
+    fn a_lone_right_turner_goes_on_red_without_waiting() {
+        // The west arm's light is red for the first 400 ticks
+        let light = TrafficLight::new(300, 50, 50);
+        let closed_lanes = HashSet::new();
+        let conditions = RoadConditions {
+            weather: Weather::Clear,
+            controller: &light,
+            relevance: &RouteRelevance,
+            tick: 0,
+            closed_lanes: &closed_lanes,
+            max_in_intersection: None,
+        };
+        let from = Direction::Left;
+        let plan = |turn: TurnDirection| {
+            let lane = *route_lanes(turn).start();
+            Vehicle::new(from, Direction::target_for(from, turn), lane, traits(), &conditions, &[], 0)
+        };
+        let waits = |vehicle: &Vehicle| vehicle.path.windows(2).any(|pair| pair[0].position == pair[1].position);
+
+        let right = plan(TurnDirection::Right);
+        assert!(!waits(&right), "the right turn stopped on its way");
+        let (entered, _) = right.intersection_window().expect("it crosses");
+        assert!(entered < 400, "entered on tick {}, after the light went green", entered);
+        assert!(waits(&plan(TurnDirection::Straight)), "straight traffic waits for the green");
+    }
+
+    #[test]
+    fn a_vehicle_without_right_of_way_halts_at_its_stop_line() {
+        let (first, second) = crossing_pair(Weather::Clear);
+        let size = second.rect.width();
+        let stop_line = second.stop_line.expect("it has an approach");
+        let entry = second
+            .path
+            .iter()
+            .position(|tp| tp.position.is_in_intersection(size))
+            .expect("it crosses in the end");
+        assert_eq!(second.path[entry - 1].position, stop_line, "it enters from the stop line");
+
+        // Nothing before its entry gets further along the approach than the stop line
+        let spawn = Position {
+            x: second.rect.x(),
+            y: second.rect.y(),
+        };
+        let reach = spawn.calculate_steps_to(&stop_line);
+        assert!(second.path[..entry].iter().all(|tp| spawn.calculate_steps_to(&tp.position) <= reach));
+
+        // It stands there without moving while the first crosses, which drives straight on
+        let held = |vehicle: &Vehicle, line: Position| vehicle.path.iter().filter(|tp| tp.position == line).count();
+        assert!(held(&second, stop_line) > 1, "held for {} ticks", held(&second, stop_line));
+        assert_eq!(held(&first, first.stop_line.expect("it has an approach")), 1);
+    }
+
+    #[test]
+    fn a_two_car_conflict_takes_few_resolver_steps() {
+        let (first, second) = crossing_pair(Weather::Clear);
+        assert_eq!(first.resolver_steps, 0, "nothing to avoid");
+        // Giving way means holding back once, not searching the path over and over
+        assert!(second.resolver_steps > 0);
+        assert!(
+            second.resolver_steps < second.path.len() as u64,
+            "{} steps for a path of {}",
+            second.resolver_steps,
+            second.path.len()
+        );
+    }
+
+    #[test]
+    fn a_left_turner_takes_more_steps_inside_the_intersection_than_a_straight_through() {
+        let closed_lanes = HashSet::new();
+        let conditions = RoadConditions {
+            weather: Weather::Clear,
+            controller: &SmartController,
+            relevance: &RouteRelevance,
+            tick: 0,
+            closed_lanes: &closed_lanes,
+            max_in_intersection: None,
+        };
+        let from = Direction::Up;
+        let steps_inside = |turn: TurnDirection| {
+            let lane = *route_lanes(turn).start();
+            let target = Direction::target_for(from, turn);
+            let vehicle = Vehicle::new(from, target, lane, traits(), &conditions, &[], 0);
+            let (entry, clear) = vehicle.intersection_window().expect("it crosses");
+            clear - entry
+        };
+        let (left, straight) = (steps_inside(TurnDirection::Left), steps_inside(TurnDirection::Straight));
+        assert!(left > straight, "a left turn inside for {} ticks, straight through for {}", left, straight);
+
+        // Over the same distance a left turn is half as fast, whatever its arc adds
+        let ticks_to_cover = |turn: TurnDirection, distance: i32| {
+            let (mut covered, mut ticks) = (0, 0);
+            while covered < distance {
+                covered += PathCalculator::intersection_step(turn, ticks);
+                ticks += 1;
+            }
+            ticks
+        };
+        assert_eq!(ticks_to_cover(TurnDirection::Straight, 120), 60);
+        assert_eq!(ticks_to_cover(TurnDirection::Left, 120), 120);
+    }
+
+    #[test]
+    fn a_truck_overhangs_its_lane_and_takes_longer_to_clear_the_intersection() {
+        let closed_lanes = HashSet::new();
+        let conditions = RoadConditions {
+            weather: Weather::Clear,
+            controller: &SmartController,
+            relevance: &RouteRelevance,
+            tick: 0,
+            closed_lanes: &closed_lanes,
+            max_in_intersection: None,
+        };
+        let from = Direction::Up;
+        let plan = |turn: TurnDirection, size: u32| {
+            let lane = *route_lanes(turn).start();
+            let traits = VehicleTraits { size, ..traits() };
+            Vehicle::new(from, Direction::target_for(from, turn), lane, traits, &conditions, &[], 0)
+        };
+
+        let truck = plan(TurnDirection::Straight, TRUCK_VEHICLE_SIZE);
+        assert_eq!(truck.rect.width(), TRUCK_VEHICLE_SIZE);
+        assert!(truck.rect.width() > LINE_SPACING as u32, "a truck is wider than a lane");
+
+        // Starting level with a car, its longer front reaches the intersection sooner, so
+        // it takes up the intersection for longer
+        let car = plan(TurnDirection::Straight, VEHICLE_SIZE);
+        let (truck_entry, truck_clear) = truck.intersection_window().expect("it crosses");
+        let (car_entry, car_clear) = car.intersection_window().expect("it crosses");
+        assert!(truck_entry < car_entry, "entered on tick {}, a car on {}", truck_entry, car_entry);
+        assert!(
+            truck_clear - truck_entry > car_clear - car_entry,
+            "inside for {} ticks, a car for {}",
+            truck_clear - truck_entry,
+            car_clear - car_entry
+        );
+
+        // It overhangs the right-turn lane beside it, so follows and is followed by traffic there
+        let beside = plan(TurnDirection::Right, VEHICLE_SIZE);
+        assert!(truck.shares_lane_with(&beside) && beside.shares_lane_with(&truck));
+        assert!(!car.shares_lane_with(&beside));
+    }
 }
@@ -1,45 +1,181 @@
+use crate::constants::*;
+use crate::core::RelevanceStrategy;
 use crate::direction::*;
-use crate::geometry::position::TimedPosition;
+use crate::geometry::spawn::{get_spawn_position, lane_fits, open_route_lanes, route_lanes};
+use crate::geometry::position::{Position, TimedPosition};
+use crate::intersection::Controller;
+use crate::weather::Weather;
 use rand::Rng;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
+use std::collections::{HashSet, VecDeque};
 
-#[derive(Debug, PartialEq)]
+/// A sideways move into the route's lane, made on the approach before the intersection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaneChange {
+    /// Coordinate along the approach where the vehicle moves sideways
+    pub at: i32,
+    /// Cross-axis coordinate of the vehicle before and after the move
+    pub from: i32,
+    pub to: i32,
+}
+
+/// Where a vehicle is in the reservation handshake with the intersection. Its passage
+/// is requested until its path through the intersection is planned, which grants it a
+/// slot, and it keeps the slot until it enters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PassageState {
+    Requested,
+    Granted,
+    Entered,
+}
+
+/// What a vehicle and its driver are like. The manager draws these from its seeded
+/// random generator, so that runs with the same seed have the same vehicles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VehicleTraits {
+    pub size: u32,
+    pub color: Color,
+    /// From 0 for the most cautious driver to 1 for the most aggressive.
+    pub aggression: f32,
+    pub velocity_type: i32,
+}
+
+impl VehicleTraits {
+    pub fn random(rng: &mut impl Rng) -> Self {
+        Self {
+            size: VEHICLE_SIZE_CLASSES[rng.gen_range(0..VEHICLE_SIZE_CLASSES.len())],
+            color: Color::RGB(rng.gen(), rng.gen(), rng.gen()),
+            aggression: rng.gen_range(0.0..=1.0),
+            velocity_type: rng.gen_range(1..=3),
+        }
+    }
+}
+
+/// What a new vehicle plans its path against, besides the other vehicles.
+pub struct RoadConditions<'a> {
+    pub weather: Weather,
+    pub controller: &'a dyn Controller,
+    pub relevance: &'a dyn RelevanceStrategy,
+    // Current simulation tick, at which the vehicle's path starts
+    pub tick: u64,
+    // Approach lanes closed for road works, which the vehicle neither uses nor crosses
+    pub closed_lanes: &'a HashSet<(Direction, i32)>,
+    // Most vehicles allowed in the intersection at once; further ones wait at the stop line
+    pub max_in_intersection: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Vehicle {
     pub id: usize,
     pub rect: Rect,
     pub color: Color,
     pub(crate) initial_position: Direction,
+    // Spawn points of the lane the vehicle starts in and the one it takes through
+    // the intersection, before the vehicle is centered in them
+    pub(crate) lane: Position,
+    pub(crate) route_lane: Position,
+    pub(crate) lane_change: Option<LaneChange>,
     pub(crate) start_direction: Direction,
     pub(crate) target_direction: Direction,
     pub(crate) turn_direction: TurnDirection,
     pub(crate) turn_position: (Option<i32>, Option<i32>),
     pub(crate) path: Vec<TimedPosition>,
+    // Passes of the collision resolver's loops it took to plan the path
+    pub(crate) resolver_steps: u64,
+    // Tick at which the vehicle's path was planned
+    pub(crate) arrival: u64,
+    pub passage_state: PassageState,
+    // Consecutive ticks the vehicle has not moved, other than waiting at its stop line
+    pub(crate) stuck_ticks: u32,
+    // Last point of the path before the intersection, where the vehicle waits its turn
+    pub(crate) stop_line: Option<Position>,
+    // From 0 for the most cautious driver to 1 for the most aggressive, scaling the gaps below
+    pub(crate) aggression: f32,
+    pub(crate) following_gap: i32,
+    // Margin kept around crossing traffic in the intersection
+    pub(crate) crossing_gap: i32,
+    // How far the driver sees in fog, where crossing traffic in sight is waited for
+    pub(crate) sight_range: Option<i32>,
+    // Pixels per tick once out of the intersection
+    pub(crate) exit_speed: i32,
+    // Recent positions, oldest first, at most `TRAIL_LENGTH` of them
+    pub(crate) trail: VecDeque<Position>,
     pub texture_name: String,
+    // Car texture the vehicle is drawn with, picked by the manager from those loaded
     pub texture_index: usize,
+    // Facing in 90 degree steps, and the continuous angle the vehicle is drawn at
     pub rotation: f64,
+    pub heading: f64,
     velocity_type: i32,
 }
 
 impl Vehicle {
+    /// Spawns the vehicle in `lane`, counted from the kerb. If that lane does not serve
+    /// its route, it changes into the nearest open one that does on the approach.
     pub fn new(
         initial_position: Direction,
         target_direction: Direction,
-        size: u32,
-        all_vehicles: &Vec<Vehicle>,
+        lane: i32,
+        traits: VehicleTraits,
+        conditions: &RoadConditions,
+        all_vehicles: &[Vehicle],
         id: usize,
     ) -> Self {
-        use crate::geometry::spawn::get_spawn_position;
         use crate::intersection::turning::get_turning_position;
 
-        let start_position = get_spawn_position(initial_position, target_direction);
-        let color = Self::random_color();
-        let rect = Rect::new(start_position.x, start_position.y, size, size);
-        let turn_direction = Direction::turn_direction(initial_position, target_direction);
+        let size = traits.size;
         let start_direction = initial_position.opposite();
-        let turn_position = get_turning_position(initial_position, target_direction);
-        let mut rng = rand::thread_rng();
-        let texture_index = rng.gen_range(0..3);
+        let lane_offset = (LINE_SPACING - size as i32) / 2;
+        let turn_direction = Direction::turn_direction(initial_position, target_direction);
+        let route_lanes = route_lanes(turn_direction);
+        let mut open_lanes = open_route_lanes(initial_position, turn_direction, Some(lane), conditions.closed_lanes);
+        open_lanes.retain(|&open| lane_fits(size, open));
+        let route_lane = if open_lanes.contains(&lane) {
+            Self::less_congested_lane(initial_position, lane, &open_lanes, all_vehicles)
+        } else {
+            // The manager only plans vehicles that have an open lane to go to
+            open_lanes
+                .iter()
+                .copied()
+                .min_by_key(|open| (open - lane).abs())
+                .unwrap_or_else(|| lane.clamp(*route_lanes.start(), *route_lanes.end()))
+        };
+        let route_lane = get_spawn_position(initial_position, route_lane);
+        let lane = get_spawn_position(initial_position, lane);
+        // Start just outside the window whatever the size, so the first step is on screen
+        let mut start_position = lane;
+        if start_direction.is_horizontal() {
+            start_position.y += lane_offset;
+            start_position.x = start_position.x.max(-(size as i32));
+        } else {
+            start_position.x += lane_offset;
+            start_position.y = start_position.y.max(-(size as i32));
+        }
+        let lane_change = (lane != route_lane).then(|| {
+            let change_point =
+                lane.move_in_direction(&start_direction, LINE_SPACING + LANE_CHANGE_DISTANCE);
+            if start_direction.is_horizontal() {
+                LaneChange {
+                    at: change_point.x,
+                    from: lane.y + lane_offset,
+                    to: route_lane.y + lane_offset,
+                }
+            } else {
+                LaneChange {
+                    at: change_point.y,
+                    from: lane.x + lane_offset,
+                    to: route_lane.x + lane_offset,
+                }
+            }
+        });
+        let rect = Rect::new(start_position.x, start_position.y, size, size);
+        let (turn_x, turn_y) = get_turning_position(initial_position, target_direction);
+        let turn_position = (
+            turn_x.map(|x| x + lane_offset),
+            turn_y.map(|y| y + lane_offset),
+        );
+        let arrival = conditions.tick;
         let rotation = match initial_position {
             Direction::Up => 0.0,
             Direction::Right => 90.0,
@@ -47,40 +183,150 @@ impl Vehicle {
             Direction::Left => 270.0,
         };
 
-        let velocity_type = rng.gen_range(1..=3);
+        let caution = 1.0 - traits.aggression;
+        // Half the weather's gap for the most aggressive driver, one and a half for the most cautious
+        let following_gap = ((conditions.weather.following_gap() as f32 * (0.5 + caution)
+            + CAUTIOUS_FOLLOWING_GAP as f32 * caution)
+            .round()) as i32;
+        let crossing_gap =
+            (CAUTIOUS_CROSSING_GAP as f32 * caution).round() as i32 + conditions.weather.crossing_gap();
 
         let mut vehicle = Vehicle {
             id,
             rect,
-            color,
+            color: traits.color,
             initial_position,
+            lane,
+            route_lane,
+            lane_change,
             start_direction,
             target_direction,
             turn_direction,
             turn_position,
             path: Vec::new(),
+            resolver_steps: 0,
+            arrival,
+            passage_state: PassageState::Requested,
+            stuck_ticks: 0,
+            stop_line: None,
+            aggression: traits.aggression,
+            following_gap,
+            crossing_gap,
+            sight_range: conditions.weather.sight_range(),
+            exit_speed: conditions.weather.exit_speed(),
+            trail: VecDeque::with_capacity(TRAIL_LENGTH),
             texture_name: "car".to_string(),
             rotation,
-            texture_index,
-            velocity_type,
+            heading: rotation,
+            texture_index: 0,
+            velocity_type: traits.velocity_type,
         };
 
         use crate::core::path_calculator::PathCalculator;
-        vehicle.path = PathCalculator::calculate_path(&vehicle, &start_position, all_vehicles);
+        let mut resolver_steps = 0;
+        vehicle.path = PathCalculator::calculate_path(
+            &vehicle,
+            &start_position,
+            all_vehicles,
+            conditions,
+            &mut resolver_steps,
+        );
+        vehicle.resolver_steps = resolver_steps;
+        if vehicle.intersection_window().is_some() {
+            vehicle.passage_state = PassageState::Granted;
+        }
+        vehicle.stop_line = vehicle
+            .path
+            .iter()
+            .take_while(|tp| !tp.position.is_in_intersection(size))
+            .last()
+            .map(|tp| tp.position);
 
         vehicle
     }
 
-    fn random_color() -> Color {
-        let mut rng = rand::thread_rng();
-        Color::RGB(
-            rng.gen_range(0..=255),
-            rng.gen_range(0..=255),
-            rng.gen_range(0..=255),
+    /// Moves over to a neighbouring open lane serving the same route when traffic is
+    /// queued just ahead in `lane` and the neighbouring lane is clear further up the road.
+    fn less_congested_lane(
+        initial_position: Direction,
+        lane: i32,
+        route_lanes: &[i32],
+        all_vehicles: &[Vehicle],
+    ) -> i32 {
+        let is_occupied = |lane: i32, cells: i32| {
+            let strip = Self::lane_strip(initial_position, lane, cells * LINE_SPACING);
+            all_vehicles
+                .iter()
+                .any(|v| v.initial_position == initial_position && v.rect.has_intersection(strip))
+        };
+
+        if !is_occupied(lane, LANE_CHANGE_QUEUE_CELLS) {
+            return lane;
+        }
+        [lane - 1, lane + 1]
+            .into_iter()
+            .filter(|neighbour| route_lanes.contains(neighbour))
+            .find(|&neighbour| !is_occupied(neighbour, LANE_CHANGE_CLEAR_CELLS))
+            .unwrap_or(lane)
+    }
+
+    /// The first `length` pixels of a lane, measured from its spawn point.
+    fn lane_strip(initial_position: Direction, lane: i32, length: i32) -> Rect {
+        let spawn = get_spawn_position(initial_position, lane);
+        let end = spawn.move_in_direction(&initial_position.opposite(), length);
+        Rect::new(
+            spawn.x.min(end.x),
+            spawn.y.min(end.y),
+            ((spawn.x - end.x).abs() + LINE_SPACING) as u32,
+            ((spawn.y - end.y).abs() + LINE_SPACING) as u32,
         )
     }
 
     pub fn update_position(&mut self) {
+        let previous = (self.rect.x(), self.rect.y());
+        self.advance_along_path();
+        let position = Position {
+            x: self.rect.x(),
+            y: self.rect.y(),
+        };
+        if self.passage_state == PassageState::Granted && position.is_in_intersection(self.rect.width()) {
+            self.passage_state = PassageState::Entered;
+        }
+        if (position.x, position.y) == previous && Some(position) != self.stop_line {
+            self.stuck_ticks += 1;
+        } else {
+            self.stuck_ticks = 0;
+        }
+        self.record_trail(position);
+    }
+
+    /// Adds `position` to the trail once the vehicle is `TRAIL_SPACING` pixels past the
+    /// last recorded one, dropping the oldest position when the trail is full.
+    fn record_trail(&mut self, position: Position) {
+        if self
+            .trail
+            .back()
+            .is_some_and(|last| last.calculate_steps_to(&position) < TRAIL_SPACING)
+        {
+            return;
+        }
+        if self.trail.len() == TRAIL_LENGTH {
+            self.trail.pop_front();
+        }
+        self.trail.push_back(position);
+    }
+
+    /// Stuck vehicles outside the intersection can be taken off the road without
+    /// leaving anyone mid-conflict.
+    pub fn is_stuck(&self) -> bool {
+        let position = Position {
+            x: self.rect.x(),
+            y: self.rect.y(),
+        };
+        self.stuck_ticks > STUCK_TICK_LIMIT && !position.is_in_intersection(self.rect.width())
+    }
+
+    fn advance_along_path(&mut self) {
         if !self.path.is_empty() {
             let next = self.path.remove(0);
 
@@ -99,12 +345,264 @@ impl Vehicle {
 
             self.rect.set_x(next.position.x);
             self.rect.set_y(next.position.y);
+            self.update_heading();
+        }
+    }
+
+    /// Points the vehicle at its path `HEADING_LOOKAHEAD` pixels ahead. Waiting vehicles
+    /// and vehicles at the end of their path keep their heading.
+    fn update_heading(&mut self) {
+        let (x, y) = (self.rect.x(), self.rect.y());
+        let ahead = self
+            .path
+            .iter()
+            .find(|point| (point.position.x - x).abs() + (point.position.y - y).abs() >= HEADING_LOOKAHEAD)
+            .or(self.path.last());
+
+        if let Some(ahead) = ahead {
+            let (dx, dy) = (ahead.position.x - x, ahead.position.y - y);
+            if dx != 0 || dy != 0 {
+                // Angles run clockwise from facing up, like the snapped rotation
+                self.heading = (dx as f64).atan2(-dy as f64).to_degrees().rem_euclid(360.0);
+            }
+        }
+    }
+
+    /// Distance from the stop line within which the driver carries on through an amber
+    /// light rather than stopping, further out the more aggressive they are.
+    pub fn amber_no_return_distance(&self) -> u64 {
+        (AMBER_NO_RETURN_DISTANCE as f32 * (0.5 + self.aggression)).round() as u64
+    }
+
+    /// Pixels the vehicle moves on its next tick.
+    pub fn next_step(&self) -> i32 {
+        self.path.first().map_or(0, |next| {
+            (next.position.x - self.rect.x()).abs() + (next.position.y - self.rect.y()).abs()
+        })
+    }
+
+    /// Ticks on which the planned path enters the intersection and then clears it.
+    pub fn intersection_window(&self) -> Option<(u64, u64)> {
+        let size = self.rect.width();
+        let entry = self
+            .path
+            .iter()
+            .position(|tp| tp.position.is_in_intersection(size))?;
+        let clear = self.path[entry..]
+            .iter()
+            .find(|tp| !tp.position.is_in_intersection(size))
+            .or(self.path.last())?;
+        Some((self.path[entry].time, clear.time))
+    }
+
+    /// Part of the intersection the remaining path sweeps through, so the space it
+    /// holds shrinks as the vehicle crosses.
+    pub fn reserved_area(&self) -> Option<Rect> {
+        let size = self.rect.width();
+        self.path
+            .iter()
+            .filter(|tp| tp.position.is_in_intersection(size))
+            .map(|tp| Rect::new(tp.position.x, tp.position.y, size, self.rect.height()))
+            .reduce(|area, rect| area.union(rect))
+    }
+
+    /// Right of way between conflicting vehicles: the one that arrived first goes
+    /// first, and vehicles arriving on the same tick go in the order north, east,
+    /// south, west. A lower key has priority and the other vehicle yields.
+    pub fn right_of_way(&self) -> (u64, u8, usize) {
+        (self.arrival, self.initial_position.priority(), self.id)
+    }
+
+    /// Vehicles share a lane when they enter from the same side and use a common lane
+    /// on the approach. U-turners queue in the left-turn lane and lane changers occupy
+    /// every lane from the one they start in to the one they move into, so this is not
+    /// just a route comparison. A vehicle wider than a lane also shares the lanes beside
+    /// it, which it overhangs.
+    pub fn shares_lane_with(&self, other: &Vehicle) -> bool {
+        if self.initial_position != other.initial_position {
+            return false;
+        }
+
+        let (low, high) = self.approach_span();
+        let (other_low, other_high) = other.approach_span();
+        low < other_high && other_low < high
+    }
+
+    /// Whether the vehicle is wider than a lane, and so reaches past its lane's edges.
+    pub fn overhangs_lane(&self) -> bool {
+        self.rect.width() > LINE_SPACING as u32
+    }
+
+    // Cross-axis pixels the vehicle covers on the approach, from the edge of the lowest
+    // lane it uses, less any overhang, to the far edge of the highest plus any overhang
+    fn approach_span(&self) -> (i32, i32) {
+        let (low, high) = self.approach_lanes();
+        let overhang = (self.rect.width() as i32 - LINE_SPACING).max(0) / 2;
+        (low - overhang, high + LINE_SPACING + overhang)
+    }
+
+    // Cross-axis coordinates of the outermost lanes the vehicle uses on the approach
+    fn approach_lanes(&self) -> (i32, i32) {
+        let (lane, route_lane) = (self.across(&self.lane), self.across(&self.route_lane));
+        (lane.min(route_lane), lane.max(route_lane))
+    }
+
+    /// Space the vehicle at `position` holds against `later`, planned after it and
+    /// sharing its lane: itself, and the stretch of road up to any lane change ahead of
+    /// it. That is its own until the change is done, across every lane it uses, so later
+    /// vehicles stay behind rather than wait where it will move over. It is also the
+    /// later vehicle's, in the lane that one moves into, so it does not cut in ahead of
+    /// a vehicle that was planned without it.
+    pub fn lane_space_at(&self, position: &Position, later: &Vehicle) -> Rect {
+        let (width, height) = (self.rect.width(), self.rect.height());
+        let rect = Rect::new(position.x, position.y, width, height);
+        let across = self.across(position);
+        let changed_lane = |change: &LaneChange| {
+            self.is_past(position, change.at) || (self.along(position) == change.at && across == change.to)
+        };
+        let own_change = self
+            .lane_change
+            .filter(|change| !changed_lane(change))
+            .map(|change| self.point(change.at, change.to));
+        let route_lane = later.across(&later.route_lane);
+        let moved_into = (route_lane..route_lane + LINE_SPACING).contains(&across)
+            || (across..across + width as i32).contains(&route_lane);
+        let later_change = later
+            .lane_change
+            .filter(|change| moved_into && !self.is_past(position, change.at))
+            .map(|change| self.point(change.at, across));
+        [own_change, later_change]
+            .into_iter()
+            .flatten()
+            .fold(rect, |space, point| space.union(Rect::new(point.x, point.y, width, height)))
+    }
+
+    // Whether `position` is further along the approach than the coordinate `along`
+    fn is_past(&self, position: &Position, along: i32) -> bool {
+        match self.start_direction {
+            Direction::Right | Direction::Down => self.along(position) > along,
+            Direction::Left | Direction::Up => self.along(position) < along,
+        }
+    }
+
+    fn point(&self, along: i32, across: i32) -> Position {
+        if self.start_direction.is_horizontal() {
+            Position { x: along, y: across }
+        } else {
+            Position { x: across, y: along }
+        }
+    }
+
+    fn along(&self, position: &Position) -> i32 {
+        if self.start_direction.is_horizontal() {
+            position.x
+        } else {
+            position.y
+        }
+    }
+
+    fn across(&self, position: &Position) -> i32 {
+        if self.start_direction.is_horizontal() {
+            position.y
+        } else {
+            position.x
+        }
+    }
+
+    fn lane_change_direction(&self, change: &LaneChange) -> Direction {
+        match (self.start_direction.is_horizontal(), change.to > change.from) {
+            (true, true) => Direction::Down,
+            (true, false) => Direction::Up,
+            (false, true) => Direction::Right,
+            (false, false) => Direction::Left,
+        }
+    }
+
+    /// Updates the direction taken when leaving `position`, covering the lane change
+    /// on the approach as well as the turn.
+    pub fn update_direction(&self, direction: &mut Direction, position: &Position) {
+        if let Some(change) = self.lane_change.filter(|c| self.along(position) == c.at) {
+            let across = self.across(position);
+            if *direction == self.start_direction && across == change.from {
+                *direction = self.lane_change_direction(&change);
+                return;
+            }
+            if *direction == self.lane_change_direction(&change) && across == change.to {
+                *direction = self.start_direction;
+                return;
+            }
+        }
+
+        direction.update_direction(&self.target_direction, position, &self.turn_position);
+    }
+
+    /// Moves `speed` pixels along `direction`, stopping early on any coordinate where
+    /// the route changes direction. A path rewound by the resolver can resume on an
+    /// odd coordinate, and stepping over a turn would leave the vehicle on the wrong road.
+    pub fn advance(&self, position: &Position, direction: &Direction, speed: i32) -> Position {
+        let (from, turn_at) = if direction.is_horizontal() {
+            (position.x, self.turn_position.0)
+        } else {
+            (position.y, self.turn_position.1)
+        };
+        let lane_change_at = self.lane_change.map(|change| {
+            if direction.is_horizontal() == self.start_direction.is_horizontal() {
+                change.at
+            } else {
+                change.to
+            }
+        });
+
+        let forward = matches!(direction, Direction::Right | Direction::Down);
+        let distance = [turn_at, lane_change_at]
+            .into_iter()
+            .flatten()
+            .map(|stop| if forward { stop - from } else { from - stop })
+            .filter(|&distance| distance > 0 && distance < speed)
+            .min()
+            .unwrap_or(speed);
+
+        position.move_in_direction(direction, distance)
+    }
+
+    /// The direction a vehicle moves in when leaving `position` along its route.
+    pub fn direction_at(&self, position: &Position) -> Direction {
+        if let Some(change) = self.lane_change.filter(|c| self.along(position) == c.at) {
+            let across = self.across(position);
+            let (low, high) = (change.from.min(change.to), change.from.max(change.to));
+            if across != change.to && (low..=high).contains(&across) {
+                return self.lane_change_direction(&change);
+            }
+        }
+
+        if self.turn_direction != TurnDirection::UTurn {
+            return if position.is_after_turn(&self.turn_position) {
+                self.target_direction
+            } else {
+                self.start_direction
+            };
+        }
+
+        let on_x = self.turn_position.0.is_some_and(|turn_x| position.x == turn_x);
+        let on_y = self.turn_position.1.is_some_and(|turn_y| position.y == turn_y);
+        let (on_first_turn, on_second_turn) = if self.start_direction.is_horizontal() {
+            (on_x, on_y)
+        } else {
+            (on_y, on_x)
+        };
+
+        if on_second_turn {
+            self.target_direction
+        } else if on_first_turn {
+            self.start_direction.turn_left()
+        } else {
+            self.start_direction
         }
     }
 
-    pub fn is_in_bounds(&self, window_size: u32) -> bool {
+    pub fn is_in_bounds(&self, world_size: (u32, u32)) -> bool {
         use crate::geometry::rect_extensions::RectExtensions;
-        self.rect.is_in_bounds(window_size)
+        self.rect.is_in_bounds(world_size)
     }
 
     #[allow(dead_code)]
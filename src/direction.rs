@@ -1,11 +1,21 @@
-use rand::Rng;
+use crate::geometry::layout::RoadLayout;
 use crate::geometry::Position;
+use serde::Deserialize;
 
-#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq)]
+/// A direction on screen, with y growing downward, so `Up` is towards the top of the
+/// window and the north arm. A vehicle's `initial_position` is the arm it enters from:
+/// one from `Up` appears at the top and drives down. Its current and target directions
+/// are directions of travel: one heading for `Up` drives up and leaves at the top.
+/// Config files name the arms by compass point.
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq, Deserialize)]
 pub enum Direction {
+    #[serde(rename = "north")]
     Up,
+    #[serde(rename = "south")]
     Down,
+    #[serde(rename = "west")]
     Left,
+    #[serde(rename = "east")]
     Right,
 }
 
@@ -14,29 +24,32 @@ pub enum TurnDirection {
     Left,
     Right,
     Straight,
+    UTurn,
 }
 
 impl Direction {
-    pub fn new(exclude: Option<Direction>) -> Direction {
-        let mut rng = rand::thread_rng();
-        loop {
-            let new_direction = match rng.gen_range(0..4) {
-                0 => Direction::Up,
-                1 => Direction::Left,
-                2 => Direction::Down,
-                3 => Direction::Right,
-                _ => unreachable!(),
-            };
-
-            if let Some(exclude_dir) = exclude {
-                if new_direction != exclude_dir {
-                    return new_direction;
-                }
-            } else {
-                return new_direction;
-            }
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Left,
+        Direction::Down,
+        Direction::Right,
+    ];
+
+    /// Whether the road on this side of the intersection exists. Vehicles neither
+    /// enter from nor leave towards a closed arm.
+    pub fn is_open(&self) -> bool {
+        RoadLayout::current().closed_arm != Some(*self)
+    }
+
+    pub fn compass_name(&self) -> &'static str {
+        match self {
+            Direction::Up => "North",
+            Direction::Down => "South",
+            Direction::Left => "West",
+            Direction::Right => "East",
         }
     }
+
     pub fn opposite(&self) -> Direction {
         match self {
             Direction::Up => Direction::Down,
@@ -46,32 +59,78 @@ impl Direction {
         }
     }
 
+    /// The direction on the driver's left, in screen coordinates (y grows downward).
+    pub fn turn_left(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+        }
+    }
+
+    /// Right-of-way rank of traffic entering from this side, lowest first:
+    /// north, then east, south and west.
+    pub fn priority(&self) -> u8 {
+        match self {
+            Direction::Up => 0,
+            Direction::Right => 1,
+            Direction::Down => 2,
+            Direction::Left => 3,
+        }
+    }
+
+    pub fn is_horizontal(&self) -> bool {
+        matches!(self, Direction::Left | Direction::Right)
+    }
+
     pub fn update_direction(
         &mut self,
         target_direction: &Direction,
         position: &Position,
         turn_position: &(Option<i32>, Option<i32>),
     ) {
-        if let Some(turn_x) = turn_position.0 {
-            if *self != *target_direction && position.x == turn_x {
-                *self = *target_direction;
-            }
+        if *self == *target_direction {
+            return;
         }
 
-        if let Some(turn_y) = turn_position.1 {
-            if *self != *target_direction && position.y == turn_y {
-                *self = *target_direction;
-            }
+        // A U-turn first swings left onto the cross axis, then turns back at the
+        // coordinate stored for the other axis.
+        let next_direction = if *self == target_direction.opposite() {
+            self.turn_left()
+        } else {
+            *target_direction
+        };
+
+        let reached_turn = if self.is_horizontal() {
+            turn_position.0.is_some_and(|turn_x| position.x == turn_x)
+        } else {
+            turn_position.1.is_some_and(|turn_y| position.y == turn_y)
+        };
+
+        if reached_turn {
+            *self = next_direction;
+        }
+    }
+
+    /// The target direction reached by taking `turn` after entering from `initial_position`.
+    pub fn target_for(initial_position: Direction, turn: TurnDirection) -> Direction {
+        let start_direction = initial_position.opposite();
+        match turn {
+            TurnDirection::Straight => start_direction,
+            TurnDirection::UTurn => initial_position,
+            TurnDirection::Left => start_direction.turn_left(),
+            TurnDirection::Right => start_direction.turn_left().opposite(),
         }
     }
 
     pub fn turn_direction(initial_position: Direction, target: Direction) -> TurnDirection {
         match (initial_position, target) {
-            // Straight
+            // Leaving back towards the origin
             (Direction::Up, Direction::Up)
             | (Direction::Down, Direction::Down)
             | (Direction::Left, Direction::Left)
-            | (Direction::Right, Direction::Right) => TurnDirection::Straight,
+            | (Direction::Right, Direction::Right) => TurnDirection::UTurn,
 
             // Turning Left
             (Direction::Up, Direction::Left)
@@ -93,3 +152,21 @@ impl Direction {
         }
     }
 }
+
+impl TurnDirection {
+    pub const ALL: [TurnDirection; 4] = [
+        TurnDirection::Left,
+        TurnDirection::Straight,
+        TurnDirection::Right,
+        TurnDirection::UTurn,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TurnDirection::Left => "Left",
+            TurnDirection::Straight => "Straight",
+            TurnDirection::Right => "Right",
+            TurnDirection::UTurn => "U-turn",
+        }
+    }
+}
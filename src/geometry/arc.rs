@@ -0,0 +1,120 @@
+use crate::direction::Direction;
+use crate::geometry::position::Position;
+use std::f64::consts::FRAC_PI_2;
+
+/// A quarter turn of `radius` pixels from `entry`, heading `entry_dir`, round to heading
+/// `exit_dir`, which must be at right angles to it. The arc runs from `entry` to the point
+/// `radius` further along both directions, and each point is one pixel on from the one
+/// before along just one of them, so it has as many steps as the corner it cuts.
+pub fn turn_arc(
+    entry: Position,
+    entry_dir: Direction,
+    exit_dir: Direction,
+    radius: i32,
+) -> Vec<Position> {
+    debug_assert!(entry_dir.is_horizontal() != exit_dir.is_horizontal());
+    let unit = |direction: &Direction| {
+        let step = Position { x: 0, y: 0 }.move_in_direction(direction, 1);
+        (step.x as f64, step.y as f64)
+    };
+    let (forward, sideways) = (unit(&entry_dir), unit(&exit_dir));
+
+    let centre = (
+        entry.x as f64 + sideways.0 * radius as f64,
+        entry.y as f64 + sideways.1 * radius as f64,
+    );
+    let off_circle = |position: &Position| {
+        ((position.x as f64 - centre.0).hypot(position.y as f64 - centre.1) - radius as f64).abs()
+    };
+
+    let samples = 2 * radius.max(1);
+    let mut arc: Vec<Position> = Vec::with_capacity(samples as usize + 1);
+    for sample in 0..=samples {
+        let angle = FRAC_PI_2 * sample as f64 / samples as f64;
+        let ahead = radius as f64 * angle.sin();
+        let across = radius as f64 * (1.0 - angle.cos());
+        let position = Position {
+            x: entry.x + (forward.0 * ahead + sideways.0 * across).round() as i32,
+            y: entry.y + (forward.1 * ahead + sideways.1 * across).round() as i32,
+        };
+        let Some(&last) = arc.last() else {
+            arc.push(position);
+            continue;
+        };
+        if last == position {
+            continue;
+        }
+        if last.x != position.x && last.y != position.y {
+            // Cut the diagonal through whichever corner of it lies nearer the circle
+            let corners = [Position { x: position.x, y: last.y }, Position { x: last.x, y: position.y }];
+            let corner = if off_circle(&corners[0]) <= off_circle(&corners[1]) {
+                corners[0]
+            } else {
+                corners[1]
+            };
+            arc.push(corner);
+        }
+        arc.push(position);
+    }
+    arc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::TURN_ARC_RADIUS;
+    use crate::geometry::spawn::lane_coordinate;
+
+    // Every quarter turn, with the corner where the entry lane meets the exit lane
+    fn turns() -> Vec<(Direction, Direction, Position)> {
+        let mut turns = Vec::new();
+        for entry_dir in Direction::ALL {
+            for exit_dir in [entry_dir.turn_left(), entry_dir.turn_left().opposite()] {
+                let (entry_lane, exit_lane) = (lane_coordinate(entry_dir, 0), lane_coordinate(exit_dir, 1));
+                let corner = if entry_dir.is_horizontal() {
+                    Position { x: exit_lane, y: entry_lane }
+                } else {
+                    Position { x: entry_lane, y: exit_lane }
+                };
+                turns.push((entry_dir, exit_dir, corner));
+            }
+        }
+        turns
+    }
+
+    // How far along `direction` a position lies
+    fn progress(position: Position, direction: Direction) -> i32 {
+        match direction {
+            Direction::Up => -position.y,
+            Direction::Down => position.y,
+            Direction::Left => -position.x,
+            Direction::Right => position.x,
+        }
+    }
+
+    #[test]
+    fn arcs_start_in_the_entry_lane_and_end_in_the_exit_lane() {
+        for (entry_dir, exit_dir, corner) in turns() {
+            let entry = corner.move_in_direction(&entry_dir.opposite(), TURN_ARC_RADIUS);
+            let arc = turn_arc(entry, entry_dir, exit_dir, TURN_ARC_RADIUS);
+            let exit = corner.move_in_direction(&exit_dir, TURN_ARC_RADIUS);
+            assert_eq!(arc.first(), Some(&entry), "{:?} to {:?}", entry_dir, exit_dir);
+            assert_eq!(arc.last(), Some(&exit), "{:?} to {:?}", entry_dir, exit_dir);
+        }
+    }
+
+    #[test]
+    fn arcs_never_turn_back_and_move_a_pixel_at_a_time() {
+        for (entry_dir, exit_dir, corner) in turns() {
+            let entry = corner.move_in_direction(&entry_dir.opposite(), TURN_ARC_RADIUS);
+            let arc = turn_arc(entry, entry_dir, exit_dir, TURN_ARC_RADIUS);
+            for pair in arc.windows(2) {
+                let (from, to) = (pair[0], pair[1]);
+                assert!(progress(to, entry_dir) >= progress(from, entry_dir), "{:?} to {:?}", entry_dir, exit_dir);
+                assert!(progress(to, exit_dir) >= progress(from, exit_dir), "{:?} to {:?}", entry_dir, exit_dir);
+                assert_eq!((to.x - from.x).abs() + (to.y - from.y).abs(), 1, "{:?} then {:?}", from, to);
+            }
+            assert_eq!(arc.len() as i32, 2 * TURN_ARC_RADIUS + 1, "{:?} to {:?}", entry_dir, exit_dir);
+        }
+    }
+}
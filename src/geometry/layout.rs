@@ -0,0 +1,108 @@
+use crate::constants::{DEFAULT_LANES_PER_DIRECTION, WORLD_SIZE};
+use crate::direction::Direction;
+use crate::geometry::Position;
+use std::cell::Cell;
+
+/// How the roads are laid out. A simulation sets it from its config when it is made,
+/// and everything that places, draws or counts lanes reads it from there.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RoadLayout {
+    /// Lanes in each direction of travel on every road.
+    pub lanes_per_direction: i32,
+    /// How far the middle of the intersection sits from the middle of the world, for an
+    /// off-centre junction.
+    pub intersection_offset: (i32, i32),
+    /// Side of the intersection with no road, turning it into a T-intersection.
+    pub closed_arm: Option<Direction>,
+    /// Width and height of the world the roads run across, in simulation coordinates.
+    pub world_size: (u32, u32),
+}
+
+impl RoadLayout {
+    pub const DEFAULT: RoadLayout = RoadLayout {
+        lanes_per_direction: DEFAULT_LANES_PER_DIRECTION,
+        intersection_offset: (0, 0),
+        closed_arm: None,
+        world_size: (WORLD_SIZE, WORLD_SIZE),
+    };
+
+    /// This layout with a world shaped like a `width` by `height` window, so the roads
+    /// run to its edges. The shorter side stays `WORLD_SIZE` across, keeping vehicles and
+    /// lanes the same size, and the longer side grows with the window's aspect.
+    pub fn for_window(self, width: u32, height: u32) -> Self {
+        let (width, height) = (width.max(1) as u64, height.max(1) as u64);
+        // Even, so the intersection stays on whole coordinates in the middle
+        let long_side = |long: u64, short: u64| (WORLD_SIZE as u64 * long / short) as u32 & !1;
+        let world_size = if width >= height {
+            (long_side(width, height), WORLD_SIZE)
+        } else {
+            (WORLD_SIZE, long_side(height, width))
+        };
+        Self { world_size, ..self }
+    }
+
+    /// The layout in use on this thread.
+    pub fn current() -> Self {
+        LAYOUT.with(Cell::get)
+    }
+
+    /// Lays the roads out this way for everything on this thread from now on. Threads
+    /// that plan or draw for a simulation apply its layout before they start.
+    pub fn apply(self) {
+        LAYOUT.with(|layout| layout.set(self));
+    }
+}
+
+impl Default for RoadLayout {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+thread_local! {
+    // Kept per thread so that tests running side by side can each lay out their own roads
+    static LAYOUT: Cell<RoadLayout> = const { Cell::new(RoadLayout::DEFAULT) };
+}
+
+/// Lanes in each direction of travel in the current layout.
+pub fn lanes_per_direction() -> i32 {
+    RoadLayout::current().lanes_per_direction
+}
+
+/// Width and height of the world in the current layout.
+pub fn world_size() -> (u32, u32) {
+    RoadLayout::current().world_size
+}
+
+/// Middle of the intersection in the current layout, where the centre lines cross.
+pub fn road_center() -> Position {
+    let layout = RoadLayout::current();
+    let (x, y) = layout.intersection_offset;
+    let (width, height) = layout.world_size;
+    Position {
+        x: width as i32 / 2 + x,
+        y: height as i32 / 2 + y,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_world_takes_the_window_shape_around_a_fixed_short_side() {
+        let layout = RoadLayout {
+            lanes_per_direction: 2,
+            ..RoadLayout::DEFAULT
+        };
+        assert_eq!(layout.for_window(600, 600).world_size, (WORLD_SIZE, WORLD_SIZE));
+        assert_eq!(layout.for_window(1200, 600).world_size, (2 * WORLD_SIZE, WORLD_SIZE));
+        assert_eq!(layout.for_window(900, 1200).world_size, (WORLD_SIZE, 1066));
+        // Only the world changes with the window
+        assert_eq!(layout.for_window(1200, 600).lanes_per_direction, 2);
+
+        layout.for_window(1200, 600).apply();
+        assert_eq!(world_size(), (2 * WORLD_SIZE, WORLD_SIZE));
+        assert_eq!(road_center(), Position { x: WORLD_SIZE as i32, y: WORLD_SIZE as i32 / 2 });
+    }
+}
@@ -1,3 +1,5 @@
+pub mod arc;
+pub mod layout;
 pub mod position;
 pub mod spawn;
 pub mod rect_extensions;
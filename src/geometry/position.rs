@@ -38,17 +38,17 @@ impl Position {
         false
     }
 
-    pub fn is_in_intersection(&self) -> bool {
+    pub fn is_in_intersection(&self, size: u32) -> bool {
         use crate::intersection::IntersectionBounds;
-        IntersectionBounds::is_position_in_intersection(self)
+        IntersectionBounds::is_position_in_intersection(self, size)
     }
 
     pub fn calculate_steps_to(&self, new_position: &Position) -> u64 {
         ((self.x - new_position.x).abs() + (self.y - new_position.y).abs()) as u64
     }
 
-    pub fn is_out_of_intersection(&self) -> bool {
+    pub fn is_out_of_intersection(&self, size: u32) -> bool {
         use crate::intersection::IntersectionBounds;
-        IntersectionBounds::is_position_out_of_intersection(self)
+        IntersectionBounds::is_position_out_of_intersection(self, size)
     }
 }
@@ -1,56 +1,187 @@
 use crate::constants::*;
-use crate::direction::Direction;
+use crate::direction::{Direction, TurnDirection};
+use crate::geometry::layout::{lanes_per_direction, road_center, world_size};
 use crate::geometry::position::Position;
+use crate::intersection::IntersectionBounds;
+use sdl2::rect::Rect;
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
 
-pub fn get_spawn_position(initial_position: Direction, target_direction: Direction) -> Position {
-    match initial_position {
-        Direction::Up => {
-            let lane = match target_direction {
-                Direction::Right => 7 * LINE_SPACING,
-                Direction::Down => 6 * LINE_SPACING,
-                Direction::Left => 5 * LINE_SPACING,
-                _ => panic!("Invalid target direction"),
-            };
-            Position {
-                x: lane,
-                y: -LINE_SPACING,
-            }
-        }
-        Direction::Left => {
-            let lane = match target_direction {
-                Direction::Right => 9 * LINE_SPACING,
-                Direction::Up => 8 * LINE_SPACING,
-                Direction::Down => 10 * LINE_SPACING,
-                _ => panic!("Invalid target direction"),
-            };
-            Position {
-                x: -LINE_SPACING,
-                y: lane,
-            }
-        }
-        Direction::Down => {
-            let lane = match target_direction {
-                Direction::Right => 10 * LINE_SPACING,
-                Direction::Up => 9 * LINE_SPACING,
-                Direction::Left => 8 * LINE_SPACING,
-                _ => panic!("Invalid target direction"),
-            };
-            Position {
-                x: lane,
-                y: WINDOW_SIZE as i32,
-            }
+/// Cross-axis coordinate of a lane for traffic moving in `travel_direction`.
+/// Lanes are counted from the kerb, so lane 0 is the rightmost one.
+pub fn lane_coordinate(travel_direction: Direction, lane: i32) -> i32 {
+    let center = if travel_direction.is_horizontal() {
+        road_center().y
+    } else {
+        road_center().x
+    };
+    let lanes = lanes_per_direction();
+    match travel_direction {
+        Direction::Down | Direction::Left => center - (lanes - lane) * LINE_SPACING,
+        Direction::Up | Direction::Right => center + (lanes - 1 - lane) * LINE_SPACING,
+    }
+}
+
+/// Lanes a vehicle may approach the intersection in for the given turn. Right turns
+/// keep to the rightmost lane, left turns and U-turns to the leftmost, and straight
+/// traffic uses the lanes in between, or every lane when there are none.
+pub fn route_lanes(turn_direction: TurnDirection) -> RangeInclusive<i32> {
+    let lanes = lanes_per_direction();
+    match turn_direction {
+        TurnDirection::Right => 0..=0,
+        TurnDirection::Left | TurnDirection::UTurn => lanes - 1..=lanes - 1,
+        TurnDirection::Straight if lanes > 2 => 1..=lanes - 2,
+        TurnDirection::Straight => 0..=lanes - 1,
+    }
+}
+
+/// Whether a vehicle `size` pixels wide may use `lane`. One wider than a lane overhangs
+/// the lanes beside it, so it keeps out of the lane next to the centre line, where it
+/// would reach into oncoming traffic.
+pub fn lane_fits(size: u32, lane: i32) -> bool {
+    size <= LINE_SPACING as u32 || lane < lanes_per_direction() - 1
+}
+
+/// Lanes for `turn` from `initial_position` that are not closed. Given the lane a vehicle
+/// starts in, only those it can change into without crossing a closed lane, its own
+/// included, are kept.
+pub fn open_route_lanes(
+    initial_position: Direction,
+    turn: TurnDirection,
+    from: Option<i32>,
+    closed_lanes: &HashSet<(Direction, i32)>,
+) -> Vec<i32> {
+    let is_open = |lane: i32| !closed_lanes.contains(&(initial_position, lane));
+    route_lanes(turn)
+        .filter(|&lane| match from {
+            Some(from) => (from.min(lane)..=from.max(lane)).all(is_open),
+            None => is_open(lane),
+        })
+        .collect()
+}
+
+/// Approach arm and lane nearest to a point on one of the open arms' roads, or `None`
+/// for points off the road or inside the intersection.
+pub fn approach_lane_at(point: Position) -> Option<(Direction, i32)> {
+    let (left, top, right, bottom) = IntersectionBounds::edges();
+    let on_vertical_road = (left..right).contains(&point.x);
+    let on_horizontal_road = (top..bottom).contains(&point.y);
+
+    let (arm, across) = if on_vertical_road && point.y < top {
+        (Direction::Up, point.x)
+    } else if on_vertical_road && point.y >= bottom {
+        (Direction::Down, point.x)
+    } else if on_horizontal_road && point.x < left {
+        (Direction::Left, point.y)
+    } else if on_horizontal_road && point.x >= right {
+        (Direction::Right, point.y)
+    } else {
+        return None;
+    };
+    if !arm.is_open() {
+        return None;
+    }
+
+    let lane = (0..lanes_per_direction())
+        .min_by_key(|&lane| (lane_coordinate(arm.opposite(), lane) + LINE_SPACING / 2 - across).abs())?;
+    Some((arm, lane))
+}
+
+/// Direction traffic travels in at a point on the roads outside the intersection, or
+/// `None` for points off the road or inside the intersection.
+pub fn travel_direction_at(point: Position) -> Option<Direction> {
+    let (left, top, right, bottom) = IntersectionBounds::edges();
+    let on_vertical_road = (left..right).contains(&point.x);
+    let on_horizontal_road = (top..bottom).contains(&point.y);
+    let center = road_center();
+
+    if on_vertical_road && !on_horizontal_road {
+        Some(if point.x < center.x { Direction::Down } else { Direction::Up })
+    } else if on_horizontal_road && !on_vertical_road {
+        Some(if point.y < center.y { Direction::Left } else { Direction::Right })
+    } else {
+        None
+    }
+}
+
+/// Where vehicles from `initial_position` appear, across every lane, with room for the
+/// largest vehicle and a following gap around it. A new vehicle is only placed there
+/// once no vehicle is in it.
+pub fn spawn_area(initial_position: Direction) -> Rect {
+    let gap = CAUTIOUS_FOLLOWING_GAP;
+    // Every vehicle starts at the spawn position along the road, but one wider than a
+    // lane overhangs it on both sides across the road
+    let overhang = (TRUCK_VEHICLE_SIZE as i32 - LINE_SPACING) / 2;
+    let [first, last] = [0, lanes_per_direction() - 1].map(|lane| {
+        let mut position = get_spawn_position(initial_position, lane);
+        if initial_position.is_horizontal() {
+            position.y -= overhang;
+        } else {
+            position.x -= overhang;
         }
-        Direction::Right => {
-            let lane = match target_direction {
-                Direction::Up => 5 * LINE_SPACING,
-                Direction::Left => 6 * LINE_SPACING,
-                Direction::Down => 7 * LINE_SPACING,
-                _ => panic!("Invalid target direction"),
-            };
-            Position {
-                x: WINDOW_SIZE as i32,
-                y: lane,
-            }
+        Rect::new(position.x, position.y, TRUCK_VEHICLE_SIZE, TRUCK_VEHICLE_SIZE)
+    });
+    let area = first.union(last);
+    Rect::new(
+        area.x() - gap,
+        area.y() - gap,
+        area.width() + 2 * gap as u32,
+        area.height() + 2 * gap as u32,
+    )
+}
+
+pub fn get_spawn_position(initial_position: Direction, lane: i32) -> Position {
+    let across = lane_coordinate(initial_position.opposite(), lane);
+    let (width, height) = world_size();
+    match initial_position {
+        Direction::Up => Position {
+            x: across,
+            y: -LINE_SPACING,
+        },
+        Direction::Left => Position {
+            x: -LINE_SPACING,
+            y: across,
+        },
+        Direction::Down => Position {
+            x: across,
+            y: height as i32,
+        },
+        Direction::Right => Position {
+            x: width as i32,
+            y: across,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Middle of `lane` across the road, for vehicles entering from `arm`
+    fn lane_middle(arm: Direction, lane: i32) -> i32 {
+        lane_coordinate(arm.opposite(), lane) + LINE_SPACING / 2
+    }
+
+    #[test]
+    fn approach_lane_at_finds_the_arm_and_lane_clicked() {
+        let (_, top, right, bottom) = IntersectionBounds::edges();
+        let last = lanes_per_direction() - 1;
+        let clicks = [
+            (Position { x: lane_middle(Direction::Up, 0), y: top / 2 }, (Direction::Up, 0)),
+            (Position { x: lane_middle(Direction::Down, last), y: bottom + 10 }, (Direction::Down, last)),
+            (Position { x: 5, y: lane_middle(Direction::Left, 1) }, (Direction::Left, 1)),
+            (Position { x: right, y: lane_middle(Direction::Right, 0) + 3 }, (Direction::Right, 0)),
+        ];
+        for (point, expected) in clicks {
+            assert_eq!(approach_lane_at(point), Some(expected), "click at {:?}", point);
         }
     }
+
+    #[test]
+    fn approach_lane_at_ignores_the_intersection_and_the_grass() {
+        let (left, top, _, _) = IntersectionBounds::edges();
+        assert_eq!(approach_lane_at(road_center()), None);
+        assert_eq!(approach_lane_at(Position { x: left - 1, y: top - 1 }), None);
+        assert_eq!(approach_lane_at(Position { x: 0, y: 0 }), None);
+    }
 }
@@ -1,35 +1,96 @@
 use crate::constants::*;
+use crate::geometry::layout::{lanes_per_direction, road_center};
 use crate::geometry::position::Position;
+use sdl2::rect::Rect;
 
 pub struct IntersectionBounds;
 
 impl IntersectionBounds {
-    pub fn is_position_in_intersection(position: &Position) -> bool {
+    /// The square where the roads cross.
+    pub fn rect() -> Rect {
+        let (left, top, right, bottom) = Self::edges();
+        Rect::new(left, top, (right - left) as u32, (bottom - top) as u32)
+    }
+
+    /// Left, top, right and bottom coordinates of the intersection.
+    pub fn edges() -> (i32, i32, i32, i32) {
+        let center = road_center();
+        let half_road = lanes_per_direction() * LINE_SPACING;
+        (
+            center.x - half_road,
+            center.y - half_road,
+            center.x + half_road,
+            center.y + half_road,
+        )
+    }
+
+    pub fn is_position_in_intersection(position: &Position, size: u32) -> bool {
         let rect_left = position.x;
-        let rect_right = position.x + VEHICLE_SIZE as i32;
+        let rect_right = position.x + size as i32;
         let rect_top = position.y;
-        let rect_bottom = position.y + VEHICLE_SIZE as i32;
+        let rect_bottom = position.y + size as i32;
+        let (left, top, right, bottom) = Self::edges();
 
-        rect_left < INTERSECTION_BOTTOM_RIGHT.x
-            && rect_right > INTERSECTION_TOP_LEFT.x
-            && rect_top < INTERSECTION_BOTTOM_RIGHT.y
-            && rect_bottom > INTERSECTION_TOP_LEFT.y
+        rect_left < right && rect_right > left && rect_top < bottom && rect_bottom > top
     }
 
-    pub fn is_position_out_of_intersection(position: &Position) -> bool {
-        if position.x <= 4 * LINE_SPACING && (5 * LINE_SPACING..=7 * LINE_SPACING).contains(&position.y) {
+    /// Whether a vehicle of `size` at `position` has left the intersection down one of
+    /// the exits. The side of the centre line it is on is judged by its middle, which
+    /// stays in its lane however far a wide vehicle overhangs.
+    pub fn is_position_out_of_intersection(position: &Position, size: u32) -> bool {
+        let (left, top, right, bottom) = Self::edges();
+        let center = road_center();
+        let middle = Position {
+            x: position.x + size as i32 / 2,
+            y: position.y + size as i32 / 2,
+        };
+
+        if position.x <= left - LINE_SPACING && (top..center.y).contains(&middle.y) {
             return true;
         }
-        if position.x >= 11 * LINE_SPACING && (8 * LINE_SPACING..=10 * LINE_SPACING).contains(&position.y) {
+        if position.x >= right && (center.y..bottom).contains(&middle.y) {
             return true;
         }
-        if position.y <= 4 * LINE_SPACING && (8 * LINE_SPACING..=10 * LINE_SPACING).contains(&position.x) {
+        if position.y <= top - LINE_SPACING && (center.x..right).contains(&middle.x) {
             return true;
         }
-        if position.y >= 11 * LINE_SPACING && (5 * LINE_SPACING..=7 * LINE_SPACING).contains(&position.x) {
+        if position.y >= bottom && (left..center.x).contains(&middle.x) {
             return true;
         }
 
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::layout::RoadLayout;
+
+    #[test]
+    fn an_offset_intersection_has_shifted_bounds() {
+        RoadLayout {
+            intersection_offset: (100, -60),
+            ..RoadLayout::DEFAULT
+        }
+        .apply();
+        let half_road = 3 * LINE_SPACING;
+        let (center_x, center_y) = (WORLD_SIZE as i32 / 2 + 100, WORLD_SIZE as i32 / 2 - 60);
+        assert_eq!(
+            IntersectionBounds::edges(),
+            (center_x - half_road, center_y - half_road, center_x + half_road, center_y + half_road)
+        );
+
+        let size = VEHICLE_SIZE;
+        let inside = Position { x: center_x, y: center_y };
+        assert!(IntersectionBounds::is_position_in_intersection(&inside, size));
+        // Just past the shifted right edge, though well inside where a centred one would be
+        let past_right = Position { x: center_x + half_road, y: center_y };
+        assert!(!IntersectionBounds::is_position_in_intersection(&past_right, size));
+        let centred_left = Position { x: WORLD_SIZE as i32 / 2 - half_road, y: center_y };
+        assert!(!IntersectionBounds::is_position_in_intersection(&centred_left, size));
+        // Out on the east exit, below the shifted centre line
+        assert!(IntersectionBounds::is_position_out_of_intersection(&past_right, size));
+        assert!(!IntersectionBounds::is_position_out_of_intersection(&inside, size));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::direction::Direction;
+
+/// Decides when vehicles may enter the intersection. Paths are still planned around
+/// each other, so a controller only adds waiting on top of the reservation algorithm.
+/// Shared with the planning thread, so it has to be thread safe.
+pub trait Controller: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether vehicles approaching from `approach` may enter the intersection on `tick`.
+    /// Right turns do not wait for it, only for conflicting traffic.
+    fn may_enter(&self, approach: Direction, tick: u64) -> bool;
+
+    /// Tick the light for `approach` turned amber, while it is amber on `tick`. Vehicles
+    /// may not enter on amber unless they were too close to stop when it began.
+    fn amber_since(&self, _approach: Direction, _tick: u64) -> Option<u64> {
+        None
+    }
+
+    /// Whether the controller shows signals, which are drawn on the stop lines.
+    fn is_signalled(&self) -> bool {
+        false
+    }
+}
+
+/// The reservation algorithm on its own: the intersection is always open.
+pub struct SmartController;
+
+impl Controller for SmartController {
+    fn name(&self) -> &'static str {
+        "smart"
+    }
+
+    fn may_enter(&self, _approach: Direction, _tick: u64) -> bool {
+        true
+    }
+}
+
+/// Fixed-cycle light: north and south green then amber, all red, east and west green
+/// then amber, all red.
+pub struct TrafficLight {
+    green_ticks: u64,
+    amber_ticks: u64,
+    clearance_ticks: u64,
+}
+
+impl TrafficLight {
+    pub fn new(green_ticks: u64, amber_ticks: u64, clearance_ticks: u64) -> Self {
+        Self {
+            green_ticks,
+            amber_ticks,
+            clearance_ticks,
+        }
+    }
+
+    /// Ticks into the current half cycle on `tick`, if `approach` has that half.
+    fn phase_for(&self, approach: Direction, tick: u64) -> Option<u64> {
+        let half_cycle = self.green_ticks + self.amber_ticks + self.clearance_ticks;
+        let phase = tick % (2 * half_cycle);
+        let horizontal_turn = phase >= half_cycle;
+        (approach.is_horizontal() == horizontal_turn).then_some(phase % half_cycle)
+    }
+}
+
+impl Controller for TrafficLight {
+    fn name(&self) -> &'static str {
+        "traffic light"
+    }
+
+    fn may_enter(&self, approach: Direction, tick: u64) -> bool {
+        self.phase_for(approach, tick)
+            .is_some_and(|phase| phase < self.green_ticks)
+    }
+
+    fn amber_since(&self, approach: Direction, tick: u64) -> Option<u64> {
+        self.phase_for(approach, tick)
+            .filter(|phase| (self.green_ticks..self.green_ticks + self.amber_ticks).contains(phase))
+            .map(|phase| tick - (phase - self.green_ticks))
+    }
+
+    fn is_signalled(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crossing_arms_are_never_green_together_and_all_red_clears_the_box() {
+        let (green, amber, clearance) = (40, 10, 5);
+        let light = TrafficLight::new(green, amber, clearance);
+        let half_cycle = green + amber + clearance;
+        for tick in 0..4 * half_cycle {
+            let north_south = light.may_enter(Direction::Up, tick) || light.may_enter(Direction::Down, tick);
+            let east_west = light.may_enter(Direction::Left, tick) || light.may_enter(Direction::Right, tick);
+            assert!(!(north_south && east_west), "both pairs green on tick {}", tick);
+            // An approach is green, amber or red, never two at once
+            for approach in Direction::ALL {
+                assert!(!(light.may_enter(approach, tick) && light.amber_since(approach, tick).is_some()));
+            }
+        }
+
+        // North and south go green, amber and then all red before east and west get a turn
+        assert!(light.may_enter(Direction::Up, 0) && !light.may_enter(Direction::Left, 0));
+        assert_eq!(light.amber_since(Direction::Down, green + 3), Some(green));
+        for tick in green + amber..half_cycle {
+            assert!(Direction::ALL
+                .into_iter()
+                .all(|approach| !light.may_enter(approach, tick) && light.amber_since(approach, tick).is_none()));
+        }
+        assert!(light.may_enter(Direction::Right, half_cycle) && !light.may_enter(Direction::Up, half_cycle));
+        assert_eq!(light.amber_since(Direction::Left, half_cycle + green), Some(half_cycle + green));
+        assert!(light.may_enter(Direction::Up, 2 * half_cycle));
+    }
+}
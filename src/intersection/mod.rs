@@ -1,4 +1,6 @@
 pub mod turning;
 pub mod bounds;
+pub mod controller;
 
 pub use bounds::IntersectionBounds;
+pub use controller::{Controller, SmartController, TrafficLight};
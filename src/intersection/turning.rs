@@ -1,34 +1,39 @@
-use crate::constants::LINE_SPACING;
-use crate::direction::Direction;
+use crate::direction::{Direction, TurnDirection};
+use crate::geometry::layout::lanes_per_direction;
+use crate::geometry::spawn::lane_coordinate;
 
+/// Returns the coordinate at which a vehicle turns onto its target direction.
+/// Right turns exit into the rightmost lane and left turns into the leftmost.
+///
+/// U-turns need both coordinates: the one on the approach axis is where the
+/// vehicle swings left (the same point as a left turn), the other is where it
+/// turns back into the leftmost outgoing lane.
 pub fn get_turning_position(
     initial_position: Direction,
     target_direction: Direction,
 ) -> (Option<i32>, Option<i32>) {
-    if target_direction == initial_position.opposite() {
-        return (None, None);
-    }
+    let start_direction = initial_position.opposite();
+    let leftmost = lanes_per_direction() - 1;
+    let on_approach_axis = |coordinate: i32| {
+        if start_direction.is_horizontal() {
+            (Some(coordinate), None)
+        } else {
+            (None, Some(coordinate))
+        }
+    };
 
-    match initial_position {
-        Direction::Up => match target_direction {
-            Direction::Right => (None, Some(8 * LINE_SPACING)),
-            Direction::Left => (None, Some(5 * LINE_SPACING)),
-            _ => (None, None),
-        },
-        Direction::Left => match target_direction {
-            Direction::Up => (Some(8 * LINE_SPACING), None),
-            Direction::Down => (Some(5 * LINE_SPACING), None),
-            _ => (None, None),
-        },
-        Direction::Down => match target_direction {
-            Direction::Left => (None, Some(7 * LINE_SPACING)),
-            Direction::Right => (None, Some(10 * LINE_SPACING)),
-            _ => (None, None),
-        },
-        Direction::Right => match target_direction {
-            Direction::Down => (Some(7 * LINE_SPACING), None),
-            Direction::Up => (Some(10 * LINE_SPACING), None),
-            _ => (None, None),
-        },
+    match Direction::turn_direction(initial_position, target_direction) {
+        TurnDirection::Straight => (None, None),
+        TurnDirection::Right => on_approach_axis(lane_coordinate(target_direction, 0)),
+        TurnDirection::Left => on_approach_axis(lane_coordinate(target_direction, leftmost)),
+        TurnDirection::UTurn => {
+            let swing = lane_coordinate(start_direction.turn_left(), leftmost);
+            let back = lane_coordinate(target_direction, leftmost);
+            if start_direction.is_horizontal() {
+                (Some(swing), Some(back))
+            } else {
+                (Some(back), Some(swing))
+            }
+        }
     }
 }
@@ -1,3 +1,5 @@
+mod audio;
+mod config;
 mod constants;
 mod direction;
 mod core;
@@ -5,110 +7,695 @@ mod geometry;
 mod intersection;
 mod rendering;
 mod simulation;
+mod weather;
 
+use audio::AudioEngine;
+use config::{load_config, SimulationConfig};
 use constants::*;
 use direction::*;
-use rendering::{render_stats_modal, RoadRenderer};
-use sdl2::event::Event;
-use sdl2::image::LoadTexture;
-use sdl2::keyboard::Keycode;
-use simulation::VehicleManager;
-use std::time::Instant;
+use geometry::layout::RoadLayout;
+use geometry::spawn::approach_lane_at;
+use geometry::Position;
+use core::AlwaysRelevant;
+use intersection::TrafficLight;
+use rendering::assets::{find_assets_dir, load_car_texture, load_skins};
+use rendering::{
+    render_stats_modal, Decorations, FrameRecorder, Hud, IdLabels, Menu, MenuAction, Minimap,
+    PassageOverlay, PathRenderer, ReservationOverlay, RoadRenderer, RouteLegend,
+    SimulationOptions, Toasts, Trails,
+};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+use sdl2::video::{FullscreenType, WindowPos};
+use simulation::spawn_scheduler::{SpawnScheduler, SpawnSettings};
+use simulation::stress::StressTest;
+use simulation::vehicle_manager::SpawnRejection;
+use simulation::{
+    frame_remainder, FixedTimestep, HeatmapLayer, Phase, Profiler, SimEvent, TraceSource, VehicleManager,
+};
+use std::num::{NonZeroU32, NonZeroU64, NonZeroUsize};
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Shown when spawning by hand while a scenario decides every spawn
+const SCENARIO_SPAWNS: &str = "Spawns follow the scenario";
+
+/// What the window is showing: the start menu, the running simulation, or the
+/// statistics of the simulation that just finished.
+#[derive(Copy, Clone, PartialEq)]
+enum Screen {
+    Menu,
+    Running,
+    Stats,
+}
+
+/// A fresh simulation with the menu's settings on a world shaped like a `window_size`
+/// window, replaying `trace` or playing `scenario` if given, its vehicles drawn with one
+/// of `texture_count` car textures.
+fn new_simulation(
+    audio: &Rc<AudioEngine>,
+    config: &SimulationConfig,
+    options: &SimulationOptions,
+    trace: Option<&String>,
+    scenario: Option<&String>,
+    texture_count: usize,
+    window_size: (u32, u32),
+) -> Result<VehicleManager, String> {
+    // The lane count and seed picked on the menu stand in for the config's
+    let config = &SimulationConfig {
+        lanes_per_direction: options.lanes_per_direction,
+        spawn_seed: options.seed,
+        ..*config
+    };
+    let mut vehicle_manager = VehicleManager::new_with_config(Rc::clone(audio), config);
+    let (width, height) = window_size;
+    vehicle_manager.set_layout(config.layout().for_window(width, height));
+    vehicle_manager.set_texture_count(texture_count);
+    // Planning into heavy traffic can take longer than a frame
+    vehicle_manager.plan_in_background();
+    if let Some(path) = scenario {
+        vehicle_manager.set_trace(TraceSource::scenario_from_file(path)?);
+    } else if let Some(path) = trace {
+        vehicle_manager.set_trace(TraceSource::from_file(path)?);
+    }
+    if options.traffic_light {
+        let (green, amber, clearance) = config.traffic_light.to_ticks();
+        vehicle_manager.set_controller(Box::new(TrafficLight::new(green, amber, clearance)));
+    }
+    if options.always_relevant {
+        vehicle_manager.set_relevance(Box::new(AlwaysRelevant));
+    }
+    vehicle_manager.set_weather(options.weather);
+    vehicle_manager.set_spawn_settings(spawn_settings(config, options));
+    Ok(vehicle_manager)
+}
+
+/// Stops the clock on the run and writes out its statistics and timeline.
+fn end_run(vehicle_manager: &mut VehicleManager) {
+    vehicle_manager.set_end_time();
+    if let Err(e) = vehicle_manager
+        .get_statistics()
+        .export_json(STATS_EXPORT_PATH, vehicle_manager.get_heatmap()) {
+        eprintln!("Warning: failed to write {}: {}", STATS_EXPORT_PATH, e);
+    }
+    if let Err(e) = vehicle_manager.get_timeline().export_csv(TIMELINE_EXPORT_PATH) {
+        eprintln!("Warning: failed to write {}: {}", TIMELINE_EXPORT_PATH, e);
+    }
+}
+
+/// The value given after `name` on the command line, if the flag is there.
+fn flag_value<'a>(args: &'a [String], name: &str) -> Option<&'a String> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|index| args.get(index + 1))
+}
+
+/// The value after `name` parsed as a `T`, or an error naming the flag if it does not
+/// parse.
+fn parse_flag<T: FromStr>(args: &[String], name: &str) -> Result<Option<T>, String> {
+    flag_value(args, name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| format!("invalid {} value: {}", name, value))
+        })
+        .transpose()
+}
+
+/// How random generation spawns, from the config and the interval and seed picked on
+/// the menu.
+fn spawn_settings(config: &SimulationConfig, options: &SimulationOptions) -> SpawnSettings {
+    SpawnSettings {
+        model: config.spawn_model,
+        interval_ms: options.spawn_interval.as_millis() as u64,
+        seed: options.seed,
+        direction_weights: config.direction_weights,
+    }
+}
+
+/// Arm an arrow key spawns from. Keys name the way the vehicle travels on screen, so Up
+/// spawns from the south, while spawns name the arm entered from.
+fn spawn_arm(keycode: Keycode) -> Option<Direction> {
+    match keycode {
+        Keycode::Up => Some(Direction::Down),
+        Keycode::Down => Some(Direction::Up),
+        Keycode::Left => Some(Direction::Right),
+        Keycode::Right => Some(Direction::Left),
+        _ => None,
+    }
+}
 
 pub fn main() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().collect();
+    // `--scenario <file>` drives every spawn from a scenario of `time_ms,from,target` lines
+    let scenario = flag_value(&args, "--scenario");
+    // `--stress <n>` keeps about n vehicles on the road and reports frame-time percentiles
+    let stress_target = parse_flag::<NonZeroUsize>(&args, "--stress")?.map(NonZeroUsize::get);
+    // `--bench <seconds>` runs the simulation without a window and reports its throughput.
+    // With a scenario it plays the scenario instead, failing if any vehicles collide, and
+    // with `--stress` it runs the stress test for that long
+    if let Some(seconds) = parse_flag::<f64>(&args, "--bench")? {
+        let budget = Duration::try_from_secs_f64(seconds)
+            .map_err(|_| format!("invalid --bench value: {}", seconds))?;
+        if let Some(target) = stress_target {
+            simulation::bench::run_stress(target, budget).print();
+            return Ok(());
+        }
+        let Some(path) = scenario else {
+            simulation::bench::run_bench(budget).print();
+            return Ok(());
+        };
+        let report = simulation::bench::run_scenario(TraceSource::scenario_from_file(path)?, budget);
+        report.print();
+        if report.collisions > 0 || !report.finished {
+            return Err(format!("scenario {} failed", path));
+        }
+        return Ok(());
+    }
+
+    // `--config <file>` reads the simulation settings from a JSON file other than
+    // `config.json`. Without either file the defaults are used
+    let config_path = flag_value(&args, "--config").map_or(CONFIG_PATH, String::as_str);
+    let mut config = load_config(config_path)?;
+    // `--compare <seconds>` draws that long a spawn schedule from the config and plays it
+    // without a window under the smart controller and the traffic light, printing a table
+    // of both. `--report <file>` also writes the table, as CSV for a `.csv` file and as
+    // markdown otherwise
+    if let Some(seconds) = parse_flag::<f64>(&args, "--compare")? {
+        if !seconds.is_finite() || seconds <= 0.0 {
+            return Err(format!("invalid --compare value: {}", seconds));
+        }
+        let report = simulation::compare::run_comparison(&config, seconds, COMPARE_TIME_LIMIT);
+        report.print();
+        if let Some(path) = flag_value(&args, "--report") {
+            report.export(path)?;
+        }
+        return Ok(());
+    }
+    // A stress run needs room on the road for its target
+    if let Some(target) = stress_target {
+        config.max_vehicles = config.max_vehicles.max(target);
+    }
+    // `--duration <seconds>` ends a windowed stress run after that long, showing its report
+    let stress_duration = match parse_flag::<f64>(&args, "--duration")? {
+        Some(seconds) => Duration::try_from_secs_f64(seconds)
+            .ok()
+            .filter(|duration| !duration.is_zero())
+            .ok_or_else(|| format!("invalid --duration value: {}", seconds))?,
+        None => STRESS_DURATION,
+    };
+
     let sdl_context = sdl2::init().expect("Failed to initialize SDL2");
     let video_subsystem = sdl_context
         .video()
         .expect("Failed to get SDL2 video subsystem");
     let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
+    // Sound is optional: without an audio device the simulation runs silently
+    let audio = Rc::new(AudioEngine::open(&sdl_context).unwrap_or_else(|e| {
+        eprintln!("Audio disabled: {}", e);
+        AudioEngine::silent()
+    }));
 
     let window = video_subsystem
-        .window("road_intersection", WINDOW_SIZE, WINDOW_SIZE)
+        .window("road_intersection", config.window_size, config.window_size)
         .position_centered()
+        .resizable()
         .build()
         .expect("Failed to create window");
 
-    let mut canvas = window
-        .into_canvas()
-        .build()
-        .expect("Failed to create canvas");
+    // `--vsync` waits for the display's refresh when presenting, on top of the frame cap
+    // The static scenery is drawn once into a texture, which needs render targets
+    let mut canvas_builder = window.into_canvas().target_texture();
+    if args.iter().any(|arg| arg == "--vsync") {
+        canvas_builder = canvas_builder.present_vsync();
+    }
+    let mut canvas = canvas_builder.build().expect("Failed to create canvas");
+    // The world takes the window's shape, its shorter side WORLD_SIZE across: SDL scales
+    // it to fill the window and maps mouse clicks back into it
+    let (width, height) = canvas.window().size();
+    let (world_width, world_height) = config.layout().for_window(width, height).world_size;
+    canvas
+        .set_logical_size(world_width, world_height)
+        .map_err(|e| e.to_string())?;
     let mut event_pump = sdl_context
         .event_pump()
         .expect("Failed to get SDL2 event pump");
 
+    // `--assets <dir>` or `SMART_ROAD_ASSETS` point at the assets when they are not found
+    let assets_override = flag_value(&args, "--assets")
+        .cloned()
+        .or_else(|| std::env::var("SMART_ROAD_ASSETS").ok());
+    let assets = find_assets_dir(assets_override.as_deref());
+
+    // Text is only used by overlays, so the simulation still runs without the font
+    let font_path = assets.join("font.ttf");
     let font = ttf_context
-        .load_font("assets/font.ttf", 14)
-        .map_err(|e| e.to_string())?;
+        .load_font(&font_path, 14)
+        .map_err(|e| {
+            eprintln!(
+                "Warning: failed to load {}: {}, text is disabled",
+                font_path.display(),
+                e
+            )
+        })
+        .ok();
 
     let texture_creator = canvas.texture_creator();
-    let car_textures = [
-        texture_creator.load_texture("assets/cars.png")?,
-        texture_creator.load_texture("assets/cars-4.png")?,
-        texture_creator.load_texture("assets/green-car.png")?,
-    ];
+    // Images in assets/skins replace the bundled cars when there are any
+    let mut car_textures = load_skins(&texture_creator, &assets);
+    if car_textures.is_empty() {
+        car_textures = CAR_SPRITES
+            .iter()
+            .map(|&(file, color)| load_car_texture(&texture_creator, &assets.join(file), color, VEHICLE_SIZE))
+            .collect::<Result<Vec<_>, _>>()?;
+    } else {
+        println!("Loaded {} car skins", car_textures.len());
+    }
 
-    let mut vehicle_manager = VehicleManager::new();
+    // `--trace <file>` replays spawns from a trace of `timestamp,direction,target` lines
+    let trace = flag_value(&args, "--trace");
+    // `--traffic-light` replaces the free-for-all with a fixed-cycle light, as a baseline.
+    // It only picks the menu's initial choice
+    let mut options = SimulationOptions {
+        traffic_light: args.iter().any(|arg| arg == "--traffic-light"),
+        // `--always-relevant` plans every path around every other vehicle, skipping none
+        // by route, to check what the shortcuts leave out
+        always_relevant: args.iter().any(|arg| arg == "--always-relevant"),
+        spawn_interval: config.spawn_interval(),
+        lanes_per_direction: config.lanes_per_direction,
+        seed: config.spawn_seed,
+        ..SimulationOptions::default()
+    };
+    let mut vehicle_manager = new_simulation(
+        &audio,
+        &config,
+        &options,
+        trace,
+        scenario,
+        car_textures.len(),
+        canvas.window().size(),
+    )?;
+    // Settings are picked on the start menu, which needs the font to be shown
+    let mut menu = Menu::new(options, config.lane_counts());
+    let mut screen = if font.is_some() {
+        Screen::Menu
+    } else {
+        Screen::Running
+    };
+    // `--log-events` prints every simulation event as it is drained
+    let log_events = args.iter().any(|arg| arg == "--log-events");
+    // `--fps <n>` caps rendering at n frames per second instead of FRAME_DURATION
+    let frame_duration = match parse_flag::<NonZeroU32>(&args, "--fps")? {
+        Some(fps) => Duration::from_secs(1) / fps.get(),
+        None => FRAME_DURATION,
+    };
+    // `--record-frames <dir>` saves every `--record-every <n>`th frame as a PNG there
+    let mut recorder = match flag_value(&args, "--record-frames") {
+        Some(directory) => {
+            let every = parse_flag::<NonZeroU64>(&args, "--record-every")?.map_or(1, NonZeroU64::get);
+            Some(FrameRecorder::new(directory, every)?)
+        }
+        None => None,
+    };
     let mut random_generation = false;
-    let mut last_random_spawn = Instant::now();
-    let mut show_stats = false;
+    let mut scheduler = SpawnScheduler::new(spawn_settings(&config, &options));
+    let mut stress = stress_target.map(StressTest::new);
+    let mut profiler = Profiler::new();
+    let mut run_started = Instant::now();
+    let mut debug_mode = false;
+    let mut heatmap_layer: Option<HeatmapLayer> = None;
+    let mut color_by_route = false;
+    let mut show_minimap = false;
+    let mut show_ids = false;
+    let mut show_trails = false;
+    let mut decorations = Decorations::new(&texture_creator, config.decoration_density, options.seed)?;
+    let mut background = RoadRenderer::build_background(&mut canvas, &texture_creator, &decorations)?;
+    let mut id_labels = IdLabels::new(&texture_creator);
+    let mut toasts = Toasts::new(&texture_creator);
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    let mut paused = false;
+    // Windowed size and position to go back to when leaving fullscreen
+    let mut windowed_bounds: Option<((u32, u32), (i32, i32))> = None;
+    // Ticks still to run while paused, queued with N
+    let mut manual_steps: u32 = 0;
+    let mut timestep = FixedTimestep::new(SIMULATION_STEP, MAX_STEPS_PER_FRAME);
+    let mut last_frame = Instant::now();
 
     'running: loop {
+        let frame_start = Instant::now();
         for event in event_pump.poll_iter() {
+            let running = screen == Screen::Running;
             match event {
                 Event::Quit { .. } => break 'running,
+                // Some renderers lose what was drawn into textures, for example when the
+                // window goes fullscreen on Direct3D
+                Event::RenderTargetsReset { .. } => {
+                    background = RoadRenderer::build_background(&mut canvas, &texture_creator, &decorations)?;
+                }
+                // The roads are laid out again to run to the edges of the resized window.
+                // Vehicles were planned on the old roads, so a running simulation is cleared
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(width, height),
+                    ..
+                } => {
+                    let layout = RoadLayout::current().for_window(width as u32, height as u32);
+                    if layout != RoadLayout::current() {
+                        let (world_width, world_height) = layout.world_size;
+                        canvas
+                            .set_logical_size(world_width, world_height)
+                            .map_err(|e| e.to_string())?;
+                        if running {
+                            vehicle_manager.set_layout(layout);
+                            toasts.push("Window resized, the road was cleared", TOAST_DURATION);
+                        } else {
+                            // A finished run keeps its statistics on show
+                            layout.apply();
+                        }
+                        decorations = Decorations::new(&texture_creator, config.decoration_density, options.seed)?;
+                        background = RoadRenderer::build_background(&mut canvas, &texture_creator, &decorations)?;
+                    }
+                }
+                Event::MouseButtonDown {
+                    mouse_btn: MouseButton::Left,
+                    x,
+                    y,
+                    ..
+                } if running => {
+                    if scenario.is_some() {
+                        toasts.push(SCENARIO_SPAWNS, TOAST_DURATION);
+                    } else if let Some((direction, lane)) = approach_lane_at(Position { x, y }) {
+                        if let Err(reason) = vehicle_manager.try_spawn_vehicle_in_lane(direction, lane) {
+                            toasts.push(reason.to_string(), TOAST_DURATION);
+                        }
+                    }
+                }
+                Event::MouseButtonDown {
+                    mouse_btn: MouseButton::Right,
+                    x,
+                    y,
+                    ..
+                } if running => {
+                    if let Some((direction, lane)) = approach_lane_at(Position { x, y }) {
+                        match vehicle_manager.toggle_lane(direction, lane) {
+                            Ok(closed) => toasts.push(
+                                format!(
+                                    "Lane {} from the {} {}",
+                                    lane,
+                                    direction.compass_name(),
+                                    if closed { "closed" } else { "reopened" }
+                                ),
+                                TOAST_DURATION,
+                            ),
+                            Err(reason) => toasts.push(reason.to_string(), TOAST_DURATION),
+                        }
+                    }
+                }
                 Event::KeyDown {
                     keycode: Some(keycode),
                     ..
+                } if screen == Screen::Menu && keycode != Keycode::F11 => match menu.handle_key(keycode) {
+                    Some(MenuAction::Start(chosen)) => {
+                        options = chosen;
+                        vehicle_manager = new_simulation(
+                            &audio,
+                            &config,
+                            &options,
+                            trace,
+                            scenario,
+                            car_textures.len(),
+                            canvas.window().size(),
+                        )?;
+                        // The new run may have another lane count and seed, so the
+                        // scenery is laid out again around its roads
+                        decorations = Decorations::new(&texture_creator, config.decoration_density, options.seed)?;
+                        background = RoadRenderer::build_background(&mut canvas, &texture_creator, &decorations)?;
+                        random_generation = options.random_generation && scenario.is_none();
+                        scheduler = SpawnScheduler::new(spawn_settings(&config, &options));
+                        stress = stress_target.map(StressTest::new);
+                        profiler = Profiler::new();
+                        run_started = Instant::now();
+                        paused = false;
+                        screen = Screen::Running;
+                    }
+                    Some(MenuAction::Quit) => break 'running,
+                    None => {}
+                },
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    ..
                 } => match keycode {
                     Keycode::Escape => {
-                        if show_stats {
-                            break 'running;
+                        if screen == Screen::Stats {
+                            // Without the font there is no menu to go back to
+                            if font.is_none() {
+                                break 'running;
+                            }
+                            menu = Menu::new(options, config.lane_counts());
+                            screen = Screen::Menu;
                         } else {
-                            vehicle_manager.set_end_time();
-                            show_stats = true;
+                            end_run(&mut vehicle_manager);
+                            screen = Screen::Stats;
                             random_generation = false;
                         }
                     }
-                    Keycode::Up if !show_stats => vehicle_manager.try_spawn_vehicle(Direction::Up),
-                    Keycode::Down if !show_stats => {
-                        vehicle_manager.try_spawn_vehicle(Direction::Down)
+                    Keycode::Up | Keycode::Down | Keycode::Left | Keycode::Right | Keycode::R
+                        if running && scenario.is_some() =>
+                    {
+                        toasts.push(SCENARIO_SPAWNS, TOAST_DURATION);
+                    }
+                    Keycode::Up | Keycode::Down | Keycode::Left | Keycode::Right if running => {
+                        let direction = spawn_arm(keycode).expect("an arrow key");
+                        let spawned = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            vehicle_manager.spawn_platoon(direction, PLATOON_SIZE)
+                        } else {
+                            vehicle_manager.try_spawn_vehicle(direction)
+                        };
+                        if let Err(reason) = spawned {
+                            toasts.push(reason.to_string(), TOAST_DURATION);
+                        }
+                    }
+                    Keycode::R if running => {
+                        random_generation = !random_generation;
+                        toasts.push(format!("Random generation {}", on_off(random_generation)), TOAST_DURATION);
+                    }
+                    Keycode::D if running => {
+                        debug_mode = !debug_mode;
+                        toasts.push(format!("Debug view {}", on_off(debug_mode)), TOAST_DURATION);
+                    }
+                    Keycode::H if running && keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+                        vehicle_manager.reset_heatmap();
+                        toasts.push("Heatmap reset", TOAST_DURATION);
+                    }
+                    Keycode::H if running => {
+                        heatmap_layer = match heatmap_layer {
+                            None => Some(HeatmapLayer::Visits),
+                            Some(HeatmapLayer::Visits) => Some(HeatmapLayer::Stopped),
+                            Some(HeatmapLayer::Stopped) => None,
+                        };
+                        let shown = match heatmap_layer {
+                            Some(HeatmapLayer::Visits) => "Heatmap: occupancy",
+                            Some(HeatmapLayer::Stopped) => "Heatmap: time stopped",
+                            None => "Heatmap off",
+                        };
+                        toasts.push(shown, TOAST_DURATION);
+                    }
+                    Keycode::W if running => {
+                        vehicle_manager.cycle_weather();
+                        toasts.push(format!("Weather: {:?}", vehicle_manager.get_weather()), TOAST_DURATION);
+                    }
+                    Keycode::C if running => {
+                        color_by_route = !color_by_route;
+                        toasts.push(format!("Colour by route {}", on_off(color_by_route)), TOAST_DURATION);
+                    }
+                    Keycode::M if running => {
+                        show_minimap = !show_minimap;
+                        toasts.push(format!("Minimap {}", on_off(show_minimap)), TOAST_DURATION);
+                    }
+                    Keycode::S => {
+                        audio.set_muted(!audio.is_muted());
+                        toasts.push(format!("Sound {}", on_off(!audio.is_muted())), TOAST_DURATION);
                     }
-                    Keycode::Left if !show_stats => {
-                        vehicle_manager.try_spawn_vehicle(Direction::Left)
+                    Keycode::I if running => {
+                        show_ids = !show_ids;
+                        toasts.push(format!("Vehicle ids {}", on_off(show_ids)), TOAST_DURATION);
                     }
-                    Keycode::Right if !show_stats => {
-                        vehicle_manager.try_spawn_vehicle(Direction::Right)
+                    Keycode::T if running => {
+                        show_trails = !show_trails;
+                        toasts.push(format!("Trails {}", on_off(show_trails)), TOAST_DURATION);
+                    }
+                    Keycode::Z if running => {
+                        vehicle_manager.reset_statistics();
+                        profiler = Profiler::new();
+                        toasts.push("Statistics reset", TOAST_DURATION);
+                    }
+                    Keycode::X if running => {
+                        vehicle_manager.clear();
+                        toasts.push("Cleared all vehicles", TOAST_DURATION);
+                    }
+                    Keycode::F12 => {
+                        let seconds = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map_or(0, |elapsed| elapsed.as_secs());
+                        let path = format!("state-{}.json", seconds);
+                        match vehicle_manager.snapshot().write(&path) {
+                            Ok(()) => toasts.push(format!("State written to {}", path), TOAST_DURATION),
+                            Err(e) => {
+                                eprintln!("Warning: failed to write {}: {}", path, e);
+                                toasts.push(format!("Failed to write {}", path), TOAST_DURATION);
+                            }
+                        }
+                    }
+                    Keycode::F11 => {
+                        let window = canvas.window_mut();
+                        match windowed_bounds.take() {
+                            Some(((width, height), (x, y))) => {
+                                window.set_fullscreen(FullscreenType::Off)?;
+                                window.set_size(width, height).map_err(|e| e.to_string())?;
+                                window.set_position(WindowPos::Positioned(x), WindowPos::Positioned(y));
+                            }
+                            None => {
+                                windowed_bounds = Some((window.size(), window.position()));
+                                window.set_fullscreen(FullscreenType::Desktop)?;
+                            }
+                        }
+                    }
+                    Keycode::Space if running => paused = !paused,
+                    Keycode::N if paused && running => {
+                        manual_steps += if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            10
+                        } else {
+                            1
+                        };
                     }
-                    Keycode::R if !show_stats => random_generation = !random_generation,
                     _ => {}
                 },
                 _ => {}
             }
         }
 
-        if random_generation
-            && Instant::now().duration_since(last_random_spawn) >= VEHICLE_SPAWN_INTERVAL
-        {
-            let direction = Direction::new(None);
-            vehicle_manager.try_spawn_vehicle(direction);
-            last_random_spawn = Instant::now();
-        }
+        profiler.record(Phase::Events, frame_start.elapsed());
+        let rendering = Instant::now();
 
         RoadRenderer::render_background(&mut canvas);
-        RoadRenderer::render_road_surface(&mut canvas);
-        RoadRenderer::render_lane_markers(&mut canvas);
+        RoadRenderer::blit_background(&mut canvas, &background)?;
+        RoadRenderer::render_stop_lines(
+            &mut canvas,
+            vehicle_manager.get_controller(),
+            vehicle_manager.get_tick(),
+        )?;
+        RoadRenderer::render_closed_lanes(&mut canvas, vehicle_manager.get_closed_lanes())?;
 
-        if !show_stats {
-            vehicle_manager.update_vehicles();
+        profiler.record(Phase::Render, rendering.elapsed());
+        let now = Instant::now();
+        let steps = timestep.advance(now.duration_since(last_frame));
+        last_frame = now;
+
+        let steps = if paused {
+            std::mem::take(&mut manual_steps)
+        } else {
+            steps
+        };
+
+        let updating = Instant::now();
+        if screen == Screen::Running {
+            for _ in 0..steps {
+                if let Some(stress) = &stress {
+                    stress.top_up(&mut vehicle_manager);
+                }
+                if random_generation {
+                    for direction in scheduler.due() {
+                        match vehicle_manager.try_spawn_vehicle(direction) {
+                            // Arrivals during an arm's cooldown wait for it instead of being lost
+                            Err(SpawnRejection::Cooldown(_)) => scheduler.defer(direction),
+                            // Other rejections are expected while generating, so they are only reported when debugging
+                            Err(reason) if debug_mode => println!("Random spawn skipped: {}", reason),
+                            _ => {}
+                        }
+                    }
+                }
+                vehicle_manager.update_vehicles();
+
+                let events = vehicle_manager.drain_events();
+                if log_events {
+                    for event in &events {
+                        println!("{:?}", event);
+                    }
+                } else if scenario.is_some() {
+                    for event in &events {
+                        if let SimEvent::SpawnDelayed { id, delay } = event {
+                            println!("Scenario spawn of vehicle {} delayed by {} ticks", id, delay);
+                        }
+                    }
+                }
+                // In debug mode, stop on the tick a collision happens so it can be inspected
+                let collided = events
+                    .iter()
+                    .any(|event| matches!(event, SimEvent::Collision { .. }));
+                if debug_mode && collided {
+                    paused = true;
+                    break;
+                }
+            }
+        }
+
+        // Spawning, path calculation and close-call checks are timed inside the update
+        // and kept out of its own time
+        let inside_update = vehicle_manager.take_phase_times();
+        let timed_inside: Duration = [Phase::Spawn, Phase::PathCalculation, Phase::CloseCalls]
+            .into_iter()
+            .map(|phase| inside_update.get(phase))
+            .sum();
+        profiler.record(Phase::Update, updating.elapsed().saturating_sub(timed_inside));
+        profiler.record_all(inside_update);
+        let rendering = Instant::now();
+
+        RoadRenderer::render_queue_bars(&mut canvas, vehicle_manager.get_statistics())?;
+
+        if let Some(layer) = heatmap_layer {
+            RoadRenderer::render_heatmap(&mut canvas, vehicle_manager.get_heatmap(), layer)?;
+        }
+
+        if show_trails {
+            Trails::render(&mut canvas, vehicle_manager.get_vehicles())?;
+        }
+
+        if debug_mode {
+            PathRenderer::render_vehicle_paths(&mut canvas, vehicle_manager.get_vehicles())?;
+            ReservationOverlay::render(
+                &mut canvas,
+                font.as_ref(),
+                vehicle_manager.get_vehicles(),
+                vehicle_manager.get_tick(),
+            )?;
         }
 
+        let statistics = vehicle_manager.get_statistics();
+        let near_miss_ids: Vec<usize> = statistics
+            .recent_close_calls()
+            .iter()
+            .filter(|event| statistics.get_tick() - event.tick < CLOSE_CALL_TINT_TICKS)
+            .flat_map(|event| [event.vehicles.0, event.vehicles.1])
+            .collect();
+
         for vehicle in vehicle_manager.get_vehicles() {
+            let texture = &mut car_textures[vehicle.texture_index];
+            if near_miss_ids.contains(&vehicle.id) {
+                texture.set_color_mod(255, 80, 80);
+            } else if color_by_route {
+                let tint = RouteLegend::route_color(vehicle.turn_direction);
+                texture.set_color_mod(tint.r, tint.g, tint.b);
+            } else {
+                texture.set_color_mod(255, 255, 255);
+            }
             canvas
                 .copy_ex(
-                    &car_textures[vehicle.texture_index],
+                    texture,
                     None,
                     Some(vehicle.rect),
-                    vehicle.rotation,
+                    vehicle.heading,
                     None,
                     false,
                     false,
@@ -116,13 +703,160 @@ pub fn main() -> Result<(), String> {
                 .map_err(|e| e.to_string())?;
         }
 
-        if show_stats {
-            render_stats_modal(&mut canvas, vehicle_manager.get_statistics(), &font)?;
+        if debug_mode {
+            PassageOverlay::render(
+                &mut canvas,
+                font.as_ref(),
+                vehicle_manager.get_vehicles(),
+                &vehicle_manager.requested_passages(),
+                vehicle_manager.get_tick(),
+            )?;
+        }
+
+        RoadRenderer::render_close_calls(&mut canvas, vehicle_manager.get_statistics())?;
+        RoadRenderer::render_weather(
+            &mut canvas,
+            vehicle_manager.get_weather(),
+            vehicle_manager.get_tick(),
+        )?;
+
+        if show_minimap {
+            Minimap::render(&mut canvas, vehicle_manager.get_vehicles())?;
         }
 
+        if let Some(font) = &font {
+            if show_ids {
+                id_labels.render(&mut canvas, font, vehicle_manager.get_vehicles())?;
+            }
+
+            if color_by_route {
+                RouteLegend::render(&mut canvas, font)?;
+            }
+
+            toasts.render(&mut canvas, font)?;
+
+            if paused || debug_mode {
+                Hud::render(
+                    &mut canvas,
+                    font,
+                    vehicle_manager.get_tick(),
+                    vehicle_manager.vehicles_in_intersection().len(),
+                    vehicle_manager.get_statistics().min_time_to_collision(),
+                    paused,
+                )?;
+            }
+            if debug_mode {
+                Hud::render_profile(&mut canvas, font, &profiler.recent())?;
+            }
+
+            match screen {
+                Screen::Menu => menu.render(&mut canvas, font)?,
+                Screen::Stats => {
+                    render_stats_modal(&mut canvas, vehicle_manager.get_statistics(), &profiler.run(), font)?
+                }
+                Screen::Running => {}
+            }
+        }
+
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.capture(&canvas)?;
+        }
         canvas.present();
-        ::std::thread::sleep(FRAME_DURATION);
+        profiler.record(Phase::Render, rendering.elapsed());
+
+        if screen == Screen::Running {
+            let frame_time = frame_start.elapsed();
+            profiler.end_frame();
+            vehicle_manager.record_frame(frame_time);
+            if let Some(stress) = stress.as_mut() {
+                stress.record_frame(frame_time, vehicle_manager.get_vehicles().len());
+                if run_started.elapsed() >= stress_duration {
+                    end_run(&mut vehicle_manager);
+                    stress.report(&vehicle_manager).print();
+                    screen = Screen::Stats;
+                    random_generation = false;
+                }
+            }
+        } else {
+            // The menu and the statistics screen are not part of the run
+            profiler.skip_frame();
+        }
+        ::std::thread::sleep(frame_remainder(frame_duration, frame_start.elapsed()));
+    }
+
+    if let Some(recorder) = recorder {
+        recorder.finish();
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parse_flag_reads_the_value_after_the_flag() {
+        let args = args("smart-road --fps 30 --stress 12");
+        assert_eq!(parse_flag::<u32>(&args, "--fps"), Ok(Some(30)));
+        assert_eq!(parse_flag::<usize>(&args, "--stress"), Ok(Some(12)));
+        assert_eq!(parse_flag::<u32>(&args, "--bench"), Ok(None));
+    }
+
+    #[test]
+    fn parse_flag_names_the_flag_it_cannot_parse() {
+        let args = args("smart-road --fps 0 --bench soon");
+        assert_eq!(
+            parse_flag::<NonZeroU32>(&args, "--fps"),
+            Err("invalid --fps value: 0".to_string())
+        );
+        assert_eq!(
+            parse_flag::<f64>(&args, "--bench"),
+            Err("invalid --bench value: soon".to_string())
+        );
+    }
+
+    #[test]
+    fn a_new_simulation_uses_the_lane_count_and_seed_picked_on_the_menu() {
+        let options = SimulationOptions {
+            lanes_per_direction: 4,
+            seed: 9,
+            ..SimulationOptions::default()
+        };
+        let config = SimulationConfig::default();
+        let audio = Rc::new(AudioEngine::silent());
+        let mut vehicle_manager =
+            new_simulation(&audio, &config, &options, None, None, 1, (WORLD_SIZE, WORLD_SIZE)).expect("nothing to load");
+        assert_eq!(geometry::layout::lanes_per_direction(), 4);
+        assert_eq!(vehicle_manager.toggle_lane(Direction::Left, 3), Ok(true));
+        assert_eq!(spawn_settings(&config, &options).seed, 9);
+    }
+
+    #[test]
+    fn arrow_keys_spawn_vehicles_travelling_the_way_they_point() {
+        let mut vehicle_manager = VehicleManager::new(Rc::new(AudioEngine::silent()));
+        for (keycode, (dx, dy)) in [
+            (Keycode::Up, (0, -1)),
+            (Keycode::Down, (0, 1)),
+            (Keycode::Left, (-1, 0)),
+            (Keycode::Right, (1, 0)),
+        ] {
+            let arm = spawn_arm(keycode).expect("an arrow key");
+            let id = vehicle_manager.try_spawn_on_tick(arm, None).expect("every arm is open");
+            vehicle_manager.update_vehicles();
+            let vehicle = vehicle_manager
+                .get_vehicles()
+                .iter()
+                .find(|vehicle| vehicle.id == id)
+                .expect("planned on this thread");
+            let [first, second] = [&vehicle.path[0], &vehicle.path[1]].map(|point| point.position);
+            let step = ((second.x - first.x).signum(), (second.y - first.y).signum());
+            assert_eq!(step, (dx, dy), "{:?}", keycode);
+        }
+        assert_eq!(spawn_arm(Keycode::R), None);
+    }
+}
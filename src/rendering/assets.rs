@@ -0,0 +1,124 @@
+use sdl2::image::LoadTexture;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, TextureCreator};
+use sdl2::surface::Surface;
+use sdl2::video::WindowContext;
+use std::path::{Path, PathBuf};
+
+const ASSETS_DIR: &str = "assets";
+// Directory inside the assets of user-supplied car images, replacing the bundled cars
+const SKINS_DIR: &str = "skins";
+
+/// Finds the assets directory, so the simulation runs from any working directory.
+/// An `override_dir` from `--assets` or `SMART_ROAD_ASSETS` is used as given. Otherwise
+/// `assets` is looked for in the working directory, next to the executable and the
+/// directories above it (which covers `target/release`), and in the source tree the
+/// binary was built from. Falls back to `assets`, leaving each asset's own fallback
+/// to cover whatever is missing.
+pub fn find_assets_dir(override_dir: Option<&str>) -> PathBuf {
+    if let Some(dir) = override_dir {
+        return PathBuf::from(dir);
+    }
+
+    let executable = std::env::current_exe().ok();
+    let executable_dirs = executable.iter().flat_map(|exe| exe.ancestors().skip(1));
+    std::iter::once(Path::new(""))
+        .chain(executable_dirs)
+        .chain(std::iter::once(Path::new(env!("CARGO_MANIFEST_DIR"))))
+        .map(|dir| dir.join(ASSETS_DIR))
+        .find(|dir| dir.is_dir())
+        .unwrap_or_else(|| PathBuf::from(ASSETS_DIR))
+}
+
+/// Loads a car texture, falling back to a plain generated car when the file is
+/// missing or unreadable so the simulation still runs without the asset set.
+pub fn load_car_texture<'a>(
+    texture_creator: &'a TextureCreator<WindowContext>,
+    path: &Path,
+    fallback_color: Color,
+    size: u32,
+) -> Result<Texture<'a>, String> {
+    match texture_creator.load_texture(path) {
+        Ok(texture) => Ok(texture),
+        Err(e) => {
+            eprintln!(
+                "Warning: failed to load {}: {}, using a generated texture",
+                path.display(),
+                e
+            );
+            let surface = fallback_car_surface(size, fallback_color)?;
+            texture_creator
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Loads every PNG in the skins directory of `assets`, in file name order, each drawn
+/// facing up like the bundled cars. Files that fail to load are skipped with a warning.
+/// Returns no textures when the directory is missing or has no usable images.
+pub fn load_skins<'a>(
+    texture_creator: &'a TextureCreator<WindowContext>,
+    assets: &Path,
+) -> Vec<Texture<'a>> {
+    let Ok(entries) = std::fs::read_dir(assets.join(SKINS_DIR)) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+        })
+        .collect();
+    paths.sort();
+
+    paths
+        .iter()
+        .filter_map(|path| {
+            texture_creator
+                .load_texture(path)
+                .map_err(|e| eprintln!("Warning: skipping skin {}: {}", path.display(), e))
+                .ok()
+        })
+        .collect()
+}
+
+/// A square car body with a dark windshield at the top, matching the sprites
+/// which face up before rotation.
+pub fn fallback_car_surface(size: u32, color: Color) -> Result<Surface<'static>, String> {
+    let mut surface = Surface::new(size, size, PixelFormatEnum::RGBA32)?;
+    let margin = size / 8;
+
+    surface.fill_rect(None, Color::RGBA(0, 0, 0, 0))?;
+    surface.fill_rect(
+        Rect::new(margin as i32, 0, size - 2 * margin, size),
+        color,
+    )?;
+    surface.fill_rect(
+        Rect::new(2 * margin as i32, margin as i32, size - 4 * margin, size / 5),
+        Color::RGB(30, 30, 40),
+    )?;
+
+    Ok(surface)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_fallback_car_is_a_coloured_body_of_the_asked_size() {
+        let color = Color::RGB(200, 40, 40);
+        let surface = fallback_car_surface(40, color).expect("a software surface needs no video");
+        assert_eq!(surface.size(), (40, 40));
+
+        let pitch = surface.pitch() as usize;
+        // RGBA32 keeps each pixel's bytes in red, green, blue, alpha order
+        let pixel = |x: usize, y: usize| surface.with_lock(|pixels| pixels[y * pitch + 4 * x..][..4].to_vec());
+        assert_eq!(pixel(20, 30), [200, 40, 40, 255], "the body");
+        assert_eq!(pixel(0, 20)[3], 0, "clear beside the body");
+        assert_eq!(pixel(20, 6), [30, 30, 40, 255], "the windshield");
+    }
+}
@@ -0,0 +1,173 @@
+use crate::geometry::layout::world_size;
+use crate::intersection::IntersectionBounds;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas, Texture, TextureCreator};
+use sdl2::surface::Surface;
+use sdl2::video::{Window, WindowContext};
+
+// Decorations per 10,000 square pixels of grass at a density of 1
+const PER_AREA: f32 = 1.0 / 10_000.0;
+// Grass left clear beside the road so nothing hangs over the kerb
+const ROAD_MARGIN: i32 = 6;
+const TREE_SIZE: std::ops::RangeInclusive<u32> = 18..=32;
+const BUSH_SIZE: std::ops::RangeInclusive<u32> = 8..=14;
+const TREE_PROBABILITY: f64 = 0.6;
+
+#[derive(Copy, Clone)]
+enum Kind {
+    Tree,
+    Bush,
+}
+
+/// Trees and bushes scattered over the grass of the current layout's world, drawn once
+/// into a texture and copied into the road background between the grass and the road.
+pub struct Decorations<'a> {
+    texture: Texture<'a>,
+}
+
+impl<'a> Decorations<'a> {
+    /// Places the decorations, `density` times the default amount of them. The same
+    /// `seed` places them the same way, so screenshots of a seeded run are reproducible.
+    pub fn new(
+        texture_creator: &'a TextureCreator<WindowContext>,
+        density: f32,
+        seed: u64,
+    ) -> Result<Self, String> {
+        let (width, height) = world_size();
+        let mut surface = Surface::new(width, height, PixelFormatEnum::RGBA32)?;
+        surface.fill_rect(None, Color::RGBA(0, 0, 0, 0))?;
+
+        let mut decorations = place(density, seed);
+        // Lower ones are nearer the viewer, so they are drawn over those behind them
+        decorations.sort_by_key(|(_, rect)| rect.bottom());
+        for (kind, rect) in decorations {
+            match kind {
+                Kind::Tree => draw_tree(&mut surface, rect)?,
+                Kind::Bush => draw_bush(&mut surface, rect)?,
+            }
+        }
+
+        let mut texture = texture_creator
+            .create_texture_from_surface(&surface)
+            .map_err(|e| e.to_string())?;
+        texture.set_blend_mode(BlendMode::Blend);
+        Ok(Self { texture })
+    }
+
+    pub fn render(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        let (width, height) = world_size();
+        canvas.copy(&self.texture, None, Rect::new(0, 0, width, height))
+    }
+}
+
+/// Picks random spots on the grass, away from both roads whether or not an arm is closed.
+fn place(density: f32, seed: u64) -> Vec<(Kind, Rect)> {
+    let (left, top, right, bottom) = IntersectionBounds::edges();
+    let (width, height) = world_size();
+    let (width, height) = (width as i32, height as i32);
+    let road_area = (right - left) * height + (bottom - top) * width - (right - left) * (bottom - top);
+    let count = ((width * height - road_area) as f32 * PER_AREA * density).round() as usize;
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut decorations = Vec::with_capacity(count);
+    while decorations.len() < count {
+        let (kind, size) = if rng.gen_bool(TREE_PROBABILITY) {
+            (Kind::Tree, rng.gen_range(TREE_SIZE))
+        } else {
+            (Kind::Bush, rng.gen_range(BUSH_SIZE))
+        };
+        let x = rng.gen_range(0..width - size as i32);
+        let y = rng.gen_range(0..height - size as i32);
+        let on_vertical_road = x + size as i32 > left - ROAD_MARGIN && x < right + ROAD_MARGIN;
+        let on_horizontal_road = y + size as i32 > top - ROAD_MARGIN && y < bottom + ROAD_MARGIN;
+        if !on_vertical_road && !on_horizontal_road {
+            decorations.push((kind, Rect::new(x, y, size, size)));
+        }
+    }
+    decorations
+}
+
+/// A pine seen from above at an angle: a stepped dark green triangle on a short trunk.
+fn draw_tree(surface: &mut Surface, rect: Rect) -> Result<(), String> {
+    let size = rect.width() as i32;
+    let trunk = (size / 6).max(2);
+    let crown_height = size - trunk;
+    surface.fill_rect(
+        Rect::new(rect.x() + (size - trunk) / 2, rect.y() + crown_height, trunk as u32, trunk as u32),
+        Color::RGB(100, 65, 30),
+    )?;
+
+    // Rows of whole rects widen towards the base; a few rows keep it cheap
+    let steps = 4;
+    for step in 0..steps {
+        let width = size * (step + 1) / steps;
+        let row_top = crown_height * step / steps;
+        let row_height = crown_height * (step + 1) / steps - row_top;
+        surface.fill_rect(
+            Rect::new(
+                rect.x() + (size - width) / 2,
+                rect.y() + row_top,
+                width as u32,
+                row_height as u32,
+            ),
+            Color::RGB(20, 110 - step as u8 * 8, 35),
+        )?;
+    }
+    Ok(())
+}
+
+/// A round-ish bush: a plus of two rects with a lighter patch on top.
+fn draw_bush(surface: &mut Surface, rect: Rect) -> Result<(), String> {
+    let size = rect.width() as i32;
+    let inset = size / 4;
+    let color = Color::RGB(35, 140, 45);
+    surface.fill_rect(
+        Rect::new(rect.x() + inset, rect.y(), (size - 2 * inset) as u32, size as u32),
+        color,
+    )?;
+    surface.fill_rect(
+        Rect::new(rect.x(), rect.y() + inset, size as u32, (size - 2 * inset) as u32),
+        color,
+    )?;
+    surface.fill_rect(
+        Rect::new(rect.x() + inset, rect.y() + inset, inset as u32, inset as u32),
+        Color::RGB(80, 180, 70),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::WORLD_SIZE;
+    use crate::geometry::layout::RoadLayout;
+
+    fn rects(density: f32, seed: u64) -> Vec<Rect> {
+        place(density, seed).into_iter().map(|(_, rect)| rect).collect()
+    }
+
+    #[test]
+    fn placement_follows_the_seed() {
+        assert_eq!(rects(1.0, 7), rects(1.0, 7));
+        assert_ne!(rects(1.0, 7), rects(1.0, 8));
+    }
+
+    #[test]
+    fn decorations_stay_off_the_roads() {
+        // A wide world, so the roads are off centre in neither direction's grass alone
+        RoadLayout::DEFAULT.for_window(1600, 900).apply();
+        let (left, top, right, bottom) = IntersectionBounds::edges();
+        let (width, height) = world_size();
+        let vertical_road = Rect::new(left, 0, (right - left) as u32, height);
+        let horizontal_road = Rect::new(0, top, width, (bottom - top) as u32);
+        let decorations = rects(2.0, 3);
+        assert!(!decorations.is_empty());
+        assert!(decorations.iter().any(|rect| rect.right() > WORLD_SIZE as i32));
+        for rect in decorations {
+            assert!(!rect.has_intersection(vertical_road) && !rect.has_intersection(horizontal_road));
+            assert!(rect.x() >= 0 && rect.y() >= 0 && rect.right() <= width as i32 && rect.bottom() <= height as i32);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use sdl2::image::SaveSurface;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::surface::Surface;
+use sdl2::video::Window;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::thread::{self, JoinHandle};
+
+// Frames that can be waiting for or being written at once. When the writer falls
+// this far behind, frames are dropped instead of holding up the simulation
+const FRAME_BUFFERS: usize = 4;
+const FORMAT: PixelFormatEnum = PixelFormatEnum::ABGR8888;
+
+struct Frame {
+    index: u64,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+/// Saves every `every`th rendered frame as a numbered PNG in a directory, for
+/// assembling into a video afterwards. PNGs are encoded on a background thread.
+pub struct FrameRecorder {
+    every: u64,
+    rendered: u64,
+    captured: u64,
+    dropped: u64,
+    frames: Option<SyncSender<Frame>>,
+    free_buffers: Receiver<Vec<u8>>,
+    writer: Option<JoinHandle<u64>>,
+}
+
+impl FrameRecorder {
+    pub fn new(directory: &str, every: u64) -> Result<Self, String> {
+        std::fs::create_dir_all(directory).map_err(|e| format!("{}: {}", directory, e))?;
+
+        let (frames, pending) = mpsc::sync_channel::<Frame>(FRAME_BUFFERS);
+        let (return_buffer, free_buffers) = mpsc::channel();
+        for _ in 0..FRAME_BUFFERS {
+            return_buffer.send(Vec::new()).map_err(|e| e.to_string())?;
+        }
+
+        let directory = PathBuf::from(directory);
+        let writer = thread::spawn(move || {
+            let mut written = 0;
+            for mut frame in pending {
+                let path = directory.join(format!("frame-{:06}.png", frame.index));
+                let saved = Surface::from_data(
+                    &mut frame.pixels,
+                    frame.width,
+                    frame.height,
+                    frame.width * FORMAT.byte_size_per_pixel() as u32,
+                    FORMAT,
+                )
+                .and_then(|surface| surface.save(&path));
+                match saved {
+                    Ok(()) => written += 1,
+                    Err(e) => eprintln!("Warning: failed to write {}: {}", path.display(), e),
+                }
+                // The recorder may already be gone once it has stopped sending
+                let _ = return_buffer.send(frame.pixels);
+            }
+            written
+        });
+
+        Ok(Self {
+            every: every.max(1),
+            rendered: 0,
+            captured: 0,
+            dropped: 0,
+            frames: Some(frames),
+            free_buffers,
+            writer: Some(writer),
+        })
+    }
+
+    /// Call once per frame, after drawing and before presenting.
+    pub fn capture(&mut self, canvas: &Canvas<Window>) -> Result<(), String> {
+        self.rendered += 1;
+        if !(self.rendered - 1).is_multiple_of(self.every) {
+            return Ok(());
+        }
+        let Some(frames) = &self.frames else {
+            return Ok(());
+        };
+
+        let mut pixels = match self.free_buffers.try_recv() {
+            Ok(pixels) => pixels,
+            Err(TryRecvError::Empty) => {
+                self.dropped += 1;
+                return Ok(());
+            }
+            Err(TryRecvError::Disconnected) => return Err("frame writer stopped".to_string()),
+        };
+
+        let (width, height) = canvas.output_size()?;
+        let pitch = width as usize * FORMAT.byte_size_per_pixel();
+        // Cleared so that letterboxing, which SDL does not read back, comes out black
+        pixels.clear();
+        pixels.resize(pitch * height as usize, 0);
+        // The whole output, matching the buffer. With a logical size SDL reads only the
+        // scaled scene inside it and writes that at its offset in the buffer
+        let output = Rect::new(0, 0, width, height);
+        // SAFETY: the buffer holds `pitch * height` bytes for the `width` by `height`
+        // rect, and SDL writes no more than the part of that rect it reads
+        let result = unsafe {
+            sdl2::sys::SDL_RenderReadPixels(
+                canvas.raw(),
+                output.raw(),
+                FORMAT as u32,
+                pixels.as_mut_ptr() as *mut std::ffi::c_void,
+                pitch as i32,
+            )
+        };
+        if result != 0 {
+            return Err(sdl2::get_error());
+        }
+
+        frames
+            .send(Frame {
+                index: self.captured,
+                width,
+                height,
+                pixels,
+            })
+            .map_err(|e| e.to_string())?;
+        self.captured += 1;
+        Ok(())
+    }
+
+    /// Waits for the frames still being written and prints how many were saved.
+    pub fn finish(mut self) {
+        self.frames = None;
+        let written = self
+            .writer
+            .take()
+            .map_or(0, |writer| writer.join().unwrap_or(0));
+        println!(
+            "Recorded {} frames, {} dropped while the writer caught up",
+            written, self.dropped
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use crate::constants::PROFILE_WINDOW;
+use crate::simulation::profiler::PhaseTiming;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, TextureQuery};
+use sdl2::ttf::Font;
+use sdl2::video::Window;
+
+const MARGIN: i32 = 10;
+
+pub struct Hud;
+
+impl Hud {
+    /// Shows the simulation tick, how many vehicles are in the intersection and the
+    /// soonest predicted collision in the bottom-left corner, and whether the simulation
+    /// is paused.
+    pub fn render(
+        canvas: &mut Canvas<Window>,
+        font: &Font,
+        tick: u64,
+        in_intersection: usize,
+        time_to_collision: Option<f32>,
+        paused: bool,
+    ) -> Result<(), String> {
+        let mut text = format!("Tick {}, {} in intersection", tick, in_intersection);
+        if let Some(seconds) = time_to_collision {
+            text.push_str(&format!(", TTC {:.2} s", seconds));
+        }
+        if paused {
+            text.push_str(" (paused, N to step)");
+        }
+        let (_, window_height) = canvas.logical_size();
+        render_line(canvas, font, &text, window_height as i32 - MARGIN, Color::RGB(255, 255, 255))
+    }
+
+    /// Lists the average and worst time per frame of each phase over the profiler's
+    /// window, above the line drawn by `render`.
+    pub fn render_profile(canvas: &mut Canvas<Window>, font: &Font, timings: &[PhaseTiming]) -> Result<(), String> {
+        let ms = |time: std::time::Duration| time.as_secs_f64() * 1000.0;
+        let mut lines = vec![format!("Last {} frames, avg / worst", PROFILE_WINDOW)];
+        lines.extend(timings.iter().map(|timing| {
+            format!(
+                "{}: {:.2} / {:.2} ms",
+                timing.phase.name(),
+                ms(timing.average),
+                ms(timing.worst)
+            )
+        }));
+
+        let (_, window_height) = canvas.logical_size();
+        let line_height = font.height();
+        // Bottom of the first line, leaving the last clear of the line drawn by `render`
+        let mut bottom = window_height as i32 - MARGIN - line_height - 4 - (lines.len() as i32 - 1) * line_height;
+        for line in &lines {
+            render_line(canvas, font, line, bottom, Color::RGB(200, 220, 255))?;
+            bottom += line_height;
+        }
+        Ok(())
+    }
+}
+
+/// Draws `text` at the left margin with its bottom edge at `bottom`.
+fn render_line(canvas: &mut Canvas<Window>, font: &Font, text: &str, bottom: i32, color: Color) -> Result<(), String> {
+    let surface = font.render(text).blended(color).map_err(|e| e.to_string())?;
+    let texture_creator = canvas.texture_creator();
+    let texture = texture_creator
+        .create_texture_from_surface(&surface)
+        .map_err(|e| e.to_string())?;
+    let TextureQuery { width, height, .. } = texture.query();
+    canvas.copy(&texture, None, Some(Rect::new(MARGIN, bottom - height as i32, width, height)))
+}
@@ -0,0 +1,121 @@
+use crate::core::Vehicle;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, RenderTarget, Texture, TextureCreator, TextureQuery};
+use sdl2::surface::Surface;
+use sdl2::ttf::Font;
+use sdl2::video::WindowContext;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+/// Draws each vehicle's id above it, rendering the text once per id.
+pub struct IdLabels<'a, C = WindowContext> {
+    texture_creator: &'a TextureCreator<C>,
+    cache: HashMap<usize, Texture<'a>>,
+}
+
+impl<'a, C> IdLabels<'a, C> {
+    pub fn new(texture_creator: &'a TextureCreator<C>) -> Self {
+        Self {
+            texture_creator,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Labels stay upright whatever the vehicle's heading. Labels of vehicles that
+    /// have left are dropped from the cache.
+    pub fn render<T: RenderTarget<Context = C>>(
+        &mut self,
+        canvas: &mut Canvas<T>,
+        font: &Font,
+        vehicles: &[Vehicle],
+    ) -> Result<(), String> {
+        self.render_with(canvas, vehicles, |text| {
+            font.render(text)
+                .blended(Color::RGB(255, 255, 255))
+                .map_err(|e| e.to_string())
+        })
+    }
+
+    /// Draws the labels, calling `draw_text` for the ids that have none cached yet.
+    fn render_with<T: RenderTarget<Context = C>>(
+        &mut self,
+        canvas: &mut Canvas<T>,
+        vehicles: &[Vehicle],
+        mut draw_text: impl FnMut(&str) -> Result<Surface<'static>, String>,
+    ) -> Result<(), String> {
+        self.cache
+            .retain(|id, _| vehicles.iter().any(|vehicle| vehicle.id == *id));
+
+        let (width, height) = canvas.logical_size();
+        let screen = Rect::new(0, 0, width, height);
+        for vehicle in vehicles {
+            let texture = match self.cache.entry(vehicle.id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let surface = draw_text(&vehicle.id.to_string())?;
+                    let texture = self
+                        .texture_creator
+                        .create_texture_from_surface(&surface)
+                        .map_err(|e| e.to_string())?;
+                    entry.insert(texture)
+                }
+            };
+
+            let TextureQuery { width, height, .. } = texture.query();
+            let label = Rect::new(
+                vehicle.rect.center().x() - width as i32 / 2,
+                vehicle.rect.y() - height as i32 - 2,
+                width,
+                height,
+            );
+            if label.has_intersection(screen) {
+                canvas.copy(texture, None, Some(label))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::AudioEngine;
+    use crate::direction::Direction;
+    use crate::simulation::VehicleManager;
+    use sdl2::pixels::PixelFormatEnum;
+    use std::rc::Rc;
+
+    #[test]
+    fn a_label_is_drawn_once_and_reused_while_its_vehicle_stays() {
+        let mut canvas = Surface::new(200, 200, PixelFormatEnum::RGBA32)
+            .and_then(Surface::into_canvas)
+            .expect("a software canvas");
+        let texture_creator = canvas.texture_creator();
+        let mut labels = IdLabels::new(&texture_creator);
+        let mut drawn = Vec::new();
+        let mut draw_text = |text: &str| {
+            drawn.push(text.to_string());
+            Surface::new(10, 10, PixelFormatEnum::RGBA32)
+        };
+
+        let mut manager = VehicleManager::new(Rc::new(AudioEngine::silent()));
+        for direction in [Direction::Up, Direction::Left] {
+            manager.try_spawn_on_tick(direction, None).expect("the arm is open");
+        }
+        manager.update_vehicles();
+        let vehicles = manager.get_vehicles();
+        labels.render_with(&mut canvas, vehicles, &mut draw_text).unwrap();
+        let first = vehicles[0].id;
+        let texture = labels.cache[&first].raw();
+
+        for _ in 0..3 {
+            labels.render_with(&mut canvas, &vehicles[..1], &mut draw_text).unwrap();
+        }
+        // The same texture is drawn again, and the label of the vehicle that left is dropped
+        assert_eq!(labels.cache[&first].raw(), texture);
+        assert_eq!(labels.cache.len(), 1);
+        assert_eq!(drawn, vehicles.iter().map(|vehicle| vehicle.id.to_string()).collect::<Vec<_>>());
+    }
+}
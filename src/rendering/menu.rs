@@ -0,0 +1,270 @@
+use crate::constants::{DEFAULT_LANES_PER_DIRECTION, VEHICLE_SPAWN_INTERVAL};
+use crate::weather::Weather;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, TextureQuery};
+use sdl2::ttf::Font;
+use sdl2::video::Window;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+// Intervals between random spawns offered on the menu, in milliseconds
+const SPAWN_INTERVALS: [u64; 4] = [350, 700, 1000, 1500];
+const ROW_HEIGHT: i32 = 28;
+const MENU_WIDTH: u32 = 360;
+
+/// Settings a simulation is started with.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SimulationOptions {
+    pub traffic_light: bool,
+    pub weather: Weather,
+    pub spawn_interval: Duration,
+    pub random_generation: bool,
+    pub lanes_per_direction: i32,
+    /// Seed of random generation, the vehicles' routes and drivers, and the scenery.
+    pub seed: u64,
+    /// Plan paths around every vehicle rather than only those whose routes can meet.
+    /// Not on the menu, only set from the command line.
+    pub always_relevant: bool,
+}
+
+impl Default for SimulationOptions {
+    fn default() -> Self {
+        Self {
+            traffic_light: false,
+            weather: Weather::Clear,
+            spawn_interval: VEHICLE_SPAWN_INTERVAL,
+            random_generation: false,
+            lanes_per_direction: DEFAULT_LANES_PER_DIRECTION,
+            seed: 0,
+            always_relevant: false,
+        }
+    }
+}
+
+pub enum MenuAction {
+    Start(SimulationOptions),
+    Quit,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Item {
+    Controller,
+    Weather,
+    SpawnInterval,
+    RandomGeneration,
+    Lanes,
+    Seed,
+    Start,
+    Quit,
+}
+
+const ITEMS: [Item; 8] = [
+    Item::Controller,
+    Item::Weather,
+    Item::SpawnInterval,
+    Item::RandomGeneration,
+    Item::Lanes,
+    Item::Seed,
+    Item::Start,
+    Item::Quit,
+];
+
+/// Start screen for choosing the simulation's settings. Up and down pick an item,
+/// left, right and Enter change it, and Enter on Start or Quit leaves the menu.
+pub struct Menu {
+    options: SimulationOptions,
+    // Lane counts the road has room for
+    lane_counts: RangeInclusive<i32>,
+    selected: usize,
+}
+
+impl Menu {
+    pub fn new(options: SimulationOptions, lane_counts: RangeInclusive<i32>) -> Self {
+        Self {
+            options,
+            lane_counts,
+            selected: ITEMS.iter().position(|&item| item == Item::Start).unwrap_or(0),
+        }
+    }
+
+    pub fn handle_key(&mut self, keycode: Keycode) -> Option<MenuAction> {
+        match keycode {
+            Keycode::Up => self.selected = (self.selected + ITEMS.len() - 1) % ITEMS.len(),
+            Keycode::Down => self.selected = (self.selected + 1) % ITEMS.len(),
+            Keycode::Escape => return Some(MenuAction::Quit),
+            Keycode::Left => self.change(false),
+            Keycode::Right => self.change(true),
+            Keycode::Return | Keycode::KpEnter => match ITEMS[self.selected] {
+                Item::Start => return Some(MenuAction::Start(self.options)),
+                Item::Quit => return Some(MenuAction::Quit),
+                _ => self.change(true),
+            },
+            _ => {}
+        }
+        None
+    }
+
+    fn change(&mut self, forward: bool) {
+        let options = &mut self.options;
+        match ITEMS[self.selected] {
+            Item::Controller => options.traffic_light = !options.traffic_light,
+            Item::RandomGeneration => options.random_generation = !options.random_generation,
+            Item::Weather => {
+                // Three steps forward is a full cycle, so two is one step back
+                let steps = if forward { 1 } else { 2 };
+                for _ in 0..steps {
+                    options.weather = options.weather.next();
+                }
+            }
+            Item::SpawnInterval => {
+                // The configured interval need not be one of the offered ones, in which
+                // case the next larger one is the first step forward
+                let larger = SPAWN_INTERVALS
+                    .iter()
+                    .position(|&ms| Duration::from_millis(ms) >= options.spawn_interval);
+                let current = larger.unwrap_or(SPAWN_INTERVALS.len() - 1);
+                let offered = larger.is_some_and(|index| {
+                    Duration::from_millis(SPAWN_INTERVALS[index]) == options.spawn_interval
+                });
+                let next = if forward && !offered {
+                    larger.unwrap_or(0)
+                } else if forward {
+                    (current + 1) % SPAWN_INTERVALS.len()
+                } else {
+                    (current + SPAWN_INTERVALS.len() - 1) % SPAWN_INTERVALS.len()
+                };
+                options.spawn_interval = Duration::from_millis(SPAWN_INTERVALS[next]);
+            }
+            Item::Lanes => {
+                let (first, last) = (*self.lane_counts.start(), *self.lane_counts.end());
+                options.lanes_per_direction = match options.lanes_per_direction {
+                    lanes if forward && lanes >= last => first,
+                    lanes if forward => lanes + 1,
+                    lanes if lanes <= first => last,
+                    lanes => lanes - 1,
+                };
+            }
+            Item::Seed if forward => options.seed = options.seed.wrapping_add(1),
+            Item::Seed => options.seed = options.seed.wrapping_sub(1),
+            Item::Start | Item::Quit => {}
+        }
+    }
+
+    fn label(&self, item: Item) -> String {
+        let options = &self.options;
+        match item {
+            Item::Controller => format!(
+                "Controller: {}",
+                if options.traffic_light { "traffic light" } else { "smart" }
+            ),
+            Item::Weather => format!("Weather: {:?}", options.weather),
+            Item::SpawnInterval => format!(
+                "Random spawn interval: {} ms",
+                options.spawn_interval.as_millis()
+            ),
+            Item::RandomGeneration => format!(
+                "Random generation at start: {}",
+                if options.random_generation { "on" } else { "off" }
+            ),
+            Item::Lanes => format!("Lanes per direction: {}", options.lanes_per_direction),
+            Item::Seed => format!("Seed: {}", options.seed),
+            Item::Start => "Start".to_string(),
+            Item::Quit => "Quit".to_string(),
+        }
+    }
+
+    /// Draws the menu in a box in the middle of the window, the selected item highlighted.
+    pub fn render(&self, canvas: &mut Canvas<Window>, font: &Font) -> Result<(), String> {
+        let (window_width, window_height) = canvas.logical_size();
+        let height = (ITEMS.len() as i32 + 2) * ROW_HEIGHT;
+        let area = Rect::new(
+            (window_width as i32 - MENU_WIDTH as i32) / 2,
+            (window_height as i32 - height) / 2,
+            MENU_WIDTH,
+            height as u32,
+        );
+        canvas.set_draw_color(Color::RGB(50, 50, 50));
+        canvas.fill_rect(area)?;
+        canvas.set_draw_color(Color::RGB(200, 200, 200));
+        canvas.draw_rect(area)?;
+
+        let texture_creator = canvas.texture_creator();
+        let rows = std::iter::once(("Smart Road".to_string(), false)).chain(
+            ITEMS
+                .iter()
+                .enumerate()
+                .map(|(index, &item)| (self.label(item), index == self.selected)),
+        );
+        for (row, (text, selected)) in rows.enumerate() {
+            // Leave an empty row between the title and the items
+            let row = if row == 0 { 0 } else { row + 1 };
+            let y = area.y() + row as i32 * ROW_HEIGHT + ROW_HEIGHT / 2;
+            let color = if selected {
+                Color::RGB(255, 220, 0)
+            } else {
+                Color::RGB(255, 255, 255)
+            };
+            let surface = font
+                .render(&text)
+                .blended(color)
+                .map_err(|e| e.to_string())?;
+            let texture = texture_creator
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())?;
+            let TextureQuery { width, height, .. } = texture.query();
+            let x = area.x() + (MENU_WIDTH as i32 - width as i32) / 2;
+            canvas.copy(&texture, None, Some(Rect::new(x, y, width, height)))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(menu: &mut Menu, item: Item) {
+        while ITEMS[menu.selected] != item {
+            menu.handle_key(Keycode::Down);
+        }
+    }
+
+    fn started(menu: &mut Menu) -> SimulationOptions {
+        select(menu, Item::Start);
+        match menu.handle_key(Keycode::Return) {
+            Some(MenuAction::Start(options)) => options,
+            _ => panic!("Enter on Start starts the simulation"),
+        }
+    }
+
+    #[test]
+    fn lane_count_cycles_through_the_counts_that_fit() {
+        let mut menu = Menu::new(SimulationOptions::default(), 2..=4);
+        select(&mut menu, Item::Lanes);
+        let mut counts = Vec::new();
+        for _ in 0..3 {
+            menu.handle_key(Keycode::Right);
+            counts.push(started(&mut menu).lanes_per_direction);
+            select(&mut menu, Item::Lanes);
+        }
+        assert_eq!(counts, [4, 2, 3]);
+        menu.handle_key(Keycode::Left);
+        menu.handle_key(Keycode::Left);
+        assert_eq!(started(&mut menu).lanes_per_direction, 4);
+    }
+
+    #[test]
+    fn seed_steps_by_one_and_is_passed_to_the_simulation() {
+        let mut menu = Menu::new(SimulationOptions { seed: 41, ..SimulationOptions::default() }, 2..=4);
+        select(&mut menu, Item::Seed);
+        menu.handle_key(Keycode::Right);
+        menu.handle_key(Keycode::Return);
+        assert_eq!(started(&mut menu).seed, 43);
+        select(&mut menu, Item::Seed);
+        menu.handle_key(Keycode::Left);
+        assert_eq!(started(&mut menu).seed, 42);
+    }
+}
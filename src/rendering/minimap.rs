@@ -0,0 +1,146 @@
+use crate::core::Vehicle;
+use crate::direction::Direction;
+use crate::geometry::layout::world_size;
+use crate::intersection::IntersectionBounds;
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+// Length of the longer side of the overview drawn in the bottom-right corner
+const MINIMAP_SIZE: u32 = 120;
+const MINIMAP_MARGIN: i32 = 10;
+// Vehicles are never drawn smaller than this on the overview
+const MIN_VEHICLE_SIZE: u32 = 2;
+// Vehicle colours by pixels moved on the next tick, from stopped to full speed
+const SPEED_COLORS: [Color; 4] = [
+    Color::RGB(230, 40, 40),
+    Color::RGB(240, 140, 30),
+    Color::RGB(200, 220, 40),
+    Color::RGB(40, 210, 70),
+];
+
+pub struct Minimap;
+
+impl Minimap {
+    /// Area of the window the overview is drawn into, in the shape of the world.
+    pub fn area(canvas: &Canvas<Window>) -> Result<Rect, String> {
+        let (width, height) = canvas.logical_size();
+        let (world_width, world_height) = world_size();
+        let longest = world_width.max(world_height);
+        let (map_width, map_height) = (MINIMAP_SIZE * world_width / longest, MINIMAP_SIZE * world_height / longest);
+        Ok(Rect::new(
+            width as i32 - map_width as i32 - MINIMAP_MARGIN,
+            height as i32 - map_height as i32 - MINIMAP_MARGIN,
+            map_width,
+            map_height,
+        ))
+    }
+
+    /// Maps a world point into `area`, clamping points outside the world to its edges.
+    pub fn world_to_minimap(point: Point, area: Rect) -> Point {
+        let (width, height) = world_size();
+        let (width, height) = (width as i32, height as i32);
+        Point::new(
+            area.x() + point.x().clamp(0, width) * area.width() as i32 / width,
+            area.y() + point.y().clamp(0, height) * area.height() as i32 / height,
+        )
+    }
+
+    fn world_rect_to_minimap(rect: Rect, area: Rect) -> Rect {
+        let top_left = Self::world_to_minimap(rect.top_left(), area);
+        let bottom_right = Self::world_to_minimap(rect.bottom_right(), area);
+        Rect::new(
+            top_left.x().min(area.right() - MIN_VEHICLE_SIZE as i32),
+            top_left.y().min(area.bottom() - MIN_VEHICLE_SIZE as i32),
+            ((bottom_right.x() - top_left.x()) as u32).max(MIN_VEHICLE_SIZE),
+            ((bottom_right.y() - top_left.y()) as u32).max(MIN_VEHICLE_SIZE),
+        )
+    }
+
+    /// Draws the roads, the intersection and every vehicle scaled into the corner
+    /// overview, each vehicle coloured by its speed so queues stand out in red.
+    pub fn render(canvas: &mut Canvas<Window>, vehicles: &[Vehicle]) -> Result<(), String> {
+        let area = Self::area(canvas)?;
+
+        canvas.set_draw_color(Color::RGB(30, 120, 30));
+        canvas.fill_rect(area)?;
+
+        let (left, top, right, bottom) = IntersectionBounds::edges();
+        let (width, height) = world_size();
+        let open_or = |arm: Direction, edge: i32, intersection_side: i32| {
+            if arm.is_open() {
+                edge
+            } else {
+                intersection_side
+            }
+        };
+        let roads: Vec<Rect> = [
+            Rect::new(
+                left,
+                open_or(Direction::Up, 0, top),
+                (right - left) as u32,
+                (open_or(Direction::Down, height as i32, bottom) - open_or(Direction::Up, 0, top)) as u32,
+            ),
+            Rect::new(
+                open_or(Direction::Left, 0, left),
+                top,
+                (open_or(Direction::Right, width as i32, right) - open_or(Direction::Left, 0, left)) as u32,
+                (bottom - top) as u32,
+            ),
+        ]
+        .into_iter()
+        .map(|road| Self::world_rect_to_minimap(road, area))
+        .collect();
+        canvas.set_draw_color(Color::RGB(51, 51, 51));
+        canvas.fill_rects(&roads)?;
+
+        canvas.set_draw_color(Color::RGB(110, 110, 110));
+        canvas.draw_rect(Self::world_rect_to_minimap(IntersectionBounds::rect(), area))?;
+
+        // One batch per colour keeps this to a few draw calls however busy the road is
+        let mut vehicle_rects: [Vec<Rect>; SPEED_COLORS.len()] = Default::default();
+        for vehicle in vehicles {
+            let speed = (vehicle.next_step().max(0) as usize).min(SPEED_COLORS.len() - 1);
+            vehicle_rects[speed].push(Self::world_rect_to_minimap(vehicle.rect, area));
+        }
+        for (color, rects) in SPEED_COLORS.iter().zip(&vehicle_rects) {
+            canvas.set_draw_color(*color);
+            canvas.fill_rects(rects)?;
+        }
+
+        canvas.set_draw_color(Color::RGB(200, 200, 200));
+        canvas.draw_rect(area)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::WORLD_SIZE;
+
+    fn area() -> Rect {
+        Rect::new(600, 500, 100, 100)
+    }
+
+    #[test]
+    fn world_to_minimap_scales_into_the_area() {
+        let world = WORLD_SIZE as i32;
+        assert_eq!(Minimap::world_to_minimap(Point::new(0, 0), area()), Point::new(600, 500));
+        assert_eq!(
+            Minimap::world_to_minimap(Point::new(world / 2, world / 4), area()),
+            Point::new(650, 525)
+        );
+        assert_eq!(Minimap::world_to_minimap(Point::new(world, world), area()), Point::new(700, 600));
+    }
+
+    #[test]
+    fn world_to_minimap_clamps_points_off_the_world_to_the_edges() {
+        let world = WORLD_SIZE as i32;
+        assert_eq!(Minimap::world_to_minimap(Point::new(-50, world / 2), area()), Point::new(600, 550));
+        assert_eq!(
+            Minimap::world_to_minimap(Point::new(world / 2, world + 80), area()),
+            Point::new(650, 600)
+        );
+    }
+}
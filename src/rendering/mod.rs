@@ -1,5 +1,30 @@
+pub mod assets;
 pub mod stats_display;
 pub mod road_renderer;
+pub mod path_renderer;
+pub mod route_legend;
+pub mod minimap;
+pub mod id_labels;
+pub mod passage_overlay;
+pub mod reservation_overlay;
+pub mod hud;
+pub mod frame_recorder;
+pub mod toasts;
+pub mod menu;
+pub mod trails;
+pub mod decorations;
 
 pub use stats_display::render_stats_modal;
 pub use road_renderer::RoadRenderer;
+pub use path_renderer::PathRenderer;
+pub use route_legend::RouteLegend;
+pub use minimap::Minimap;
+pub use id_labels::IdLabels;
+pub use passage_overlay::PassageOverlay;
+pub use reservation_overlay::ReservationOverlay;
+pub use hud::Hud;
+pub use frame_recorder::FrameRecorder;
+pub use toasts::Toasts;
+pub use menu::{Menu, MenuAction, SimulationOptions};
+pub use trails::Trails;
+pub use decorations::Decorations;
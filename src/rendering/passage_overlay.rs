@@ -0,0 +1,127 @@
+use crate::constants::*;
+use crate::core::{PassageState, Vehicle};
+use crate::direction::Direction;
+use crate::geometry::layout::world_size;
+use crate::geometry::spawn::get_spawn_position;
+use crate::intersection::IntersectionBounds;
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{Canvas, TextureQuery};
+use sdl2::ttf::Font;
+use sdl2::video::Window;
+
+// The timeline at the top of the screen shows this many ticks ahead
+const TIMELINE_TICKS: u64 = 600;
+const TIMELINE_ROW_HEIGHT: u32 = 4;
+const TIMELINE_MAX_ROWS: usize = 20;
+
+const MOVING: Color = Color::RGB(0, 220, 0);
+const WAITING: Color = Color::RGB(255, 220, 0);
+// Handshake lines to the intersection, while a passage is requested and once granted
+const REQUESTED: Color = Color::RGB(255, 120, 0);
+const GRANTED: Color = Color::RGB(0, 190, 255);
+
+/// Debug view of each vehicle's planned passage through the intersection.
+pub struct PassageOverlay;
+
+impl PassageOverlay {
+    /// Outlines vehicles that still have to cross, green while moving and yellow while
+    /// waiting, with the time until they enter below them. The passages are drawn as a
+    /// timeline at the top of the screen, one row per vehicle, soonest first.
+    /// Requested passages, by arm and lane, and granted ones are drawn as handshake lines.
+    pub fn render(
+        canvas: &mut Canvas<Window>,
+        font: Option<&Font>,
+        vehicles: &[Vehicle],
+        requested: &[(Direction, i32)],
+        tick: u64,
+    ) -> Result<(), String> {
+        Self::render_handshakes(canvas, vehicles, requested)?;
+
+        let mut passages: Vec<(&Vehicle, u64, u64)> = vehicles
+            .iter()
+            .filter_map(|vehicle| {
+                let (entry, clear) = vehicle.intersection_window()?;
+                Some((vehicle, entry, clear))
+            })
+            .collect();
+        passages.sort_by_key(|&(_, entry, _)| entry);
+
+        let ticks_per_second = 1000.0 / SIMULATION_STEP.as_millis() as f32;
+        for &(vehicle, entry, _) in &passages {
+            let color = if vehicle.stuck_ticks > 0 { WAITING } else { MOVING };
+            canvas.set_draw_color(color);
+            canvas.draw_rect(vehicle.rect)?;
+
+            let Some(font) = font.filter(|_| entry > tick) else {
+                continue;
+            };
+            let text = format!("{:.1}s", (entry - tick) as f32 / ticks_per_second);
+            let surface = font.render(&text).blended(color).map_err(|e| e.to_string())?;
+            let texture_creator = canvas.texture_creator();
+            let texture = texture_creator
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())?;
+            let TextureQuery { width, height, .. } = texture.query();
+            canvas.copy(
+                &texture,
+                None,
+                Some(Rect::new(
+                    vehicle.rect.center().x() - width as i32 / 2,
+                    vehicle.rect.bottom() + 2,
+                    width,
+                    height,
+                )),
+            )?;
+        }
+
+        let scale = world_size().0 as f32 / TIMELINE_TICKS as f32;
+        let x_at = |time: u64| (time.saturating_sub(tick).min(TIMELINE_TICKS) as f32 * scale) as i32;
+        for (row, &(vehicle, entry, clear)) in passages.iter().take(TIMELINE_MAX_ROWS).enumerate() {
+            let (start, end) = (x_at(entry), x_at(clear));
+            if end <= start {
+                continue;
+            }
+            canvas.set_draw_color(if vehicle.stuck_ticks > 0 { WAITING } else { MOVING });
+            canvas.fill_rect(Rect::new(
+                start,
+                row as i32 * (TIMELINE_ROW_HEIGHT as i32 + 1),
+                (end - start) as u32,
+                TIMELINE_ROW_HEIGHT,
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Lines from where each requested vehicle will appear to the middle of the
+    /// intersection, and from each vehicle holding a granted passage to where it will
+    /// enter, until it does.
+    fn render_handshakes(
+        canvas: &mut Canvas<Window>,
+        vehicles: &[Vehicle],
+        requested: &[(Direction, i32)],
+    ) -> Result<(), String> {
+        let center = IntersectionBounds::rect().center();
+        let half_lane = LINE_SPACING / 2;
+        canvas.set_draw_color(REQUESTED);
+        for &(direction, lane) in requested {
+            let spawn = get_spawn_position(direction, lane);
+            canvas.draw_line(Point::new(spawn.x + half_lane, spawn.y + half_lane), center)?;
+        }
+
+        canvas.set_draw_color(GRANTED);
+        for vehicle in vehicles.iter().filter(|v| v.passage_state == PassageState::Granted) {
+            let size = vehicle.rect.width();
+            let Some(entry) = vehicle.path.iter().find(|tp| tp.position.is_in_intersection(size)) else {
+                continue;
+            };
+            let half = size as i32 / 2;
+            canvas.draw_line(
+                vehicle.rect.center(),
+                Point::new(entry.position.x + half, entry.position.y + half),
+            )?;
+        }
+        Ok(())
+    }
+}
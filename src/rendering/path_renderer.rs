@@ -0,0 +1,111 @@
+use crate::constants::*;
+use crate::core::Vehicle;
+use crate::geometry::position::{Position, TimedPosition};
+use sdl2::pixels::Color;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+
+pub struct PathRenderer;
+
+impl PathRenderer {
+    /// Converts a path into line segments, keeping only every `stride`-th point.
+    /// The last point is always kept so the line reaches the exit.
+    pub fn path_segments(path: &[TimedPosition], stride: usize) -> Vec<(Position, Position)> {
+        let stride = stride.max(1);
+        let mut points: Vec<Position> = path.iter().step_by(stride).map(|tp| tp.position).collect();
+        if let Some(last) = path.last() {
+            if points.last() != Some(&last.position) {
+                points.push(last.position);
+            }
+        }
+
+        points
+            .windows(2)
+            .filter(|pair| pair[0] != pair[1])
+            .map(|pair| (pair[0], pair[1]))
+            .collect()
+    }
+
+    pub fn render_vehicle_paths(
+        canvas: &mut Canvas<Window>,
+        vehicles: &[Vehicle],
+    ) -> Result<(), String> {
+        canvas.set_blend_mode(BlendMode::Blend);
+
+        for vehicle in vehicles {
+            let size = vehicle.rect.width();
+            let half = size as i32 / 2;
+            let current = Position {
+                x: vehicle.rect.x(),
+                y: vehicle.rect.y(),
+            };
+            let first = vehicle.path.first().map(|tp| tp.position);
+
+            let mut segments = Self::path_segments(&vehicle.path, PATH_RENDER_STRIDE);
+            if let Some(first) = first.filter(|first| *first != current) {
+                segments.insert(0, (current, first));
+            }
+
+            for (from, to) in segments {
+                let color = if from.is_in_intersection(size) || to.is_in_intersection(size) {
+                    Color::RGBA(255, 80, 80, 140)
+                } else {
+                    Color::RGBA(255, 255, 255, 70)
+                };
+                canvas.set_draw_color(color);
+                canvas.draw_line((from.x + half, from.y + half), (to.x + half, to.y + half))?;
+            }
+        }
+
+        canvas.set_blend_mode(BlendMode::None);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A path heading right two pixels a tick from the origin, one point per tick
+    fn straight_path(points: i32) -> Vec<TimedPosition> {
+        (0..points)
+            .map(|index| TimedPosition {
+                position: Position { x: 2 * index, y: 0 },
+                time: index as u64,
+            })
+            .collect()
+    }
+
+    fn at(x: i32) -> Position {
+        Position { x, y: 0 }
+    }
+
+    #[test]
+    fn path_segments_keep_every_stride_th_point_and_end_at_the_exit() {
+        let path = straight_path(10);
+        assert_eq!(
+            PathRenderer::path_segments(&path, 4),
+            [(at(0), at(8)), (at(8), at(16)), (at(16), at(18))]
+        );
+        // A stride that lands on the last point does not repeat it
+        assert_eq!(PathRenderer::path_segments(&path, 9), [(at(0), at(18))]);
+    }
+
+    #[test]
+    fn path_segments_join_every_point_with_a_stride_of_zero_or_one() {
+        let path = straight_path(4);
+        let every_point = [(at(0), at(2)), (at(2), at(4)), (at(4), at(6))];
+        assert_eq!(PathRenderer::path_segments(&path, 1), every_point);
+        assert_eq!(PathRenderer::path_segments(&path, 0), every_point);
+    }
+
+    #[test]
+    fn path_segments_skip_waiting_and_need_two_points() {
+        let mut path = straight_path(2);
+        // Waiting in place for a tick
+        path.insert(1, TimedPosition { position: at(0), time: 1 });
+        assert_eq!(PathRenderer::path_segments(&path, 1), [(at(0), at(2))]);
+        assert!(PathRenderer::path_segments(&straight_path(1), 1).is_empty());
+        assert!(PathRenderer::path_segments(&[], 3).is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+use crate::constants::*;
+use crate::core::Vehicle;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas, TextureQuery};
+use sdl2::ttf::Font;
+use sdl2::video::Window;
+
+const SHADE_ALPHA: u8 = 50;
+
+// Reservations are told apart by colour, cycling through this palette by vehicle id
+const PALETTE: [Color; 6] = [
+    Color::RGB(255, 90, 90),
+    Color::RGB(90, 160, 255),
+    Color::RGB(255, 200, 60),
+    Color::RGB(120, 230, 120),
+    Color::RGB(220, 120, 255),
+    Color::RGB(80, 220, 220),
+];
+
+/// Debug view of the part of the intersection each vehicle's plan holds.
+pub struct ReservationOverlay;
+
+impl ReservationOverlay {
+    /// The vehicles still holding part of the intersection, with the area they hold and
+    /// the tick they clear it on.
+    pub fn active(vehicles: &[Vehicle]) -> Vec<(&Vehicle, Rect, u64)> {
+        vehicles
+            .iter()
+            .filter_map(|vehicle| {
+                let area = vehicle.reserved_area()?;
+                let (_, clear) = vehicle.intersection_window()?;
+                Some((vehicle, area, clear))
+            })
+            .collect()
+    }
+
+    /// Shades the area each vehicle will sweep through in the intersection, labelled
+    /// with its id and the time until it clears. Areas shrink as vehicles cross and
+    /// disappear once they are out.
+    pub fn render(
+        canvas: &mut Canvas<Window>,
+        font: Option<&Font>,
+        vehicles: &[Vehicle],
+        tick: u64,
+    ) -> Result<(), String> {
+        let reservations = Self::active(vehicles);
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        for &(vehicle, area, _) in &reservations {
+            let color = PALETTE[vehicle.id % PALETTE.len()];
+            canvas.set_draw_color(Color::RGBA(color.r, color.g, color.b, SHADE_ALPHA));
+            canvas.fill_rect(area)?;
+            canvas.set_draw_color(color);
+            canvas.draw_rect(area)?;
+        }
+        canvas.set_blend_mode(BlendMode::None);
+
+        let Some(font) = font else {
+            return Ok(());
+        };
+        let ticks_per_second = 1000.0 / SIMULATION_STEP.as_millis() as f32;
+        let texture_creator = canvas.texture_creator();
+        for &(vehicle, area, clear) in &reservations {
+            let text = format!(
+                "#{} clear {:.1}s",
+                vehicle.id,
+                clear.saturating_sub(tick) as f32 / ticks_per_second
+            );
+            let surface = font
+                .render(&text)
+                .blended(PALETTE[vehicle.id % PALETTE.len()])
+                .map_err(|e| e.to_string())?;
+            let texture = texture_creator
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())?;
+            let TextureQuery { width, height, .. } = texture.query();
+            canvas.copy(
+                &texture,
+                None,
+                Some(Rect::new(area.x() + 2, area.y() + 2, width, height)),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::AudioEngine;
+    use crate::direction::{Direction, TurnDirection};
+    use crate::simulation::VehicleManager;
+    use std::rc::Rc;
+
+    #[test]
+    fn two_right_turners_from_opposite_arms_hold_their_own_corners_until_they_clear() {
+        let mut manager = VehicleManager::new(Rc::new(AudioEngine::silent()));
+        let ids = [Direction::Up, Direction::Down].map(|from| {
+            let target = Direction::target_for(from, TurnDirection::Right);
+            manager.try_spawn_on_tick(from, Some(target)).expect("the arm is open")
+        });
+        manager.update_vehicles();
+
+        let active = ReservationOverlay::active(manager.get_vehicles());
+        assert_eq!(active.iter().map(|(vehicle, ..)| vehicle.id).collect::<Vec<_>>(), ids);
+        let [(first, first_area, first_clear), (_, second_area, _)] = active[..] else {
+            panic!("two reservations");
+        };
+        // Neither turn crosses the other, so they are held side by side
+        assert!(!first_area.has_intersection(second_area));
+        assert_eq!(Some(first_clear), first.intersection_window().map(|(_, clear)| clear));
+
+        while manager.get_tick() < first_clear {
+            manager.update_vehicles();
+        }
+        let remaining = ReservationOverlay::active(manager.get_vehicles());
+        assert!(remaining.iter().all(|(vehicle, ..)| vehicle.id != ids[0]));
+    }
+}
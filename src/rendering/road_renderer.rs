@@ -1,8 +1,40 @@
 use crate::constants::*;
+use crate::direction::{Direction, TurnDirection};
+use crate::geometry::layout::{lanes_per_direction, road_center, world_size};
+use crate::geometry::spawn::{lane_coordinate, route_lanes};
+use crate::geometry::Position;
+use crate::intersection::{Controller, IntersectionBounds};
+use crate::rendering::Decorations;
+use crate::simulation::statistics::Statistics;
+use crate::simulation::{Heatmap, HeatmapLayer};
+use crate::weather::Weather;
 use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{BlendMode, Canvas, Texture, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
+
+const CENTER_LINE_COLOR: Color = Color::RGB(240, 200, 0);
+const CENTER_LINE_WIDTH: u32 = 2;
+// Length of each dash of the centre line, and of the gap after it
+const DASH_LENGTH: i32 = 12;
+// How far before the stop line the lane arrows are painted, to their middle
+const ARROW_SETBACK: i32 = 2 * LINE_SPACING;
+// How far in from the window edge a closed lane's barrier stands, to its middle
+const BARRIER_SETBACK: i32 = LINE_SPACING;
+const BARRIER_DEPTH: i32 = 8;
+// Width of each red and white stripe across the barrier
+const BARRIER_STRIPE: i32 = 6;
+const RAIN_STREAKS: i32 = 150;
+const RAIN_COLOR: Color = Color::RGBA(190, 200, 230, 110);
+// Pixels each streak falls per tick, and how far it slants sideways while doing so
+const RAIN_FALL: i32 = 14;
+const RAIN_SLANT: i32 = 3;
+
+/// Part of a lane arrow in the lane's own frame, from the arrow's middle: the first range
+/// runs along the direction of travel and the second across it, positive to the left.
+type ArrowPart = (RangeInclusive<i32>, RangeInclusive<i32>);
 
 pub struct RoadRenderer;
 
@@ -12,24 +44,69 @@ impl RoadRenderer {
         canvas.clear();
     }
 
+    /// Draws the scenery that never changes, the grass with its decorations, the road
+    /// surface and the lane markers, into a texture that is copied each frame instead.
+    /// The texture covers the world of the current layout, so it is built again when the
+    /// layout changes.
+    pub fn build_background<'a>(
+        canvas: &mut Canvas<Window>,
+        texture_creator: &'a TextureCreator<WindowContext>,
+        decorations: &Decorations,
+    ) -> Result<Texture<'a>, String> {
+        let (width, height) = world_size();
+        let mut texture = texture_creator
+            .create_texture_target(None, width, height)
+            .map_err(|e| e.to_string())?;
+        let mut drawn = Ok(());
+        canvas
+            .with_texture_canvas(&mut texture, |canvas| {
+                Self::render_background(canvas);
+                drawn = decorations.render(canvas);
+                Self::render_road_surface(canvas);
+                Self::render_lane_markers(canvas);
+                if drawn.is_ok() {
+                    drawn = Self::render_lane_arrows(canvas);
+                }
+            })
+            .map_err(|e| e.to_string())?;
+        drawn?;
+        Ok(texture)
+    }
+
+    pub fn blit_background(canvas: &mut Canvas<Window>, background: &Texture) -> Result<(), String> {
+        let (width, height) = world_size();
+        canvas.copy(background, None, Rect::new(0, 0, width, height))
+    }
+
     pub fn render_road_surface(canvas: &mut Canvas<Window>) {
         canvas.set_draw_color(Color::RGB(51, 51, 51));
 
+        // A closed arm's road stops at the intersection
+        let (left, top, right, bottom) = IntersectionBounds::edges();
+        let (width, height) = world_size();
+        let open_or = |arm: Direction, edge: i32, intersection_side: i32| {
+            if arm.is_open() {
+                edge
+            } else {
+                intersection_side
+            }
+        };
+
         canvas
             .fill_rect(Rect::new(
-                5 * LINE_SPACING,
-                0,
-                (11 - 5) * LINE_SPACING as u32,
-                WINDOW_SIZE,
+                left,
+                open_or(Direction::Up, 0, top),
+                (right - left) as u32,
+                (open_or(Direction::Down, height as i32, bottom) - open_or(Direction::Up, 0, top)) as u32,
             ))
             .unwrap();
 
         canvas
             .fill_rect(Rect::new(
-                0,
-                5 * LINE_SPACING - 1,
-                WINDOW_SIZE,
-                (11 - 5) * LINE_SPACING as u32,
+                open_or(Direction::Left, 0, left),
+                top - 1,
+                (open_or(Direction::Right, width as i32, right) - open_or(Direction::Left, 0, left)) as u32,
+                (bottom - top) as u32,
             ))
             .unwrap();
     }
@@ -37,17 +114,364 @@ impl RoadRenderer {
     pub fn render_lane_markers(canvas: &mut Canvas<Window>) {
         canvas.set_draw_color(Color::RGB(255, 255, 255));
 
-        for i in 5..=11 {
-            let x = i * LINE_SPACING;
-            canvas.draw_line((x, 0), (x, 5 * LINE_SPACING)).unwrap();
-            canvas
-                .draw_line((x, 11 * LINE_SPACING), (x, WINDOW_SIZE as i32))
-                .unwrap();
+        let (left, top, right, bottom) = IntersectionBounds::edges();
+        let center = road_center();
+        let (width, height) = world_size();
+        let (width, height) = (width as i32, height as i32);
+        for x in (left..=right).step_by(LINE_SPACING as usize).filter(|&x| x != center.x) {
+            if Direction::Up.is_open() {
+                canvas.draw_line((x, 0), (x, top)).unwrap();
+            }
+            if Direction::Down.is_open() {
+                canvas
+                    .draw_line((x, bottom), (x, height))
+                    .unwrap();
+            }
+        }
+        for y in (top..=bottom).step_by(LINE_SPACING as usize).filter(|&y| y != center.y) {
+            if Direction::Left.is_open() {
+                canvas.draw_line((0, y), (left, y)).unwrap();
+            }
+            if Direction::Right.is_open() {
+                canvas
+                    .draw_line((right, y), (width, y))
+                    .unwrap();
+            }
+        }
 
-            canvas.draw_line((0, x), (5 * LINE_SPACING, x)).unwrap();
-            canvas
-                .draw_line((11 * LINE_SPACING, x), (WINDOW_SIZE as i32, x))
-                .unwrap();
+        // Opposing directions are divided by a dashed yellow line, dashes starting at
+        // the intersection so every arm looks the same there
+        canvas.set_draw_color(CENTER_LINE_COLOR);
+        let half_width = CENTER_LINE_WIDTH as i32 / 2;
+        let dashes = |from: i32, to: i32| -> Vec<i32> {
+            if from < to {
+                (from..to).step_by(2 * DASH_LENGTH as usize).collect()
+            } else {
+                (to..from).rev().step_by(2 * DASH_LENGTH as usize).map(|end| end - DASH_LENGTH + 1).collect()
+            }
+        };
+        let mut rects = Vec::new();
+        for (arm, from, to) in [
+            (Direction::Up, top, 0),
+            (Direction::Down, bottom, height),
+            (Direction::Left, left, 0),
+            (Direction::Right, right, width),
+        ] {
+            if !arm.is_open() {
+                continue;
+            }
+            for start in dashes(from, to) {
+                rects.push(if arm.is_horizontal() {
+                    Rect::new(start, center.y - half_width, DASH_LENGTH as u32, CENTER_LINE_WIDTH)
+                } else {
+                    Rect::new(center.x - half_width, start, CENTER_LINE_WIDTH, DASH_LENGTH as u32)
+                });
+            }
         }
+        canvas.fill_rects(&rects).unwrap();
     }
+
+    /// Paints an arrow on each approach lane for every route that may use it, taken from
+    /// the same lane assignment vehicles are spawned with. Routes towards a closed arm
+    /// are left out, and U-turns only get their own arrow where left turns are closed.
+    pub fn render_lane_arrows(canvas: &mut Canvas<Window>) -> Result<(), String> {
+        let (left, top, right, bottom) = IntersectionBounds::edges();
+        canvas.set_draw_color(Color::RGB(255, 255, 255));
+
+        for arm in Direction::ALL.into_iter().filter(Direction::is_open) {
+            let travel = arm.opposite();
+            let routes: Vec<TurnDirection> = TurnDirection::ALL
+                .into_iter()
+                .filter(|&turn| Direction::target_for(arm, turn).is_open())
+                .collect();
+            let left_open = routes.contains(&TurnDirection::Left);
+
+            for lane in 0..lanes_per_direction() {
+                let across = lane_coordinate(travel, lane) + LINE_SPACING / 2;
+                let stop_line = match arm {
+                    Direction::Up => Position { x: across, y: top },
+                    Direction::Down => Position { x: across, y: bottom },
+                    Direction::Left => Position { x: left, y: across },
+                    Direction::Right => Position { x: right, y: across },
+                };
+                let middle = stop_line.move_in_direction(&arm, ARROW_SETBACK);
+
+                let parts: Vec<ArrowPart> = routes
+                    .iter()
+                    .filter(|&&turn| route_lanes(turn).contains(&lane))
+                    .filter(|&&turn| turn != TurnDirection::UTurn || !left_open)
+                    .flat_map(|&turn| arrow_parts(turn))
+                    .collect();
+                if parts.is_empty() {
+                    continue;
+                }
+                canvas.fill_rects(
+                    &parts
+                        .into_iter()
+                        .map(|(along, across)| lane_rect(middle, travel, along, across))
+                        .collect::<Vec<_>>(),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draws a stop line across the incoming lanes of each approach, where vehicles
+    /// wait for crossing traffic: white, or green, amber and red by the signal when the
+    /// controller has one.
+    pub fn render_stop_lines(
+        canvas: &mut Canvas<Window>,
+        controller: &dyn Controller,
+        tick: u64,
+    ) -> Result<(), String> {
+        let (left, top, right, bottom) = IntersectionBounds::edges();
+        let center = road_center();
+        let half_road = (lanes_per_direction() * LINE_SPACING) as u32;
+
+        let stop_lines = [
+            (Direction::Up, Rect::new(left, top - STOP_LINE_WIDTH as i32, half_road, STOP_LINE_WIDTH)),
+            (Direction::Down, Rect::new(center.x, bottom, half_road, STOP_LINE_WIDTH)),
+            (Direction::Left, Rect::new(left - STOP_LINE_WIDTH as i32, center.y, STOP_LINE_WIDTH, half_road)),
+            (Direction::Right, Rect::new(right, top, STOP_LINE_WIDTH, half_road)),
+        ]
+        .into_iter()
+        .filter(|(arm, _)| arm.is_open());
+
+        for (arm, line) in stop_lines {
+            let color = if !controller.is_signalled() {
+                Color::RGB(255, 255, 255)
+            } else if controller.may_enter(arm, tick) {
+                Color::RGB(0, 220, 0)
+            } else if controller.amber_since(arm, tick).is_some() {
+                Color::RGB(255, 170, 0)
+            } else {
+                Color::RGB(220, 0, 0)
+            };
+            canvas.set_draw_color(color);
+            canvas.fill_rect(line)?;
+        }
+
+        Ok(())
+    }
+
+    /// Draws a striped barrier across each closed lane near where its traffic enters
+    /// the window.
+    pub fn render_closed_lanes(
+        canvas: &mut Canvas<Window>,
+        closed_lanes: &HashSet<(Direction, i32)>,
+    ) -> Result<(), String> {
+        let (width, height) = world_size();
+        let half = LINE_SPACING / 2 - 2;
+        let mut stripes = [Vec::new(), Vec::new()];
+        for &(arm, lane) in closed_lanes.iter().filter(|(arm, _)| arm.is_open()) {
+            let travel = arm.opposite();
+            let across = lane_coordinate(travel, lane) + LINE_SPACING / 2;
+            let edge = match arm {
+                Direction::Up => Position { x: across, y: 0 },
+                Direction::Down => Position { x: across, y: height as i32 },
+                Direction::Left => Position { x: 0, y: across },
+                Direction::Right => Position { x: width as i32, y: across },
+            };
+            let middle = edge.move_in_direction(&travel, BARRIER_SETBACK);
+            for (index, start) in (-half..=half).step_by(BARRIER_STRIPE as usize).enumerate() {
+                let end = (start + BARRIER_STRIPE - 1).min(half);
+                stripes[index % 2].push(lane_rect(middle, travel, -BARRIER_DEPTH / 2..=BARRIER_DEPTH / 2, start..=end));
+            }
+        }
+
+        for (color, rects) in [Color::RGB(220, 30, 30), Color::RGB(245, 245, 245)].into_iter().zip(&stripes) {
+            canvas.set_draw_color(color);
+            canvas.fill_rects(rects)?;
+        }
+        Ok(())
+    }
+
+    /// Draws a bar beside each approach, one vehicle length per queued vehicle.
+    pub fn render_queue_bars(canvas: &mut Canvas<Window>, stats: &Statistics) -> Result<(), String> {
+        const BAR_WIDTH: u32 = 6;
+        const BAR_GAP: i32 = 4;
+        let (left, top, right, bottom) = IntersectionBounds::edges();
+        let (width, height) = world_size();
+        let offset = BAR_GAP + BAR_WIDTH as i32;
+
+        let bars: Vec<Rect> = stats
+            .queues
+            .iter()
+            .filter(|(_, queue)| queue.current > 0)
+            .map(|(arm, queue)| {
+                // Bars stop at the window edge
+                let approach = match arm {
+                    Direction::Up => top,
+                    Direction::Down => height as i32 - bottom,
+                    Direction::Left => left,
+                    Direction::Right => width as i32 - right,
+                };
+                let length = (queue.current as i32 * LINE_SPACING).min(approach) as u32;
+                match arm {
+                    Direction::Up => Rect::new(left - offset, top - length as i32, BAR_WIDTH, length),
+                    Direction::Down => Rect::new(right + BAR_GAP, bottom, BAR_WIDTH, length),
+                    Direction::Left => Rect::new(left - length as i32, bottom + BAR_GAP, length, BAR_WIDTH),
+                    Direction::Right => Rect::new(right, top - offset, length, BAR_WIDTH),
+                }
+            })
+            .collect();
+
+        canvas.set_draw_color(Color::RGB(255, 140, 0));
+        canvas.fill_rects(&bars)
+    }
+
+    /// Marks recent close calls with a red ring that grows and fades as it ages.
+    pub fn render_close_calls(canvas: &mut Canvas<Window>, stats: &Statistics) -> Result<(), String> {
+        const SEGMENTS: usize = 32;
+        canvas.set_blend_mode(BlendMode::Blend);
+        for event in stats.recent_close_calls() {
+            let age = (stats.get_tick() - event.tick).min(CLOSE_CALL_MARKER_TICKS) as f64
+                / CLOSE_CALL_MARKER_TICKS as f64;
+            let radius = LINE_SPACING as f64 * (0.2 + 0.8 * age);
+            let (x, y) = event.position;
+            let ring: Vec<Point> = (0..=SEGMENTS)
+                .map(|segment| {
+                    let angle = segment as f64 / SEGMENTS as f64 * std::f64::consts::TAU;
+                    Point::new(
+                        x + (radius * angle.cos()).round() as i32,
+                        y + (radius * angle.sin()).round() as i32,
+                    )
+                })
+                .collect();
+
+            canvas.set_draw_color(Color::RGBA(255, 0, 0, (255.0 * (1.0 - age)) as u8));
+            canvas.draw_lines(ring.as_slice())?;
+        }
+        canvas.set_blend_mode(BlendMode::None);
+
+        Ok(())
+    }
+
+    /// Tints each grid cell of one heatmap layer from a faint blue (rarely occupied)
+    /// to a strong red (most occupied).
+    pub fn render_heatmap(canvas: &mut Canvas<Window>, heatmap: &Heatmap, layer: HeatmapLayer) -> Result<(), String> {
+        let max_count = heatmap.max_count(layer);
+        if max_count == 0 {
+            return Ok(());
+        }
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        for row in 0..heatmap.rows() {
+            for column in 0..heatmap.columns() {
+                let count = heatmap.count(layer, column, row);
+                if count == 0 {
+                    continue;
+                }
+
+                let heat = count as f32 / max_count as f32;
+                canvas.set_draw_color(Color::RGBA(
+                    (255.0 * heat) as u8,
+                    0,
+                    (255.0 * (1.0 - heat)) as u8,
+                    (60.0 + 120.0 * heat) as u8,
+                ));
+                canvas.fill_rect(Rect::new(
+                    column as i32 * LINE_SPACING,
+                    row as i32 * LINE_SPACING,
+                    LINE_SPACING as u32,
+                    LINE_SPACING as u32,
+                ))?;
+            }
+        }
+        canvas.set_blend_mode(BlendMode::None);
+
+        Ok(())
+    }
+
+    pub fn render_weather(canvas: &mut Canvas<Window>, weather: Weather, tick: u64) -> Result<(), String> {
+        if let Some(tint) = weather.tint() {
+            canvas.set_blend_mode(BlendMode::Blend);
+            canvas.set_draw_color(tint);
+            canvas.fill_rect(None)?;
+            if weather == Weather::Rain {
+                Self::render_rain(canvas, tick)?;
+            }
+            canvas.set_blend_mode(BlendMode::None);
+        }
+
+        Ok(())
+    }
+
+    /// Falling streaks over the whole window. Each streak keeps its column and starting
+    /// height from a fixed scramble of its index and moves down with the tick, so the
+    /// rain needs no state of its own and pauses with the simulation.
+    fn render_rain(canvas: &mut Canvas<Window>, tick: u64) -> Result<(), String> {
+        let (width, height) = world_size();
+        let (width, height) = (width as i32, height as i32);
+        let length = LINE_SPACING / 2;
+        let fallen = (tick % height as u64) as i32 * RAIN_FALL;
+        canvas.set_draw_color(RAIN_COLOR);
+        for streak in 0..RAIN_STREAKS {
+            let x = streak.wrapping_mul(7919) % width;
+            let y = (streak.wrapping_mul(104_729) % height + fallen) % height;
+            let drift = y * RAIN_SLANT / RAIN_FALL;
+            let x = (x - drift).rem_euclid(width);
+            canvas.draw_line(Point::new(x, y), Point::new(x - RAIN_SLANT, y + length))?;
+        }
+        Ok(())
+    }
+}
+
+/// Rects making up the arrow for a route, sized to the lane: a shaft from behind the
+/// middle, bent towards the turn, ending in a triangular head drawn as rows of rects.
+fn arrow_parts(turn: TurnDirection) -> Vec<ArrowPart> {
+    let unit = LINE_SPACING / 10;
+    let shaft = unit / 2;
+    let head = 2 * unit;
+    // Rows of the head, widest at its base, for a head pointing along `along` or across
+    let head_rows = |tip: i32, step: i32, center: i32, pointing_along: bool| {
+        (0..=head).map(move |row| {
+            let at = tip - step * row;
+            let span = center - row / 2..=center + row / 2;
+            if pointing_along {
+                (at..=at, span)
+            } else {
+                (span, at..=at)
+            }
+        })
+    };
+
+    let mut parts: Vec<ArrowPart> = vec![(-3 * unit..=unit, -shaft..=shaft)];
+    match turn {
+        TurnDirection::Straight => {
+            parts.push((unit..=2 * unit, -shaft..=shaft));
+            parts.extend(head_rows(2 * unit + head, 1, 0, true));
+        }
+        TurnDirection::Left | TurnDirection::Right => {
+            let side = if turn == TurnDirection::Left { 1 } else { -1 };
+            let bar = [side * shaft, side * 2 * unit];
+            parts.push((unit - shaft..=unit + shaft, bar[0].min(bar[1])..=bar[0].max(bar[1])));
+            parts.extend(head_rows(side * (2 * unit + head), side, unit, false));
+        }
+        TurnDirection::UTurn => {
+            parts.push((unit - shaft..=unit + shaft, 0..=2 * unit));
+            parts.push((-unit..=unit, 2 * unit - shaft..=2 * unit + shaft));
+            parts.extend(head_rows(-unit - head, -1, 2 * unit, true));
+        }
+    }
+    parts
+}
+
+/// Screen rect of an arrow part placed at `middle` in a lane whose traffic moves in `travel`.
+fn lane_rect(middle: Position, travel: Direction, along: RangeInclusive<i32>, across: RangeInclusive<i32>) -> Rect {
+    let left_side = travel.turn_left();
+    let corner = |along: i32, across: i32| {
+        middle
+            .move_in_direction(&travel, along)
+            .move_in_direction(&left_side, across)
+    };
+    let a = corner(*along.start(), *across.start());
+    let b = corner(*along.end(), *across.end());
+    Rect::new(
+        a.x.min(b.x),
+        a.y.min(b.y),
+        (a.x - b.x).unsigned_abs() + 1,
+        (a.y - b.y).unsigned_abs() + 1,
+    )
 }
@@ -0,0 +1,64 @@
+use crate::direction::TurnDirection;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, TextureQuery};
+use sdl2::ttf::Font;
+use sdl2::video::Window;
+
+pub struct RouteLegend;
+
+impl RouteLegend {
+    /// Tint applied to vehicles taking the given route when coloring by route.
+    pub fn route_color(turn_direction: TurnDirection) -> Color {
+        match turn_direction {
+            TurnDirection::Left => Color::RGB(80, 160, 255),
+            TurnDirection::Straight => Color::RGB(90, 220, 90),
+            TurnDirection::Right => Color::RGB(255, 170, 60),
+            TurnDirection::UTurn => Color::RGB(220, 90, 220),
+        }
+    }
+
+    /// Draws a swatch and label for every route in the top-left corner.
+    pub fn render(canvas: &mut Canvas<Window>, font: &Font) -> Result<(), String> {
+        let texture_creator = canvas.texture_creator();
+        let mut y_offset = 10;
+
+        for turn_direction in TurnDirection::ALL {
+            let surface = font
+                .render(turn_direction.name())
+                .blended(Color::RGB(255, 255, 255))
+                .map_err(|e| e.to_string())?;
+            let texture = texture_creator
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())?;
+            let TextureQuery { width, height, .. } = texture.query();
+
+            canvas.set_draw_color(Self::route_color(turn_direction));
+            canvas.fill_rect(Rect::new(10, y_offset, height, height))?;
+            canvas.copy(
+                &texture,
+                None,
+                Some(Rect::new(15 + height as i32, y_offset, width, height)),
+            )?;
+
+            y_offset += height as i32 + 5;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_route_has_a_colour_of_its_own() {
+        let colors = TurnDirection::ALL.map(RouteLegend::route_color);
+        for (index, color) in colors.iter().enumerate() {
+            assert!(!colors[index + 1..].contains(color), "{:?} is used twice", color);
+        }
+        // Tinted cars stand out from the grey road they drive on
+        assert!(colors.iter().all(|color| color.r.max(color.g).max(color.b) >= 200));
+    }
+}
@@ -1,24 +1,32 @@
 use crate::direction::Direction;
-use crate::simulation::statistics::Statistics;
+use crate::simulation::profiler::PhaseTiming;
+use crate::simulation::statistics::{Statistics, THROUGHPUT_BUCKET_SECONDS};
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::{Canvas, TextureQuery};
 use sdl2::ttf::Font;
 use sdl2::video::Window;
 
+// Height reserved at the bottom of the modal for the throughput chart
+const CHART_HEIGHT: u32 = 90;
+const COLUMN_MARGIN: i32 = 20;
+// The grade letter is drawn this many times the font's size
+const GRADE_SCALE: u32 = 4;
+
 pub fn render_stats_modal(
     canvas: &mut Canvas<Window>,
     stats: &Statistics,
+    profile: &[PhaseTiming],
     font: &Font,
 ) -> Result<(), String> {
     let summary = stats.get_summary();
 
-    let (window_width, window_height) = canvas.output_size()?;
+    let (window_width, window_height) = canvas.logical_size();
     canvas.set_draw_color(Color::RGBA(0, 0, 0, 180));
     canvas.fill_rect(Rect::new(0, 0, window_width, window_height))?;
 
-    let modal_width = (window_width as f32 * 0.7) as u32;
-    let modal_height = (window_height as f32 * 0.8) as u32;
+    let modal_width = (window_width as f32 * 0.9) as u32;
+    let modal_height = (window_height as f32 * 0.9) as u32;
     let modal_x = (window_width - modal_width) / 2;
     let modal_y = (window_height - modal_height) / 2;
 
@@ -62,78 +70,315 @@ pub fn render_stats_modal(
         "N/A (no vehicles passed)".to_string()
     };
 
-    let stats_lines = vec![
-        "Traffic Simulation Statistics".to_string(),
-        "-------------------------".to_string(),
-        format!("Total Vehicles Spawned: {}", summary.total_vehicles),
-        format!("Max number of vehicles that passed the intersection: {}", summary.total_vehicles_passed),
+    let mut left_column = vec![
+        "Overview".to_string(),
+        "--------".to_string(),
+        format!("Controller: {}", summary.controller),
+        format!("Total vehicles spawned: {}", summary.total_vehicles),
+        format!("Vehicles passed: {}", summary.total_vehicles_passed),
         format!(
-            "Max Vehicles in Intersection (simultaneously): {}",
+            "Max vehicles in intersection at once: {}",
             summary.max_vehicles_in_intersection
         ),
-        format!("Simulation Duration: {:.2} seconds", summary.duration),
+        format!(
+            "Spawn queue: {} (longest {})",
+            summary.spawn_queue, summary.max_spawn_queue
+        ),
+        format!("Simulation duration: {:.2} seconds", summary.duration),
         String::new(),
-        "Vehicle Speeds".to_string(),
-        "-------------".to_string(),
-        format!("Max velocity: 3.0 pixels/frame"),
-        format!("Min velocity: 1.0 pixels/frame"),
-        "(Vehicles have 3 speed levels: slow, medium, fast)".to_string(),
+        "Weather (crossings per minute)".to_string(),
+        "-----------------".to_string(),
+    ];
+    for weather in &summary.weather {
+        left_column.push(format!(
+            "{}: {:.1} ({} in {:.0} s)",
+            weather.weather, weather.per_minute, weather.completed, weather.seconds
+        ));
+    }
+    left_column.extend([
         String::new(),
         "Intersection Times".to_string(),
         "-----------------".to_string(),
-        format!("Max time that took the vehicle to pass the intersection: {}", max_time_str),
-        format!("Min time that took the vehicle to pass the intersection: {}", min_time_str),
+        format!("Max crossing time: {}", max_time_str),
+        format!("Min crossing time: {}", min_time_str),
+        match summary.crossing_percentiles {
+            Some(p) => format!("p50 / p90 / p99: {:.2} / {:.2} / {:.2} s", p.p50, p.p90, p.p99),
+            None => "p50 / p90 / p99: N/A".to_string(),
+        },
         String::new(),
+        "Routes (done/spawned, min/avg/max)".to_string(),
+        "-----------------".to_string(),
+    ]);
+    for route in &summary.routes {
+        let times = match (route.min_time, route.average_time, route.max_time) {
+            (Some(min), Some(average), Some(max)) => {
+                format!("{:.2} / {:.2} / {:.2} s", min, average, max)
+            }
+            _ => "N/A".to_string(),
+        };
+        left_column.push(format!(
+            "{}: {}/{}, {}",
+            route.route, route.completed, route.spawned, times
+        ));
+    }
+
+    let grade = &summary.grade;
+    let mut right_column = vec![
+        format!("Grade: {:.1} / 100", grade.score),
+        "------".to_string(),
+    ];
+    for penalty in &grade.penalties {
+        right_column.push(format!(
+            "{}: {} (-{:.1})",
+            penalty.reason, penalty.count, penalty.points
+        ));
+    }
+    right_column.push(String::new());
+
+    let collisions_line = format!("Physical collisions: {}", summary.total_collisions);
+    right_column.extend([
         "Safety Statistics".to_string(),
         "----------------".to_string(),
+        collisions_line.clone(),
         format!("Close calls: {}", summary.total_close_calls),
-        String::new(),
-        "Vehicle Origins".to_string(),
-        "--------------".to_string(),
+        match summary.lowest_time_to_collision {
+            Some(lowest) => format!(
+                "TTC warnings: {} (lowest {:.2} s)",
+                summary.total_ttc_warnings, lowest
+            ),
+            None => format!("TTC warnings: {}", summary.total_ttc_warnings),
+        },
+        format!("Removed while stuck: {}", summary.total_removed_stuck),
+        format!("Wrong-way anomalies: {}", summary.total_anomalies),
+        format!("Full stops: {}", summary.total_stops),
+        format!("Total time stopped: {:.1} s", summary.total_stopped_time),
         format!(
-            "From North: {}",
-            stats.vehicles_spawned.get(&Direction::Down).unwrap_or(&0)
+            "Stopped per vehicle: avg {:.1} s, worst {:.1} s",
+            summary.average_stopped_time, summary.worst_stopped_time
         ),
         format!(
-            "From South: {}",
-            stats.vehicles_spawned.get(&Direction::Up).unwrap_or(&0)
-        ),
-        format!(
-            "From East: {}",
-            stats.vehicles_spawned.get(&Direction::Left).unwrap_or(&0)
-        ),
-        format!(
-            "From West: {}",
-            stats.vehicles_spawned.get(&Direction::Right).unwrap_or(&0)
+            "Resolver steps: worst vehicle {}, worst tick {}",
+            summary.max_vehicle_resolver_steps, summary.max_tick_resolver_steps
         ),
         String::new(),
-        "Press ESC again to close".to_string(),
-    ];
+        "Vehicle Origins".to_string(),
+        "--------------".to_string(),
+    ]);
+    for (direction, queue) in Direction::ALL
+        .into_iter()
+        .filter(Direction::is_open)
+        .zip(&summary.queues)
+    {
+        right_column.push(format!(
+            "From {}: {} (queue max {}, avg {:.1})",
+            direction.compass_name(),
+            stats.vehicles_spawned.get(&direction).unwrap_or(&0),
+            queue.max,
+            queue.average
+        ));
+    }
 
-    let mut y_offset = modal_y as i32 + 20;
-    for line in stats_lines.iter() {
-        if line.is_empty() {
-            y_offset += 15;
-            continue;
+    let white = Color::RGB(255, 255, 255);
+    let title = "Traffic Simulation Statistics";
+    let (title_width, _) = font.size_of(title).map_err(|e| e.to_string())?;
+    render_text(
+        canvas,
+        font,
+        title,
+        white,
+        modal_x as i32 + (modal_width as i32 - title_width as i32) / 2,
+        modal_y as i32 + 15,
+    )?;
+
+    render_grade_letter(
+        canvas,
+        font,
+        grade.letter,
+        modal_x as i32 + modal_width as i32 - COLUMN_MARGIN,
+        modal_y as i32 + 10,
+    )?;
+
+    let column_width = modal_width as i32 / 2;
+    for (index, column) in [left_column, right_column].iter().enumerate() {
+        let x = modal_x as i32 + COLUMN_MARGIN + index as i32 * column_width;
+        let mut y_offset = modal_y as i32 + 50;
+        for line in column {
+            if line.is_empty() {
+                y_offset += 10;
+                continue;
+            }
+
+            let color = if *line == collisions_line {
+                Color::RGB(255, 40, 40)
+            } else {
+                white
+            };
+            y_offset += render_text(canvas, font, line, color, x, y_offset)? as i32 + 3;
         }
+    }
 
-        let surface = font
-            .render(line)
-            .blended(Color::RGB(255, 255, 255))
-            .map_err(|e| e.to_string())?;
+    let footer = "Press ESC to return to the menu";
+    let (footer_width, footer_height) = font.size_of(footer).map_err(|e| e.to_string())?;
+    let footer_y = (modal_y + modal_height - footer_height) as i32 - 10;
+    render_text(
+        canvas,
+        font,
+        footer,
+        white,
+        modal_x as i32 + (modal_width as i32 - footer_width as i32) / 2,
+        footer_y,
+    )?;
 
-        let texture_creator = canvas.texture_creator();
-        let texture = texture_creator
-            .create_texture_from_surface(&surface)
-            .map_err(|e| e.to_string())?;
+    // The chart shares the bottom of the modal with the frame time breakdown
+    let bottom_width = (modal_width - 3 * COLUMN_MARGIN as u32) / 2;
+    let chart_area = Rect::new(
+        modal_x as i32 + COLUMN_MARGIN,
+        footer_y - CHART_HEIGHT as i32 - 15,
+        bottom_width,
+        CHART_HEIGHT,
+    );
+    render_throughput_chart(canvas, font, &summary.throughput, chart_area)?;
 
-        let TextureQuery { width, height, .. } = texture.query();
+    let mut profile_area = chart_area;
+    profile_area.set_x(modal_x as i32 + column_width + COLUMN_MARGIN);
+    render_frame_times(
+        canvas,
+        font,
+        profile,
+        (summary.dropped_frames, summary.frames),
+        profile_area,
+    )
+}
 
-        let x = modal_x as i32 + ((modal_width as i32 - width as i32) / 2);
-        canvas.copy(&texture, None, Some(Rect::new(x, y_offset, width, height)))?;
+/// Average and worst time per frame of each phase over the run, two phases to a row,
+/// under a heading with the frames dropped.
+fn render_frame_times(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    profile: &[PhaseTiming],
+    (dropped, frames): (u32, u32),
+    area: Rect,
+) -> Result<(), String> {
+    let white = Color::RGB(255, 255, 255);
+    let heading = format!("Frame ms avg / worst, {} of {} dropped", dropped, frames);
+    let mut y = area.y();
+    y += render_text(canvas, font, &heading, white, area.x(), y)? as i32 + 3;
 
-        y_offset += height as i32 + 5;
+    let ms = |time: std::time::Duration| time.as_secs_f64() * 1000.0;
+    let cell_width = area.width() as i32 / 2;
+    for row in profile.chunks(2) {
+        let mut height = 0;
+        for (column, timing) in row.iter().enumerate() {
+            let text = format!("{} {:.2} / {:.2}", timing.phase.name(), ms(timing.average), ms(timing.worst));
+            height = render_text(canvas, font, &text, white, area.x() + column as i32 * cell_width, y)?;
+        }
+        y += height as i32 + 3;
     }
-
     Ok(())
 }
+
+/// Bar chart of completed crossings per bucket, scaled to the busiest bucket.
+fn render_throughput_chart(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    throughput: &[u32],
+    area: Rect,
+) -> Result<(), String> {
+    let max_count = throughput.iter().copied().max().unwrap_or(0);
+    let label = format!(
+        "Vehicles completed per {} s (max {}, min {})",
+        THROUGHPUT_BUCKET_SECONDS,
+        max_count,
+        throughput.iter().copied().min().unwrap_or(0)
+    );
+    let label_height = render_text(canvas, font, &label, Color::RGB(255, 255, 255), area.x(), area.y())?;
+
+    let bars_top = area.y() + label_height as i32 + 4;
+    let bars_height = (area.bottom() - bars_top).max(1) as u32;
+
+    canvas.set_draw_color(Color::RGB(200, 200, 200));
+    canvas.fill_rect(Rect::new(area.x(), area.bottom(), area.width(), 1))?;
+
+    if max_count == 0 || throughput.is_empty() {
+        return Ok(());
+    }
+
+    let bar_width = (area.width() / throughput.len() as u32).max(1);
+    let gap = if bar_width > 2 { 1 } else { 0 };
+    // Very long runs keep the first buckets that fit
+    let bars: Vec<Rect> = throughput
+        .iter()
+        .take((area.width() / bar_width) as usize)
+        .enumerate()
+        .filter(|(_, &count)| count > 0)
+        .map(|(bucket, &count)| {
+            let height = (bars_height * count / max_count).max(1);
+            Rect::new(
+                area.x() + (bucket as u32 * bar_width) as i32,
+                area.bottom() - height as i32,
+                bar_width - gap,
+                height,
+            )
+        })
+        .collect();
+
+    canvas.set_draw_color(Color::RGB(100, 180, 255));
+    canvas.fill_rects(&bars)
+}
+
+/// Draws the grade letter enlarged, with its top-right corner at (right, y), green
+/// for a good grade, yellow for a middling one and red for a failing one.
+fn render_grade_letter(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    letter: &str,
+    right: i32,
+    y: i32,
+) -> Result<(), String> {
+    let color = match letter {
+        "A" | "B" => Color::RGB(80, 220, 80),
+        "C" | "D" => Color::RGB(255, 220, 0),
+        _ => Color::RGB(255, 40, 40),
+    };
+    let surface = font
+        .render(letter)
+        .blended(color)
+        .map_err(|e| e.to_string())?;
+    let texture_creator = canvas.texture_creator();
+    let texture = texture_creator
+        .create_texture_from_surface(&surface)
+        .map_err(|e| e.to_string())?;
+
+    let TextureQuery { width, height, .. } = texture.query();
+    let (width, height) = (width * GRADE_SCALE, height * GRADE_SCALE);
+    canvas.copy(
+        &texture,
+        None,
+        Some(Rect::new(right - width as i32, y, width, height)),
+    )
+}
+
+/// Draws `text` with its top-left corner at (x, y) and returns its height.
+fn render_text(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    text: &str,
+    color: Color,
+    x: i32,
+    y: i32,
+) -> Result<u32, String> {
+    let surface = font
+        .render(text)
+        .blended(color)
+        .map_err(|e| e.to_string())?;
+
+    let texture_creator = canvas.texture_creator();
+    let texture = texture_creator
+        .create_texture_from_surface(&surface)
+        .map_err(|e| e.to_string())?;
+
+    let TextureQuery { width, height, .. } = texture.query();
+    canvas.copy(&texture, None, Some(Rect::new(x, y, width, height)))?;
+
+    Ok(height)
+}
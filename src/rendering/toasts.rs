@@ -0,0 +1,83 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture, TextureCreator, TextureQuery};
+use sdl2::ttf::Font;
+use sdl2::video::{Window, WindowContext};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Older messages are dropped once this many are on screen
+const MAX_TOASTS: usize = 4;
+// Messages fade out over the end of their duration
+const FADE: Duration = Duration::from_millis(500);
+// Space left below the stack for the HUD line
+const BOTTOM_MARGIN: i32 = 34;
+
+struct Toast<'a> {
+    text: String,
+    texture: Option<Texture<'a>>,
+    shown_at: Instant,
+    duration: Duration,
+}
+
+/// Short feedback messages stacked in the bottom-left corner, newest at the bottom.
+pub struct Toasts<'a> {
+    texture_creator: &'a TextureCreator<WindowContext>,
+    toasts: VecDeque<Toast<'a>>,
+}
+
+impl<'a> Toasts<'a> {
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>) -> Self {
+        Self {
+            texture_creator,
+            toasts: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, text: impl Into<String>, duration: Duration) {
+        if self.toasts.len() == MAX_TOASTS {
+            self.toasts.pop_front();
+        }
+        self.toasts.push_back(Toast {
+            text: text.into(),
+            texture: None,
+            shown_at: Instant::now(),
+            duration,
+        });
+    }
+
+    /// Drops expired messages and draws the rest, rendering each message's text once.
+    pub fn render(&mut self, canvas: &mut Canvas<Window>, font: &Font) -> Result<(), String> {
+        self.toasts
+            .retain(|toast| toast.shown_at.elapsed() < toast.duration);
+
+        let (_, window_height) = canvas.logical_size();
+        let mut bottom = window_height as i32 - BOTTOM_MARGIN;
+        for toast in self.toasts.iter_mut().rev() {
+            if toast.texture.is_none() {
+                let surface = font
+                    .render(&toast.text)
+                    .blended(Color::RGB(255, 255, 255))
+                    .map_err(|e| e.to_string())?;
+                let texture = self
+                    .texture_creator
+                    .create_texture_from_surface(&surface)
+                    .map_err(|e| e.to_string())?;
+                toast.texture = Some(texture);
+            }
+            let Some(texture) = toast.texture.as_mut() else {
+                continue;
+            };
+
+            let remaining = toast.duration.saturating_sub(toast.shown_at.elapsed());
+            let alpha = (255.0 * remaining.as_secs_f32() / FADE.as_secs_f32()).min(255.0) as u8;
+            texture.set_alpha_mod(alpha);
+
+            let TextureQuery { width, height, .. } = texture.query();
+            bottom -= height as i32 + 4;
+            canvas.copy(texture, None, Some(Rect::new(10, bottom, width, height)))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use crate::core::Vehicle;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+
+const DOT_SIZE: u32 = 4;
+// Opacity of the newest dot; older ones fade towards transparent
+const MAX_ALPHA: u8 = 180;
+
+pub struct Trails;
+
+impl Trails {
+    /// Draws where each vehicle has recently been as a line of dots in its colour,
+    /// fading out towards the oldest.
+    pub fn render(canvas: &mut Canvas<Window>, vehicles: &[Vehicle]) -> Result<(), String> {
+        canvas.set_blend_mode(BlendMode::Blend);
+
+        for vehicle in vehicles {
+            let half = vehicle.rect.width() as i32 / 2 - DOT_SIZE as i32 / 2;
+            let count = vehicle.trail.len();
+            let color = vehicle.color;
+            for (age, position) in vehicle.trail.iter().rev().enumerate() {
+                let alpha = MAX_ALPHA as usize * (count - age) / count;
+                canvas.set_draw_color(Color::RGBA(color.r, color.g, color.b, alpha as u8));
+                canvas.fill_rect(Rect::new(
+                    position.x + half,
+                    position.y + half,
+                    DOT_SIZE,
+                    DOT_SIZE,
+                ))?;
+            }
+        }
+
+        canvas.set_blend_mode(BlendMode::None);
+        Ok(())
+    }
+}
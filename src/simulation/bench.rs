@@ -0,0 +1,205 @@
+use crate::audio::AudioEngine;
+use crate::config::SimulationConfig;
+use crate::constants::{SIMULATION_STEP, SPAWN_COOLDOWN};
+use crate::direction::Direction;
+use crate::simulation::events::SimEvent;
+use crate::simulation::stress::{StressReport, StressTest};
+use crate::simulation::trace::TraceSource;
+use crate::simulation::VehicleManager;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Throughput of the simulation without rendering.
+pub struct BenchReport {
+    pub steps: u64,
+    pub vehicles_spawned: u32,
+    pub vehicles_passed: u32,
+    pub average_alive: f64,
+    pub elapsed: Duration,
+}
+
+impl BenchReport {
+    pub fn steps_per_second(&self) -> f64 {
+        self.steps as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    pub fn print(&self) {
+        println!("Benchmark ran {:.2} seconds", self.elapsed.as_secs_f64());
+        println!("Simulation steps: {} ({:.0} per second)", self.steps, self.steps_per_second());
+        println!("Vehicles spawned: {}", self.vehicles_spawned);
+        println!("Vehicles processed (passed the intersection): {}", self.vehicles_passed);
+        println!("Average vehicles alive: {:.1}", self.average_alive);
+    }
+}
+
+/// Runs the simulation as fast as it goes for `budget` of wall-clock time, spawning
+/// from every open arm as often as the spawn cooldown would allow in simulated time.
+pub fn run_bench(budget: Duration) -> BenchReport {
+    let spawn_every = (SPAWN_COOLDOWN.as_millis() / SIMULATION_STEP.as_millis()).max(1) as u64;
+    let mut manager = VehicleManager::new(Rc::new(AudioEngine::silent()));
+    let start = Instant::now();
+    let mut steps: u64 = 0;
+    let mut alive_total: u64 = 0;
+
+    while start.elapsed() < budget {
+        if steps.is_multiple_of(spawn_every) {
+            for direction in Direction::ALL {
+                manager.spawn_unthrottled(direction);
+            }
+        }
+        manager.update_vehicles();
+        manager.drain_events();
+        alive_total += manager.get_vehicles().len() as u64;
+        steps += 1;
+    }
+
+    let statistics = manager.get_statistics();
+    BenchReport {
+        steps,
+        vehicles_spawned: statistics.total_vehicles,
+        vehicles_passed: statistics.total_vehicles_passed,
+        average_alive: alive_total as f64 / steps.max(1) as f64,
+        elapsed: start.elapsed(),
+    }
+}
+
+/// How a scenario played out without rendering.
+pub struct ScenarioReport {
+    pub ticks: u64,
+    // Whether every vehicle spawned and left the road within the time budget
+    pub finished: bool,
+    pub vehicles_spawned: u32,
+    pub vehicles_passed: u32,
+    pub close_calls: u32,
+    pub collisions: u32,
+    // Vehicles whose spawn had to wait, with how many ticks late they were requested
+    pub delayed: Vec<(usize, u64)>,
+}
+
+impl ScenarioReport {
+    pub fn print(&self) {
+        let seconds = self.ticks as f64 * SIMULATION_STEP.as_secs_f64();
+        if self.finished {
+            println!("Scenario finished after {} ticks ({:.2} simulated seconds)", self.ticks, seconds);
+        } else {
+            println!("Scenario did not finish within the budget, stopped after {} ticks", self.ticks);
+        }
+        println!("Vehicles spawned: {}", self.vehicles_spawned);
+        println!("Vehicles processed (passed the intersection): {}", self.vehicles_passed);
+        println!("Close calls: {}", self.close_calls);
+        println!("Collisions: {}", self.collisions);
+        for &(id, delay) in &self.delayed {
+            println!(
+                "Spawn of vehicle {} delayed by {} ticks ({} ms)",
+                id,
+                delay,
+                delay * SIMULATION_STEP.as_millis() as u64
+            );
+        }
+    }
+}
+
+/// Plays `scenario` as fast as the simulation goes until every vehicle has spawned and
+/// left the road, or `budget` of wall-clock time runs out.
+pub fn run_scenario(scenario: TraceSource, budget: Duration) -> ScenarioReport {
+    let mut manager = VehicleManager::new(Rc::new(AudioEngine::silent()));
+    manager.set_trace(scenario);
+    let start = Instant::now();
+    let mut delayed = Vec::new();
+
+    let finished = loop {
+        if manager.is_trace_finished() && manager.get_vehicles().is_empty() {
+            break true;
+        }
+        if start.elapsed() >= budget {
+            break false;
+        }
+        manager.update_vehicles();
+        for event in manager.drain_events() {
+            if let SimEvent::SpawnDelayed { id, delay } = event {
+                delayed.push((id, delay));
+            }
+        }
+    };
+
+    let statistics = manager.get_statistics();
+    ScenarioReport {
+        ticks: manager.get_tick(),
+        finished,
+        vehicles_spawned: statistics.total_vehicles,
+        vehicles_passed: statistics.total_vehicles_passed,
+        close_calls: statistics.total_close_calls,
+        collisions: statistics.collisions.len() as u32,
+        delayed,
+    }
+}
+
+/// Keeps about `target` vehicles on the road for `budget` of wall-clock time, timing
+/// every tick. The vehicle cap is raised to the target if it is lower.
+pub fn run_stress(target: usize, budget: Duration) -> StressReport {
+    let config = SimulationConfig {
+        max_vehicles: SimulationConfig::default().max_vehicles.max(target),
+        ..SimulationConfig::default()
+    };
+    let mut manager = VehicleManager::new_with_config(Rc::new(AudioEngine::silent()), &config);
+    let mut stress = StressTest::new(target);
+    let start = Instant::now();
+
+    while start.elapsed() < budget {
+        let tick_start = Instant::now();
+        stress.top_up(&mut manager);
+        manager.update_vehicles();
+        manager.drain_events();
+        stress.record_frame(tick_start.elapsed(), manager.get_vehicles().len());
+    }
+    stress.report(&manager)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(scenario: &str) -> ScenarioReport {
+        let scenario = TraceSource::parse_scenario(scenario).expect("a valid scenario");
+        run_scenario(scenario, Duration::from_secs(60))
+    }
+
+    #[test]
+    fn a_short_bench_finishes_and_reports_its_throughput() {
+        let budget = Duration::from_millis(200);
+        let report = run_bench(budget);
+        assert!(report.elapsed >= budget);
+        assert!(report.steps > 0);
+        assert!(report.steps_per_second() > 0.0);
+        // Every arm gets a vehicle on the very first step
+        assert!(report.vehicles_spawned >= Direction::ALL.len() as u32);
+        assert!(report.average_alive > 0.0);
+    }
+
+    #[test]
+    fn the_checked_in_scenarios_play_without_collisions() {
+        for entry in std::fs::read_dir("scenarios").expect("the scenarios directory") {
+            let path = entry.unwrap().path();
+            let report = run_scenario(
+                TraceSource::scenario_from_file(path.to_str().unwrap()).unwrap(),
+                Duration::from_secs(60),
+            );
+            assert!(report.finished, "{} did not finish", path.display());
+            assert_eq!(report.collisions, 0, "{}", path.display());
+            assert_eq!(report.vehicles_passed, report.vehicles_spawned, "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn a_spawn_onto_a_blocked_arm_is_reported_late() {
+        let report = play("0,north,south\n0,north,south\n0,east,west\n");
+        assert!(report.finished);
+        assert_eq!(report.vehicles_passed, 3);
+        // The second from the north waits out the cooldown and its spawn area
+        let [(id, delay)] = report.delayed[..] else {
+            panic!("delayed: {:?}", report.delayed);
+        };
+        assert_eq!(id, 2);
+        assert!(delay > 0);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::audio::AudioEngine;
+use crate::config::SimulationConfig;
+use crate::constants::SIMULATION_STEP;
+use crate::direction::Direction;
+use crate::intersection::{Controller, SmartController, TrafficLight};
+use crate::simulation::route_distribution::RouteDistribution;
+use crate::simulation::spawn_scheduler::{SpawnScheduler, SpawnSettings};
+use crate::simulation::trace::{TraceEntry, TraceSource};
+use crate::simulation::VehicleManager;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::fmt::Write;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// How one controller handled the compared spawn schedule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControllerResult {
+    pub controller: &'static str,
+    pub vehicles_passed: u32,
+    // Vehicles taken off the road after standing still too long, which never crossed
+    pub removed_stuck: u32,
+    // Crossings per simulated minute, from the first tick until the road was clear
+    pub throughput: f64,
+    pub average_crossing: Option<f32>,
+    pub p95_crossing: Option<f32>,
+    pub close_calls: u32,
+    // Longest queue seen on each arm, in `Direction::ALL` order
+    pub max_queues: [u32; 4],
+    // Whether every vehicle spawned and left the road before the tick limit
+    pub finished: bool,
+}
+
+/// The same spawn schedule played under the reservation algorithm alone and under a
+/// fixed-cycle traffic light.
+pub struct ComparisonReport {
+    pub vehicles: usize,
+    pub results: Vec<ControllerResult>,
+}
+
+impl ComparisonReport {
+    fn headers() -> Vec<String> {
+        let mut headers: Vec<String> = [
+            "controller",
+            "passed",
+            "removed stuck",
+            "throughput (per min)",
+            "average crossing (s)",
+            "p95 crossing (s)",
+            "close calls",
+        ]
+        .map(String::from)
+        .to_vec();
+        headers.extend(Direction::ALL.map(|arm| format!("max queue {}", arm.compass_name())));
+        headers
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        let seconds = |time: Option<f32>| time.map_or_else(|| "N/A".to_string(), |time| format!("{:.2}", time));
+        self.results
+            .iter()
+            .map(|result| {
+                let passed = if result.finished {
+                    result.vehicles_passed.to_string()
+                } else {
+                    format!("{} (unfinished)", result.vehicles_passed)
+                };
+                let mut row = vec![
+                    result.controller.to_string(),
+                    passed,
+                    result.removed_stuck.to_string(),
+                    format!("{:.1}", result.throughput),
+                    seconds(result.average_crossing),
+                    seconds(result.p95_crossing),
+                    result.close_calls.to_string(),
+                ];
+                row.extend(result.max_queues.map(|max| max.to_string()));
+                row
+            })
+            .collect()
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+        let _ = writeln!(markdown, "| {} |", Self::headers().join(" | "));
+        let _ = writeln!(markdown, "|{}", "---|".repeat(Self::headers().len()));
+        for row in self.rows() {
+            let _ = writeln!(markdown, "| {} |", row.join(" | "));
+        }
+        markdown
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        let _ = writeln!(csv, "{}", Self::headers().join(","));
+        for row in self.rows() {
+            let _ = writeln!(csv, "{}", row.join(","));
+        }
+        csv
+    }
+
+    pub fn print(&self) {
+        println!("Compared controllers over the same {} spawns", self.vehicles);
+        print!("{}", self.to_markdown());
+    }
+
+    /// Writes the table to `path`, as CSV when it ends in `.csv` and as markdown
+    /// otherwise.
+    pub fn export(&self, path: &str) -> Result<(), String> {
+        let contents = if path.ends_with(".csv") {
+            self.to_csv()
+        } else {
+            self.to_markdown()
+        };
+        std::fs::write(path, contents).map_err(|e| e.to_string())
+    }
+}
+
+/// Spawns over the first `ticks` ticks, drawn from `config`'s spawn model, arm weights
+/// and route weights with its seed.
+pub fn spawn_schedule(config: &SimulationConfig, ticks: u64) -> Vec<TraceEntry> {
+    let mut scheduler = SpawnScheduler::new(SpawnSettings {
+        model: config.spawn_model,
+        interval_ms: config.spawn_interval().as_millis() as u64,
+        seed: config.spawn_seed,
+        direction_weights: config.direction_weights,
+    });
+    let routes = RouteDistribution::new(&config.route_weights.to_weights()).expect("the config is validated");
+    let mut rng = StdRng::seed_from_u64(config.spawn_seed);
+    let mut schedule = Vec::new();
+    for tick in 0..ticks {
+        for direction in scheduler.due() {
+            if let Some(target) = routes.sample(&mut rng, direction, |_| true) {
+                schedule.push(TraceEntry { tick, direction, target });
+            }
+        }
+    }
+    schedule
+}
+
+/// Plays `schedule` under `controller` until it has spawned and the road is clear, or
+/// for at most `max_ticks`.
+fn play(
+    config: &SimulationConfig,
+    controller: Box<dyn Controller>,
+    schedule: &[TraceEntry],
+    max_ticks: u64,
+) -> ControllerResult {
+    let mut manager = VehicleManager::new_with_config(Rc::new(AudioEngine::silent()), config);
+    let name = controller.name();
+    manager.set_controller(controller);
+    manager.set_trace(TraceSource::from_entries(schedule.to_vec()));
+
+    let finished = loop {
+        if manager.is_trace_finished() && manager.get_vehicles().is_empty() {
+            break true;
+        }
+        if manager.get_tick() >= max_ticks {
+            break false;
+        }
+        manager.update_vehicles();
+        manager.drain_events();
+    };
+
+    let statistics = manager.get_statistics();
+    let minutes = manager.get_tick() as f64 * SIMULATION_STEP.as_secs_f64() / 60.0;
+    ControllerResult {
+        controller: name,
+        vehicles_passed: statistics.total_vehicles_passed,
+        removed_stuck: statistics.total_removed_stuck,
+        throughput: statistics.total_vehicles_passed as f64 / minutes.max(f64::EPSILON),
+        average_crossing: statistics.get_average_crossing_time(),
+        p95_crossing: statistics.get_crossing_percentiles().map(|percentiles| percentiles.p95),
+        close_calls: statistics.total_close_calls,
+        max_queues: Direction::ALL.map(|arm| statistics.queues.get(&arm).map_or(0, |queue| queue.max)),
+        finished,
+    }
+}
+
+/// Generates one spawn schedule of `seconds` of simulated time from `config` and plays
+/// it under each controller, giving each up to `time_limit` of simulated time to clear
+/// the road.
+pub fn run_comparison(config: &SimulationConfig, seconds: f64, time_limit: Duration) -> ComparisonReport {
+    let ticks = (seconds / SIMULATION_STEP.as_secs_f64()).round() as u64;
+    let max_ticks = (time_limit.as_millis() / SIMULATION_STEP.as_millis()) as u64;
+    let schedule = spawn_schedule(config, ticks);
+    let (green, amber, clearance) = config.traffic_light.to_ticks();
+    let controllers: [Box<dyn Controller>; 2] = [
+        Box::new(SmartController),
+        Box::new(TrafficLight::new(green, amber, clearance)),
+    ];
+
+    ComparisonReport {
+        vehicles: schedule.len(),
+        results: controllers
+            .into_iter()
+            .map(|controller| play(config, controller, &schedule, max_ticks))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_schedule_is_drawn_once_per_seed() {
+        let config = SimulationConfig::default();
+        let schedule = spawn_schedule(&config, 600);
+        assert!(!schedule.is_empty());
+        assert_eq!(schedule, spawn_schedule(&config, 600));
+        let reseeded = SimulationConfig {
+            spawn_seed: 1,
+            ..SimulationConfig::default()
+        };
+        assert_ne!(schedule, spawn_schedule(&reseeded, 600));
+    }
+
+    #[test]
+    fn both_controllers_play_every_spawn_and_fill_the_table() {
+        let report = run_comparison(&SimulationConfig::default(), 10.0, Duration::from_secs(120));
+        assert_eq!(report.results.len(), 2);
+        for result in &report.results {
+            assert!(result.finished, "{} did not clear the road", result.controller);
+            assert_eq!(
+                (result.vehicles_passed + result.removed_stuck) as usize,
+                report.vehicles,
+                "{}",
+                result.controller
+            );
+            assert!(result.average_crossing.is_some() && result.p95_crossing.is_some());
+        }
+
+        let markdown = report.to_markdown();
+        let csv = report.to_csv();
+        // A header, the markdown rule and one row per controller
+        assert_eq!(markdown.lines().count(), 4);
+        assert_eq!(csv.lines().count(), 3);
+        assert!(csv.lines().all(|line| line.split(',').count() == ComparisonReport::headers().len()));
+        assert!(markdown.contains("| smart |") && markdown.contains("| traffic light |"));
+    }
+}
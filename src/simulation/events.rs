@@ -0,0 +1,21 @@
+use crate::simulation::statistics::ExitReason;
+
+/// Something that happened during a simulation tick. The manager buffers these until
+/// the caller drains them, so tools can react to events instead of scraping output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SimEvent {
+    /// A vehicle was planned onto the road
+    VehicleSpawned { id: usize },
+    /// A trace or scenario spawn could only be requested `delay` ticks after its own tick
+    SpawnDelayed { id: usize, delay: u64 },
+    /// Two vehicles came closer than the safe distance
+    CloseCall { a: usize, b: usize, tick: u64 },
+    /// Two vehicles' rects overlapped
+    Collision { a: usize, b: usize, tick: u64 },
+    /// A vehicle was planned through the intersection, entering and clearing it on these ticks
+    PassageGranted { id: usize, entry: u64, clear: u64 },
+    /// A vehicle came out of the intersection, which counts as passing it
+    IntersectionCrossed { id: usize },
+    /// A vehicle was taken off the road
+    VehicleExited { id: usize, reason: ExitReason },
+}
@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+
+const FULL_SCORE: f32 = 100.0;
+// Lowest score for each letter, best first. Anything below the last is an F
+const LETTER_THRESHOLDS: [(&str, f32); 4] = [("A", 90.0), ("B", 80.0), ("C", 70.0), ("D", 60.0)];
+
+/// Points taken off the run's score for each incident.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GradingWeights {
+    pub close_call: f32,
+    pub collision: f32,
+    pub slow_crossing: f32,
+    /// Crossings taking longer than this many seconds count as slow.
+    pub slow_crossing_seconds: f32,
+    /// Per vehicle removed for being stuck, the simulation's sign of gridlock.
+    pub gridlock: f32,
+}
+
+impl Default for GradingWeights {
+    fn default() -> Self {
+        Self {
+            close_call: 0.5,
+            collision: 25.0,
+            slow_crossing: 0.5,
+            slow_crossing_seconds: 8.0,
+            gridlock: 10.0,
+        }
+    }
+}
+
+impl GradingWeights {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, weight) in [
+            ("close_call", self.close_call),
+            ("collision", self.collision),
+            ("slow_crossing", self.slow_crossing),
+            ("gridlock", self.gridlock),
+        ] {
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(format!("Invalid grading weight {} for {}", weight, name));
+            }
+        }
+        if !self.slow_crossing_seconds.is_finite() || self.slow_crossing_seconds <= 0.0 {
+            return Err(format!(
+                "slow_crossing_seconds must be positive, got {}",
+                self.slow_crossing_seconds
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Incidents of a finished run that cost points.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Incidents {
+    pub close_calls: u32,
+    pub collisions: u32,
+    pub slow_crossings: u32,
+    pub gridlocks: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Penalty {
+    pub reason: &'static str,
+    pub count: u32,
+    pub points: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Grade {
+    pub letter: &'static str,
+    pub score: f32,
+    pub penalties: Vec<Penalty>,
+}
+
+/// Scores a run out of 100, taking points off per incident. The score does not go
+/// below zero.
+pub fn grade(weights: &GradingWeights, incidents: &Incidents) -> Grade {
+    let penalties: Vec<Penalty> = [
+        ("close calls", incidents.close_calls, weights.close_call),
+        ("collisions", incidents.collisions, weights.collision),
+        ("slow crossings", incidents.slow_crossings, weights.slow_crossing),
+        ("gridlock", incidents.gridlocks, weights.gridlock),
+    ]
+    .into_iter()
+    .map(|(reason, count, weight)| Penalty {
+        reason,
+        count,
+        points: count as f32 * weight,
+    })
+    .collect();
+
+    let score = (FULL_SCORE - penalties.iter().map(|penalty| penalty.points).sum::<f32>()).max(0.0);
+    let letter = LETTER_THRESHOLDS
+        .iter()
+        .find(|&&(_, lowest)| score >= lowest)
+        .map_or("F", |&(letter, _)| letter);
+
+    Grade {
+        letter,
+        score,
+        penalties,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grade_of(incidents: Incidents) -> Grade {
+        grade(&GradingWeights::default(), &incidents)
+    }
+
+    fn close_calls(close_calls: u32) -> Incidents {
+        Incidents {
+            close_calls,
+            ..Incidents::default()
+        }
+    }
+
+    #[test]
+    fn a_clean_run_scores_full_marks() {
+        let grade = grade_of(Incidents::default());
+        assert_eq!((grade.letter, grade.score), ("A", FULL_SCORE));
+        assert!(grade.penalties.iter().all(|penalty| penalty.points == 0.0));
+    }
+
+    #[test]
+    fn letters_change_exactly_at_their_thresholds() {
+        // Half a point per close call by default
+        for (calls, letter) in [(20, "A"), (21, "B"), (40, "B"), (41, "C"), (60, "C"), (61, "D"), (80, "D"), (81, "F")] {
+            assert_eq!(grade_of(close_calls(calls)).letter, letter, "{} close calls", calls);
+        }
+    }
+
+    #[test]
+    fn penalties_add_up_per_incident() {
+        let grade = grade_of(Incidents {
+            close_calls: 2,
+            collisions: 1,
+            slow_crossings: 4,
+            gridlocks: 1,
+        });
+        let points: Vec<(&str, u32, f32)> = grade
+            .penalties
+            .iter()
+            .map(|penalty| (penalty.reason, penalty.count, penalty.points))
+            .collect();
+        assert_eq!(
+            points,
+            [("close calls", 2, 1.0), ("collisions", 1, 25.0), ("slow crossings", 4, 2.0), ("gridlock", 1, 10.0)]
+        );
+        assert_eq!((grade.letter, grade.score), ("D", 62.0));
+    }
+
+    #[test]
+    fn the_score_does_not_go_below_zero() {
+        let grade = grade_of(Incidents {
+            collisions: 5,
+            ..Incidents::default()
+        });
+        assert_eq!((grade.letter, grade.score), ("F", 0.0));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::constants::*;
+use crate::geometry::layout::world_size;
+use sdl2::rect::Rect;
+use serde::Serialize;
+
+/// Which count of the heatmap to show.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HeatmapLayer {
+    /// Ticks a vehicle center spent in the cell.
+    Visits,
+    /// Of those, the ticks the vehicle was standing still.
+    Stopped,
+}
+
+/// Counts how many ticks a vehicle center spent in each `LINE_SPACING` grid cell, and
+/// how many of them it spent stopped there. The grid covers the world of the layout it
+/// was made in.
+pub struct Heatmap {
+    visits: Vec<u32>,
+    stopped: Vec<u32>,
+    columns: usize,
+    rows: usize,
+    max_visits: u32,
+    max_stopped: u32,
+}
+
+/// The heatmap as row-major matrices, for the JSON export.
+#[derive(Serialize)]
+pub struct HeatmapExport {
+    pub cell_size: i32,
+    pub visits: Vec<Vec<u32>>,
+    pub stopped: Vec<Vec<u32>>,
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        // Cells at the far edges may stick out of a world that is not a whole number of them
+        let (width, height) = world_size();
+        let columns = width.div_ceil(LINE_SPACING as u32) as usize;
+        let rows = height.div_ceil(LINE_SPACING as u32) as usize;
+        Self {
+            visits: vec![0; columns * rows],
+            stopped: vec![0; columns * rows],
+            columns,
+            rows,
+            max_visits: 0,
+            max_stopped: 0,
+        }
+    }
+
+    pub fn record(&mut self, rect: &Rect, stopped: bool) {
+        let center = rect.center();
+        if center.x() < 0 || center.y() < 0 {
+            return;
+        }
+
+        let column = (center.x() / LINE_SPACING) as usize;
+        let row = (center.y() / LINE_SPACING) as usize;
+        if column >= self.columns || row >= self.rows {
+            return;
+        }
+
+        let index = row * self.columns + column;
+        self.visits[index] += 1;
+        self.max_visits = self.max_visits.max(self.visits[index]);
+        if stopped {
+            self.stopped[index] += 1;
+            self.max_stopped = self.max_stopped.max(self.stopped[index]);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn count(&self, layer: HeatmapLayer, column: usize, row: usize) -> u32 {
+        let index = row * self.columns + column;
+        match layer {
+            HeatmapLayer::Visits => self.visits[index],
+            HeatmapLayer::Stopped => self.stopped[index],
+        }
+    }
+
+    pub fn max_count(&self, layer: HeatmapLayer) -> u32 {
+        match layer {
+            HeatmapLayer::Visits => self.max_visits,
+            HeatmapLayer::Stopped => self.max_stopped,
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn export(&self) -> HeatmapExport {
+        let rows = |cells: &[u32]| cells.chunks(self.columns).map(<[u32]>::to_vec).collect();
+        HeatmapExport {
+            cell_size: LINE_SPACING,
+            visits: rows(&self.visits),
+            stopped: rows(&self.stopped),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::AudioEngine;
+    use crate::direction::{Direction, TurnDirection};
+    use crate::geometry::spawn::{lane_coordinate, route_lanes};
+    use crate::simulation::VehicleManager;
+    use std::rc::Rc;
+
+    #[test]
+    fn the_lane_cars_keep_driving_through_is_the_hottest() {
+        let mut manager = VehicleManager::new(Rc::new(AudioEngine::silent()));
+        let south = Direction::target_for(Direction::Up, TurnDirection::Straight);
+        for _ in 0..5 {
+            manager.try_spawn_on_tick(Direction::Up, Some(south)).expect("the north arm is open");
+            manager.update_vehicles();
+            while manager.population() > 0 {
+                assert!(manager.get_tick() < 5000, "a car never left");
+                manager.update_vehicles();
+            }
+        }
+
+        let lane = *route_lanes(TurnDirection::Straight).start();
+        let lane_column = ((lane_coordinate(Direction::Down, lane) + LINE_SPACING / 2) / LINE_SPACING) as usize;
+        let heatmap = manager.get_heatmap();
+        let max = heatmap.max_count(HeatmapLayer::Visits);
+        assert!(max > 0);
+        for row in 0..heatmap.rows() {
+            // The whole length of the lane was driven through
+            assert!(heatmap.count(HeatmapLayer::Visits, lane_column, row) > 0, "row {}", row);
+            for column in (0..heatmap.columns()).filter(|&column| column != lane_column) {
+                assert!(heatmap.count(HeatmapLayer::Visits, column, row) < max, "column {} row {}", column, row);
+            }
+        }
+    }
+}
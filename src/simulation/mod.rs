@@ -1,4 +1,25 @@
+pub mod bench;
 pub mod statistics;
+pub mod compare;
+pub mod events;
+pub mod grading;
+pub mod heatmap;
+#[cfg(test)]
+mod overlap_tests;
+pub mod planner;
+pub mod profiler;
+pub mod route_distribution;
+pub mod snapshot;
+pub mod spawn_scheduler;
+pub mod stress;
+pub mod timeline;
+pub mod timestep;
+pub mod trace;
 pub mod vehicle_manager;
 
+pub use events::SimEvent;
+pub use heatmap::{Heatmap, HeatmapLayer};
+pub use profiler::{Phase, Profiler};
+pub use timestep::{frame_remainder, FixedTimestep};
+pub use trace::TraceSource;
 pub use vehicle_manager::VehicleManager;
@@ -0,0 +1,118 @@
+//! The promise the whole simulation rests on: no two vehicles are ever in the same
+//! place at the same time, whatever traffic is asked for.
+
+use crate::audio::AudioEngine;
+use crate::config::SimulationConfig;
+use crate::core::Vehicle;
+use crate::direction::{Direction, TurnDirection};
+use crate::simulation::{TraceSource, VehicleManager};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::rc::Rc;
+
+// Long enough for every scenario here to spawn and clear the road
+const SCENARIO_TICKS: u64 = 3000;
+
+fn manager(seed: u64) -> VehicleManager {
+    let config = SimulationConfig {
+        spawn_seed: seed,
+        ..SimulationConfig::default()
+    };
+    VehicleManager::new_with_config(Rc::new(AudioEngine::silent()), &config)
+}
+
+/// A scenario line for a vehicle from `from` making `turn`, `ms` into the run.
+fn line(ms: u64, from: Direction, turn: TurnDirection) -> String {
+    let target = Direction::target_for(from, turn);
+    format!("{},{},{}\n", ms, from.compass_name(), target.compass_name())
+}
+
+/// Steps `manager` once, then describes the first two vehicles found overlapping, with
+/// the tick and both paths, or `None` when all are apart.
+fn step_and_find_overlap(manager: &mut VehicleManager) -> Option<String> {
+    manager.update_vehicles();
+    let vehicles = manager.get_vehicles();
+    for (index, a) in vehicles.iter().enumerate() {
+        for b in &vehicles[index + 1..] {
+            if a.rect.has_intersection(b.rect) {
+                let path = |vehicle: &Vehicle| {
+                    vehicle.path.iter().map(|tp| (tp.time, tp.position.x, tp.position.y)).collect::<Vec<_>>()
+                };
+                return Some(format!(
+                    "tick {}: vehicles {} at {:?} and {} at {:?} overlap\n{} path: {:?}\n{} path: {:?}",
+                    manager.get_tick(),
+                    a.id,
+                    a.rect,
+                    b.id,
+                    b.rect,
+                    a.id,
+                    path(a),
+                    b.id,
+                    path(b)
+                ));
+            }
+        }
+    }
+    None
+}
+
+/// Plays `scenario` until it has spawned and the road is clear, failing on the first
+/// overlap. Returns how many vehicles passed through.
+fn play_without_overlap(scenario: &str) -> u32 {
+    let mut manager = manager(0);
+    manager.set_trace(TraceSource::parse_scenario(scenario).expect("the scenario is valid"));
+    while !(manager.is_trace_finished() && manager.get_vehicles().is_empty()) {
+        assert!(manager.get_tick() < SCENARIO_TICKS, "the road did not clear");
+        if let Some(overlap) = step_and_find_overlap(&mut manager) {
+            panic!("{}", overlap);
+        }
+    }
+    manager.get_statistics().total_vehicles_passed
+}
+
+#[test]
+fn opposing_left_turners_never_overlap() {
+    let scenario: String = (0..4)
+        .flat_map(|pair| [Direction::Up, Direction::Down].map(|from| line(pair * 500, from, TurnDirection::Left)))
+        .collect();
+    assert_eq!(play_without_overlap(&scenario), 8);
+}
+
+#[test]
+fn a_straight_through_convoy_never_overlaps() {
+    // All asked for at once, so they enter one behind the other as fast as the arm allows
+    let scenario: String = (0..6).map(|_| line(0, Direction::Left, TurnDirection::Straight)).collect();
+    assert_eq!(play_without_overlap(&scenario), 6);
+}
+
+#[test]
+fn traffic_from_all_four_arms_at_once_never_overlaps() {
+    let turns = [TurnDirection::Left, TurnDirection::Straight, TurnDirection::Right];
+    let scenario: String = (0..3)
+        .flat_map(|wave| {
+            Direction::ALL
+                .into_iter()
+                .enumerate()
+                .map(move |(arm, from)| line(wave as u64 * 800, from, turns[(arm + wave) % turns.len()]))
+        })
+        .collect();
+    assert_eq!(play_without_overlap(&scenario), 12);
+}
+
+#[test]
+fn random_traffic_never_overlaps_for_any_of_a_hundred_seeds() {
+    for seed in 0..100 {
+        // The seed picks both the vehicles and when and where they are asked for
+        let mut manager = manager(seed);
+        let mut rng = StdRng::seed_from_u64(seed);
+        for _ in 0..500 {
+            if rng.gen_bool(0.1) {
+                let from = Direction::ALL[rng.gen_range(0..Direction::ALL.len())];
+                let _ = manager.try_spawn_on_tick(from, None);
+            }
+            if let Some(overlap) = step_and_find_overlap(&mut manager) {
+                panic!("seed {}, {}", seed, overlap);
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use crate::core::{RelevanceStrategy, RoadConditions, Vehicle, VehicleTraits};
+use crate::direction::Direction;
+use crate::geometry::layout::RoadLayout;
+use crate::intersection::Controller;
+use crate::weather::Weather;
+use std::collections::HashSet;
+use std::sync::mpsc::{self, Receiver, SendError, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+/// A vehicle to plan, with the random choices already made by the manager.
+pub struct PlanRequest {
+    pub initial_position: Direction,
+    pub target_direction: Direction,
+    pub lane: i32,
+    pub traits: VehicleTraits,
+    pub vehicle_id: usize,
+}
+
+impl PlanRequest {
+    pub fn plan(&self, conditions: &RoadConditions, all_vehicles: &[Vehicle]) -> Vehicle {
+        Vehicle::new(
+            self.initial_position,
+            self.target_direction,
+            self.lane,
+            self.traits,
+            conditions,
+            all_vehicles,
+            self.vehicle_id,
+        )
+    }
+}
+
+/// The road as it was on the tick a batch was requested: the vehicles on it with their
+/// whole remaining paths, and the conditions to plan in. Paths never change once
+/// planned and nobody joins the road while a batch is out, so the only difference by
+/// the time the batch comes back is vehicles that have left, which can only free up
+/// room. Paths keep the ticks of the snapshot, and a vehicle that joins later than
+/// planned starts from where its path has it on the tick it joins.
+pub struct PlanningSnapshot {
+    pub vehicles: Vec<Vehicle>,
+    pub weather: Weather,
+    pub controller: Arc<dyn Controller>,
+    pub relevance: Arc<dyn RelevanceStrategy>,
+    pub tick: u64,
+    pub closed_lanes: HashSet<(Direction, i32)>,
+    pub max_in_intersection: Option<usize>,
+}
+
+impl PlanningSnapshot {
+    /// Plans the requests in order, each around the snapshot's vehicles and the
+    /// requests before it, as the manager does when planning on its own thread.
+    fn plan(mut self, requests: &[PlanRequest]) -> Vec<Vehicle> {
+        let planned_from = self.vehicles.len();
+        for request in requests {
+            let conditions = RoadConditions {
+                weather: self.weather,
+                controller: self.controller.as_ref(),
+                relevance: self.relevance.as_ref(),
+                tick: self.tick,
+                closed_lanes: &self.closed_lanes,
+                max_in_intersection: self.max_in_intersection,
+            };
+            let vehicle = request.plan(&conditions, &self.vehicles);
+            self.vehicles.push(vehicle);
+        }
+        self.vehicles.split_off(planned_from)
+    }
+}
+
+/// Plans paths on a thread of its own, so that spawning into dense traffic does not
+/// stall the frame. The thread plans on the road layout of the thread that spawned it,
+/// and stops once the worker is dropped.
+pub struct PlanningWorker {
+    batches: Sender<(PlanningSnapshot, Vec<PlanRequest>)>,
+    planned: Receiver<Vec<Vehicle>>,
+}
+
+impl PlanningWorker {
+    pub fn spawn() -> Self {
+        let (batches, batch_receiver) = mpsc::channel::<(PlanningSnapshot, Vec<PlanRequest>)>();
+        let (planned_sender, planned) = mpsc::channel();
+        let layout = RoadLayout::current();
+        thread::spawn(move || {
+            layout.apply();
+            for (snapshot, requests) in batch_receiver {
+                if planned_sender.send(snapshot.plan(&requests)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { batches, planned }
+    }
+
+    /// A worker whose planning thread has already stopped.
+    #[cfg(test)]
+    pub fn stopped() -> Self {
+        let (batches, _) = mpsc::channel();
+        let (_, planned) = mpsc::channel();
+        Self { batches, planned }
+    }
+
+    /// Sends a batch to be planned, or hands its requests back if the planning thread
+    /// has stopped.
+    pub fn send(&self, snapshot: PlanningSnapshot, requests: Vec<PlanRequest>) -> Result<(), Vec<PlanRequest>> {
+        self.batches
+            .send((snapshot, requests))
+            .map_err(|SendError((_, requests))| requests)
+    }
+
+    /// The vehicles of the batch sent last, once they are planned. Fails if the
+    /// planning thread has stopped, say because planning panicked, taking the batch
+    /// with it.
+    pub fn try_receive(&self) -> Result<Option<Vec<Vehicle>>, String> {
+        match self.planned.try_recv() {
+            Ok(vehicles) => Ok(Some(vehicles)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err("the planning thread stopped".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::VEHICLE_SIZE;
+    use crate::core::RouteRelevance;
+    use crate::direction::TurnDirection;
+    use crate::geometry::spawn::route_lanes;
+    use crate::intersection::SmartController;
+    use sdl2::pixels::Color;
+    use sdl2::rect::Rect;
+
+    fn request(from: Direction, turn: TurnDirection, vehicle_id: usize) -> PlanRequest {
+        PlanRequest {
+            initial_position: from,
+            target_direction: Direction::target_for(from, turn),
+            lane: *route_lanes(turn).start(),
+            traits: VehicleTraits {
+                size: VEHICLE_SIZE,
+                color: Color::RGB(0, 0, 0),
+                aggression: 0.5,
+                velocity_type: 2,
+            },
+            vehicle_id,
+        }
+    }
+
+    fn snapshot(vehicles: Vec<Vehicle>, tick: u64) -> PlanningSnapshot {
+        PlanningSnapshot {
+            vehicles,
+            weather: Weather::Clear,
+            controller: Arc::new(SmartController),
+            relevance: Arc::new(RouteRelevance),
+            tick,
+            closed_lanes: HashSet::new(),
+            max_in_intersection: None,
+        }
+    }
+
+    // A vehicle from every arm for each turn, numbered from `first_id`
+    fn batch(first_id: usize) -> Vec<PlanRequest> {
+        let turns = [TurnDirection::Left, TurnDirection::Straight, TurnDirection::Right];
+        turns
+            .into_iter()
+            .flat_map(|turn| Direction::ALL.map(|from| (from, turn)))
+            .enumerate()
+            .map(|(index, (from, turn))| request(from, turn, first_id + index))
+            .collect()
+    }
+
+    fn rect_at(vehicle: &Vehicle, tick: u64) -> Option<Rect> {
+        let point = vehicle.path.iter().find(|point| point.time == tick)?;
+        Some(Rect::new(
+            point.position.x,
+            point.position.y,
+            vehicle.rect.width(),
+            vehicle.rect.height(),
+        ))
+    }
+
+    #[test]
+    fn planned_paths_never_overlap_the_snapshot_they_were_planned_against() {
+        // A road already busy with vehicles some way along their paths
+        let mut on_road = snapshot(Vec::new(), 0).plan(&batch(0));
+        for _ in 0..40 {
+            on_road.iter_mut().for_each(Vehicle::update_position);
+        }
+        let planned = snapshot(on_road.clone(), 40).plan(&batch(100));
+
+        assert_eq!(planned.len(), 12);
+        for (index, vehicle) in planned.iter().enumerate() {
+            assert!(vehicle.path.first().is_some_and(|point| point.time >= 40));
+            let others = on_road.iter().chain(&planned[..index]);
+            for other in others {
+                for point in &vehicle.path {
+                    let overlap = rect_at(vehicle, point.time)
+                        .zip(rect_at(other, point.time))
+                        .is_some_and(|(a, b)| a.has_intersection(b));
+                    assert!(!overlap, "{} and {} overlap on tick {}", vehicle.id, other.id, point.time);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_stopped_planning_thread_is_reported_rather_than_panicking() {
+        let worker = PlanningWorker::stopped();
+        assert!(worker.try_receive().is_err());
+        let unsent = worker.send(snapshot(Vec::new(), 0), batch(0)).expect_err("nobody plans it");
+        assert_eq!(unsent.len(), 12);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::constants::PROFILE_WINDOW;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Parts of a frame that are timed separately. Update excludes the spawning, path
+/// calculation and close-call detection done inside it, so the phases add up to the
+/// frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Phase {
+    Events,
+    Spawn,
+    Update,
+    PathCalculation,
+    CloseCalls,
+    Render,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 6] = [
+        Phase::Events,
+        Phase::Spawn,
+        Phase::Update,
+        Phase::PathCalculation,
+        Phase::CloseCalls,
+        Phase::Render,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Events => "Events",
+            Phase::Spawn => "Spawn",
+            Phase::Update => "Update",
+            Phase::PathCalculation => "Planning",
+            Phase::CloseCalls => "Close calls",
+            Phase::Render => "Render",
+        }
+    }
+}
+
+/// Time spent in each phase, indexed like `Phase::ALL`.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PhaseTimes([Duration; Phase::ALL.len()]);
+
+impl PhaseTimes {
+    pub fn add(&mut self, phase: Phase, time: Duration) {
+        self.0[phase as usize] += time;
+    }
+
+    pub fn get(&self, phase: Phase) -> Duration {
+        self.0[phase as usize]
+    }
+}
+
+/// Average and worst time of one phase per frame.
+#[derive(Debug, Copy, Clone)]
+pub struct PhaseTiming {
+    pub phase: Phase,
+    pub average: Duration,
+    pub worst: Duration,
+}
+
+/// Times the phases of every frame, over the last `PROFILE_WINDOW` frames for the
+/// debug view and over the whole run for the statistics.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    current: PhaseTimes,
+    window: VecDeque<PhaseTimes>,
+    total: PhaseTimes,
+    worst: PhaseTimes,
+    frames: u32,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds time spent in `phase` to the frame in progress.
+    pub fn record(&mut self, phase: Phase, time: Duration) {
+        self.current.add(phase, time);
+    }
+
+    /// Adds phase times measured elsewhere, such as inside the vehicle manager.
+    pub fn record_all(&mut self, times: PhaseTimes) {
+        for phase in Phase::ALL {
+            self.current.add(phase, times.get(phase));
+        }
+    }
+
+    /// Closes the frame in progress, counting it towards the averages.
+    pub fn end_frame(&mut self) {
+        let frame = std::mem::take(&mut self.current);
+        for phase in Phase::ALL {
+            self.total.add(phase, frame.get(phase));
+            let worst = &mut self.worst.0[phase as usize];
+            *worst = (*worst).max(frame.get(phase));
+        }
+        self.frames += 1;
+        if self.window.len() == PROFILE_WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back(frame);
+        #[cfg(feature = "profiling")]
+        if (self.frames as usize).is_multiple_of(PROFILE_WINDOW) {
+            self.print_recent();
+        }
+    }
+
+    /// Prints the microseconds per frame each phase took over the last window.
+    #[cfg(feature = "profiling")]
+    fn print_recent(&self) {
+        let phases: Vec<String> = self
+            .recent()
+            .iter()
+            .map(|timing| {
+                format!(
+                    "{} {} / {}",
+                    timing.phase.name(),
+                    timing.average.as_micros(),
+                    timing.worst.as_micros()
+                )
+            })
+            .collect();
+        println!(
+            "Frames {}-{}, us avg / worst: {}",
+            self.frames as usize + 1 - self.window.len(),
+            self.frames,
+            phases.join(", ")
+        );
+    }
+
+    /// Drops the frame in progress without counting it.
+    pub fn skip_frame(&mut self) {
+        self.current = PhaseTimes::default();
+    }
+
+    /// Averages and worsts over the last `PROFILE_WINDOW` frames.
+    pub fn recent(&self) -> Vec<PhaseTiming> {
+        let frames = self.window.len().max(1) as u32;
+        Phase::ALL
+            .into_iter()
+            .map(|phase| PhaseTiming {
+                phase,
+                average: self.window.iter().map(|frame| frame.get(phase)).sum::<Duration>() / frames,
+                worst: self.window.iter().map(|frame| frame.get(phase)).max().unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// Averages and worsts over every frame since the profiler was made.
+    pub fn run(&self) -> Vec<PhaseTiming> {
+        Phase::ALL
+            .into_iter()
+            .map(|phase| PhaseTiming {
+                phase,
+                average: self.total.get(phase) / self.frames.max(1),
+                worst: self.worst.get(phase),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn timing(timings: &[PhaseTiming], phase: Phase) -> (Duration, Duration) {
+        let timing = timings.iter().find(|timing| timing.phase == phase).unwrap();
+        (timing.average, timing.worst)
+    }
+
+    #[test]
+    fn the_recent_timings_cover_only_the_last_window() {
+        let mut profiler = Profiler::new();
+        // A slow frame, then a full window of quick ones pushes it out
+        profiler.record(Phase::Render, ms(50));
+        profiler.end_frame();
+        for _ in 0..PROFILE_WINDOW {
+            profiler.record(Phase::Render, ms(2));
+            profiler.end_frame();
+        }
+        assert_eq!(timing(&profiler.recent(), Phase::Render), (ms(2), ms(2)));
+        // The run keeps it
+        assert_eq!(timing(&profiler.run(), Phase::Render).1, ms(50));
+    }
+
+    #[test]
+    fn the_phases_of_a_frame_add_up() {
+        let mut profiler = Profiler::new();
+        let mut measured = PhaseTimes::default();
+        measured.add(Phase::Spawn, ms(1));
+        measured.add(Phase::PathCalculation, ms(3));
+        measured.add(Phase::PathCalculation, ms(4));
+        for frame in 0..4 {
+            profiler.record(Phase::Events, ms(1));
+            profiler.record(Phase::Events, ms(frame));
+            profiler.record_all(measured);
+            profiler.end_frame();
+        }
+
+        let run = profiler.run();
+        // 1 + 0, 1 + 1, 1 + 2 and 1 + 3 ms
+        assert_eq!(timing(&run, Phase::Events), (Duration::from_micros(2500), ms(4)));
+        assert_eq!(timing(&run, Phase::PathCalculation), (ms(7), ms(7)));
+        assert_eq!(timing(&run, Phase::Spawn), (ms(1), ms(1)));
+        assert_eq!(timing(&run, Phase::Render), (ms(0), ms(0)));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::direction::{Direction, TurnDirection};
+use rand::Rng;
+
+/// Relative weights of the routes picked for randomly spawned vehicles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteDistribution {
+    weights: Vec<(TurnDirection, f64)>,
+}
+
+impl RouteDistribution {
+    /// Weights need not sum to one, but must be finite, non-negative and not all zero.
+    pub fn new(weights: &[(TurnDirection, f64)]) -> Result<Self, String> {
+        if let Some((route, weight)) = weights
+            .iter()
+            .find(|(_, weight)| !weight.is_finite() || *weight < 0.0)
+        {
+            return Err(format!("Invalid weight {} for route {:?}", weight, route));
+        }
+        if weights.iter().map(|(_, weight)| weight).sum::<f64>() <= 0.0 {
+            return Err("Route weights must not all be zero".to_string());
+        }
+
+        Ok(Self {
+            weights: weights.to_vec(),
+        })
+    }
+
+    /// Picks a target direction for a vehicle entering from `initial_position`, among
+    /// those `usable` accepts. Routes towards a closed arm are skipped too, and the share
+    /// of skipped routes goes to the others. `None` when no weighted route is left.
+    pub fn sample(
+        &self,
+        rng: &mut impl Rng,
+        initial_position: Direction,
+        usable: impl Fn(Direction) -> bool,
+    ) -> Option<Direction> {
+        let open_routes = self.open_routes(initial_position, usable);
+        let total: f64 = open_routes.iter().map(|(_, weight)| weight).sum();
+        if total <= 0.0 {
+            return None;
+        }
+
+        let mut pick = rng.gen_range(0.0..total);
+        for &(target, weight) in &open_routes {
+            if pick < weight {
+                return Some(target);
+            }
+            pick -= weight;
+        }
+        open_routes.last().map(|&(target, _)| target)
+    }
+
+    /// Whether `sample` has a route to pick from.
+    pub fn has_route(&self, initial_position: Direction, usable: impl Fn(Direction) -> bool) -> bool {
+        !self.open_routes(initial_position, usable).is_empty()
+    }
+
+    /// Targets from `initial_position` with a weight, towards an open arm, that `usable`
+    /// accepts.
+    fn open_routes(&self, initial_position: Direction, usable: impl Fn(Direction) -> bool) -> Vec<(Direction, f64)> {
+        self.weights
+            .iter()
+            .map(|&(route, weight)| (Direction::target_for(initial_position, route), weight))
+            .filter(|&(target, weight)| target.is_open() && weight > 0.0 && usable(target))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn sampled_routes_follow_the_weights() {
+        const SPAWNS: usize = 10_000;
+        let weights = [
+            (TurnDirection::Straight, 0.4),
+            (TurnDirection::Left, 0.3),
+            (TurnDirection::Right, 0.3),
+            (TurnDirection::UTurn, 0.0),
+        ];
+        let routes = RouteDistribution::new(&weights).expect("the weights are valid");
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut counts = [0usize; 4];
+        for _ in 0..SPAWNS {
+            let target = routes.sample(&mut rng, Direction::Up, |_| true).expect("every route is open");
+            let turn = Direction::turn_direction(Direction::Up, target);
+            counts[weights.iter().position(|&(route, _)| route == turn).unwrap()] += 1;
+        }
+        for (&(route, weight), count) in weights.iter().zip(counts) {
+            let share = count as f64 / SPAWNS as f64;
+            assert!((share - weight).abs() < 0.02, "{:?} picked {} of the time", route, share);
+        }
+    }
+
+    #[test]
+    fn weights_that_cannot_be_sampled_are_rejected() {
+        assert!(RouteDistribution::new(&[(TurnDirection::Left, -1.0), (TurnDirection::Right, 2.0)]).is_err());
+        assert!(RouteDistribution::new(&[(TurnDirection::Left, f64::NAN)]).is_err());
+        assert!(RouteDistribution::new(&[(TurnDirection::Left, 0.0), (TurnDirection::Right, 0.0)]).is_err());
+        // Only their ratios matter
+        assert!(RouteDistribution::new(&[(TurnDirection::Left, 4.0), (TurnDirection::Right, 6.0)]).is_ok());
+    }
+}
@@ -0,0 +1,42 @@
+use serde::Serialize;
+
+/// Everything needed to reproduce a bug report about the current moment of a run.
+#[derive(Serialize)]
+pub struct SimulationSnapshot {
+    pub tick: u64,
+    pub weather: String,
+    pub controller: &'static str,
+    pub vehicles: Vec<VehicleSnapshot>,
+    pub pending_spawns: Vec<PendingSpawnSnapshot>,
+}
+
+#[derive(Serialize)]
+pub struct VehicleSnapshot {
+    pub id: usize,
+    pub rect: (i32, i32, u32, u32),
+    pub from: &'static str,
+    pub to: &'static str,
+    pub route: &'static str,
+    pub remaining_path: usize,
+    // Pixels the vehicle moves on its next tick
+    pub velocity: i32,
+    pub stuck_ticks: u32,
+    // Ticks on which the planned path enters and clears the intersection
+    pub passage: Option<(u64, u64)>,
+}
+
+#[derive(Serialize)]
+pub struct PendingSpawnSnapshot {
+    pub id: usize,
+    pub from: &'static str,
+    pub to: Option<&'static str>,
+    pub lane: Option<i32>,
+    pub due_tick: u64,
+}
+
+impl SimulationSnapshot {
+    pub fn write(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,244 @@
+use crate::config::DirectionWeights;
+use crate::constants::SIMULATION_STEP;
+use crate::direction::Direction;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+use std::time::Duration;
+
+/// How randomly generated vehicles arrive. Rates are vehicles per second over all arms.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SpawnModel {
+    /// One vehicle every spawn interval, the interval picked on the menu.
+    #[default]
+    FixedInterval,
+    /// Arrivals at random times, `rate` per second on average.
+    Poisson { rate: f64 },
+    /// Poisson arrivals whose rate swings from `base_rate` up to `peak_rate` and back
+    /// every `period` seconds, peaking halfway through.
+    RushHour {
+        base_rate: f64,
+        peak_rate: f64,
+        period: f64,
+    },
+}
+
+impl SpawnModel {
+    pub fn validate(&self) -> Result<(), String> {
+        let rates = match *self {
+            SpawnModel::FixedInterval => vec![],
+            SpawnModel::Poisson { rate } => vec![("rate", rate)],
+            SpawnModel::RushHour {
+                base_rate,
+                peak_rate,
+                period,
+            } => {
+                if !period.is_finite() || period <= 0.0 {
+                    return Err(format!("spawn_model period must be positive, got {}", period));
+                }
+                vec![("base_rate", base_rate), ("peak_rate", peak_rate)]
+            }
+        };
+        for (name, rate) in rates {
+            if !rate.is_finite() || rate < 0.0 {
+                return Err(format!("Invalid spawn_model {} {}", name, rate));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What the scheduler was started with, recorded in the exported statistics so a run
+/// can be repeated.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct SpawnSettings {
+    pub model: SpawnModel,
+    /// Only used by `FixedInterval`.
+    pub interval_ms: u64,
+    pub seed: u64,
+    pub direction_weights: DirectionWeights,
+}
+
+/// Decides, tick by tick, which arms random generation spawns from. Seeded, so the
+/// same settings give the same arrivals.
+pub struct SpawnScheduler {
+    settings: SpawnSettings,
+    rng: StdRng,
+    // Ticks the scheduler has been asked about, which is how long generation has run
+    ticks: u64,
+    // Arrivals not yet spawned, per arm, such as those that came during the cooldown
+    backlog: HashMap<Direction, u32>,
+}
+
+impl SpawnScheduler {
+    pub fn new(settings: SpawnSettings) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(settings.seed),
+            settings,
+            ticks: 0,
+            backlog: HashMap::new(),
+        }
+    }
+
+    /// Arms to spawn one vehicle from on this tick, each at most once. Arrivals beyond
+    /// one per arm wait for later ticks. Call once per simulation tick while generating.
+    pub fn due(&mut self) -> Vec<Direction> {
+        for direction in self.arrivals() {
+            *self.backlog.entry(direction).or_insert(0) += 1;
+        }
+        Direction::ALL
+            .into_iter()
+            .filter(|direction| match self.backlog.get_mut(direction) {
+                Some(waiting) if *waiting > 0 => {
+                    *waiting -= 1;
+                    true
+                }
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// Puts back an arrival that could not spawn yet, to be offered again next tick.
+    pub fn defer(&mut self, direction: Direction) {
+        *self.backlog.entry(direction).or_insert(0) += 1;
+    }
+
+    /// Vehicles arriving on this tick, possibly several from one arm.
+    fn arrivals(&mut self) -> Vec<Direction> {
+        let step = SIMULATION_STEP.as_secs_f64();
+        let tick = self.ticks;
+        self.ticks += 1;
+
+        let weights: Vec<(Direction, f64)> = self
+            .settings
+            .direction_weights
+            .to_weights()
+            .into_iter()
+            .filter(|&(direction, weight)| direction.is_open() && weight > 0.0)
+            .collect();
+        let total: f64 = weights.iter().map(|(_, weight)| weight).sum();
+        if total <= 0.0 {
+            return Vec::new();
+        }
+
+        let rate = match self.settings.model {
+            SpawnModel::FixedInterval => {
+                let interval = Duration::from_millis(self.settings.interval_ms);
+                let every = (interval.as_millis() / SIMULATION_STEP.as_millis()).max(1) as u64;
+                if tick.is_multiple_of(every) {
+                    return self.pick(&weights, total).into_iter().collect();
+                }
+                return Vec::new();
+            }
+            SpawnModel::Poisson { rate } => rate,
+            SpawnModel::RushHour {
+                base_rate,
+                peak_rate,
+                period,
+            } => base_rate + (peak_rate - base_rate) * (1.0 - (TAU * tick as f64 * step / period).cos()) / 2.0,
+        };
+
+        // Each arm is its own Poisson process with its share of the rate
+        let mut due = Vec::new();
+        for &(direction, weight) in &weights {
+            let arrivals = poisson(&mut self.rng, rate * weight / total * step);
+            due.extend(std::iter::repeat_n(direction, arrivals as usize));
+        }
+        due
+    }
+
+    fn pick(&mut self, weights: &[(Direction, f64)], total: f64) -> Option<Direction> {
+        let mut pick = self.rng.gen_range(0.0..total);
+        for &(direction, weight) in weights {
+            if pick < weight {
+                return Some(direction);
+            }
+            pick -= weight;
+        }
+        weights.last().map(|&(direction, _)| direction)
+    }
+}
+
+/// Draws from a Poisson distribution with mean `mean` by multiplying uniform draws,
+/// which is quick for the small means of a single tick.
+fn poisson(rng: &mut StdRng, mean: f64) -> u32 {
+    let limit = (-mean).exp();
+    let mut arrivals = 0;
+    let mut product: f64 = rng.gen();
+    while product > limit {
+        arrivals += 1;
+        product *= rng.gen::<f64>();
+    }
+    arrivals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::statistics::Statistics;
+
+    fn settings(model: SpawnModel, seed: u64) -> SpawnSettings {
+        SpawnSettings {
+            model,
+            interval_ms: 700,
+            seed,
+            // North-south the dominant flow
+            direction_weights: DirectionWeights {
+                north: 3.0,
+                east: 1.0,
+                south: 3.0,
+                west: 1.0,
+            },
+        }
+    }
+
+    // Arms due on each of the first `ticks` ticks
+    fn arrivals(settings: SpawnSettings, ticks: usize) -> Vec<Vec<Direction>> {
+        let mut scheduler = SpawnScheduler::new(settings);
+        (0..ticks).map(|_| scheduler.due()).collect()
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_arrivals() {
+        let rush_hour = SpawnModel::RushHour {
+            base_rate: 0.5,
+            peak_rate: 4.0,
+            period: 20.0,
+        };
+        for model in [SpawnModel::FixedInterval, SpawnModel::Poisson { rate: 2.0 }, rush_hour] {
+            let run = arrivals(settings(model, 11), 3000);
+            assert_eq!(run, arrivals(settings(model, 11), 3000), "{:?}", model);
+            assert_ne!(run, arrivals(settings(model, 12), 3000), "{:?}", model);
+        }
+    }
+
+    #[test]
+    fn poisson_arrivals_keep_to_the_rate_and_the_weights() {
+        let seconds = 600.0;
+        let ticks = (seconds / SIMULATION_STEP.as_secs_f64()) as usize;
+        let run = arrivals(settings(SpawnModel::Poisson { rate: 2.0 }, 5), ticks);
+        let count = |arms: &[Direction]| {
+            run.iter().flatten().filter(|direction| arms.contains(direction)).count() as f64
+        };
+        let total = count(&Direction::ALL);
+        assert!((total / seconds - 2.0).abs() < 0.2, "{} per second", total / seconds);
+        let north_south = count(&[Direction::Up, Direction::Down]);
+        assert!((north_south / total - 0.75).abs() < 0.05, "{} from north and south", north_south / total);
+    }
+
+    #[test]
+    fn the_settings_are_recorded_in_the_exported_statistics() {
+        let settings = settings(SpawnModel::Poisson { rate: 1.5 }, 42);
+        let mut statistics = Statistics::new();
+        statistics.set_spawn_settings(settings);
+        let summary = serde_json::to_value(statistics.get_summary()).unwrap();
+        let spawn = &summary["spawn"];
+        assert_eq!(spawn["seed"], 42);
+        assert_eq!(spawn["model"]["kind"], "poisson");
+        assert_eq!(spawn["model"]["rate"], 1.5);
+        assert_eq!(spawn["direction_weights"]["north"], 3.0);
+    }
+}
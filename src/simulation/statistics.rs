@@ -1,27 +1,71 @@
+use crate::constants::{CLOSE_CALL_MARKER_TICKS, FRAME_DURATION, SIMULATION_STEP};
 use crate::direction::*;
-use crate::geometry::position::Position;
+use crate::geometry::layout::world_size;
+use crate::geometry::position::{Position, TimedPosition};
+use crate::geometry::spawn::travel_direction_at;
+use crate::intersection::IntersectionBounds;
+use crate::weather::Weather;
+use crate::simulation::grading::{grade, Grade, GradingWeights, Incidents};
+use crate::simulation::heatmap::{Heatmap, HeatmapExport};
+use crate::simulation::spawn_scheduler::SpawnSettings;
+use serde::Serialize;
+use sdl2::rect::Rect;
 use std::collections::{HashMap, HashSet};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-const SAFE_DISTANCE: f32 = 55.0;
+pub const SAFE_DISTANCE: f32 = 55.0;
+// Vehicles slower than this, in pixels per tick, count as waiting in a queue
+const QUEUE_SPEED_THRESHOLD: f32 = 0.5;
+const TICKS_PER_SECOND: u64 = 1000 / SIMULATION_STEP.as_millis() as u64;
+// Collisions are predicted up to this many ticks ahead, and pairs predicted to collide
+// sooner than the warning time are counted
+const TTC_HORIZON_TICKS: usize = 120;
+const TTC_WARNING_TICKS: f32 = 45.0;
+// Completed crossings are counted in buckets of this many simulated seconds
+pub const THROUGHPUT_BUCKET_SECONDS: u64 = 10;
+
+/// Why a vehicle left the simulation.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ExitReason {
+    LeftWindow,
+    RemovedStuck,
+}
 
 #[derive(Debug)]
 pub struct VehicleStats {
-    entry_time: Instant,
-    exit_time: Option<Instant>,
+    direction: Direction,
+    // Ticks the crossing was timed from and to, so it takes as long in simulated time
+    // however fast the simulation runs
+    entry_tick: u64,
+    exit_tick: Option<u64>,
     max_velocity: f32,
     min_velocity: f32,
     in_intersection: bool,
+    passed: bool,
+    exit_reason: Option<ExitReason>,
+    // Times the vehicle came to a halt on screen, and the ticks it spent halted
+    stops: u32,
+    stopped_ticks: u32,
+    stopped: bool,
+    // Known once the vehicle is planned, after its spawn was counted
+    route: Option<TurnDirection>,
 }
 
 impl VehicleStats {
-    pub fn new() -> Self {
+    pub fn new(direction: Direction, tick: u64) -> Self {
         Self {
-            entry_time: Instant::now(),
-            exit_time: None,
+            direction,
+            entry_tick: tick,
+            exit_tick: None,
             max_velocity: 0.0,
             min_velocity: f32::MAX,
             in_intersection: false,
+            passed: false,
+            exit_reason: None,
+            stops: 0,
+            stopped_ticks: 0,
+            stopped: false,
+            route: None,
         }
     }
 
@@ -32,13 +76,54 @@ impl VehicleStats {
         }
     }
 
-    pub fn record_exit(&mut self) {
-        self.exit_time = Some(Instant::now());
+    pub fn record_exit(&mut self, tick: u64) {
+        self.exit_tick = Some(tick);
     }
 
+    /// Simulated seconds from the entry tick to the exit tick, once the vehicle is gone.
     pub fn get_intersection_time(&self) -> Option<f32> {
-        self.exit_time
-            .map(|exit| (exit.duration_since(self.entry_time)).as_secs_f32())
+        self.exit_tick
+            .map(|exit| (exit - self.entry_tick) as f32 * SIMULATION_STEP.as_secs_f32())
+    }
+}
+
+/// A new close call, kept for a while so it can be marked on screen.
+#[derive(Debug, Clone, Copy)]
+pub struct CloseCallEvent {
+    pub vehicles: (usize, usize),
+    // Midpoint between the two vehicles' centers
+    pub position: (i32, i32),
+    pub tick: u64,
+}
+
+/// Two vehicles found overlapping, with their positions on the tick it was seen.
+#[derive(Debug, Clone, Serialize)]
+pub struct CollisionIncident {
+    pub tick: u64,
+    pub vehicles: [(usize, i32, i32); 2],
+}
+
+/// Queue of waiting vehicles on one approach, counted from the intersection back.
+#[derive(Debug, Default)]
+pub struct QueueStats {
+    pub current: u32,
+    pub max: u32,
+    total: u64,
+    // Longest queue seen in each simulated second
+    per_second: Vec<u32>,
+    second_max: u32,
+}
+
+impl QueueStats {
+    fn record(&mut self, length: u32, tick: u64) {
+        self.current = length;
+        self.max = self.max.max(length);
+        self.total += length as u64;
+        self.second_max = self.second_max.max(length);
+        if tick.is_multiple_of(TICKS_PER_SECOND) {
+            self.per_second.push(self.second_max);
+            self.second_max = 0;
+        }
     }
 }
 
@@ -51,13 +136,54 @@ pub struct Statistics {
     pub vehicle_stats: HashMap<usize, VehicleStats>,
     pub max_intersection_time: f32,
     pub min_intersection_time: f32,
+    // Every completed crossing, kept for the percentiles
+    crossing_times: Vec<f32>,
     pub total_close_calls: u32,
+    pub total_removed_stuck: u32,
     pub max_velocity: f32,
     pub min_velocity: f32,
     pub current_vehicles_in_intersection: u32,
     pub max_vehicles_in_intersection: u32,
     vehicle_counter: usize,
     close_call_pairs: HashSet<(usize, usize)>,
+    recent_close_calls: Vec<CloseCallEvent>,
+    // Close call distance in clear weather, and scaled for the current weather
+    base_safe_distance: f32,
+    safe_distance: f32,
+    weather: Weather,
+    // Time spent in each weather and the crossings completed in it
+    weather_periods: HashMap<Weather, WeatherPeriod>,
+    // Soonest predicted collision on the latest tick and over the whole run, in ticks
+    min_time_to_collision: Option<f32>,
+    lowest_time_to_collision: Option<f32>,
+    ttc_warning_pairs: HashSet<(usize, usize)>,
+    pub total_ttc_warnings: u32,
+    // Vehicles seen moving against the traffic of their lane, which means a pathing bug
+    wrong_way_vehicles: HashSet<usize>,
+    pub total_anomalies: u32,
+    // Overlapping vehicle rects, which the path planning should never allow
+    collision_pairs: HashSet<(usize, usize)>,
+    pub collisions: Vec<CollisionIncident>,
+    pub queues: HashMap<Direction, QueueStats>,
+    // Requested spawns waiting for room under the vehicle cap, now and at the most
+    pub spawn_queue: usize,
+    pub max_spawn_queue: usize,
+    // Collision resolver work planning paths: in all, on the current tick, and the most
+    // for one vehicle and for one tick
+    resolver_steps: u64,
+    tick_resolver_steps: u64,
+    max_vehicle_resolver_steps: u64,
+    max_tick_resolver_steps: u64,
+    // Rendered frames, and those that took longer than the frame budget
+    pub frames: u32,
+    pub dropped_frames: u32,
+    controller: &'static str,
+    grading: GradingWeights,
+    // How random generation spawns, when the run has it
+    spawn_settings: Option<SpawnSettings>,
+    ticks: u64,
+    // Vehicles that crossed and left the window, per throughput bucket
+    throughput: Vec<u32>,
     has_valid_velocities: bool,
 }
 
@@ -72,17 +198,90 @@ impl Statistics {
             vehicle_stats: HashMap::new(),
             max_intersection_time: 0.0,
             min_intersection_time: f32::MAX,
+            crossing_times: Vec::new(),
             total_close_calls: 0,
+            total_removed_stuck: 0,
             max_velocity: 0.0,
             min_velocity: f32::MAX,
             current_vehicles_in_intersection: 0,
             max_vehicles_in_intersection: 0,
             vehicle_counter: 0,
             close_call_pairs: HashSet::new(),
+            recent_close_calls: Vec::new(),
+            base_safe_distance: SAFE_DISTANCE,
+            safe_distance: SAFE_DISTANCE,
+            weather: Weather::Clear,
+            weather_periods: HashMap::new(),
+            min_time_to_collision: None,
+            lowest_time_to_collision: None,
+            ttc_warning_pairs: HashSet::new(),
+            total_ttc_warnings: 0,
+            wrong_way_vehicles: HashSet::new(),
+            total_anomalies: 0,
+            collision_pairs: HashSet::new(),
+            collisions: Vec::new(),
+            queues: HashMap::new(),
+            spawn_queue: 0,
+            max_spawn_queue: 0,
+            resolver_steps: 0,
+            tick_resolver_steps: 0,
+            max_vehicle_resolver_steps: 0,
+            max_tick_resolver_steps: 0,
+            frames: 0,
+            dropped_frames: 0,
+            controller: "smart",
+            grading: GradingWeights::default(),
+            spawn_settings: None,
+            ticks: 0,
+            throughput: Vec::new(),
             has_valid_velocities: false,
         }
     }
 
+    /// Starts the counters and the clock over, keeping the settings. Vehicle ids keep
+    /// counting, so vehicles still on the road never share an id with new ones; they are
+    /// no longer tracked, and only vehicles spawned from now on are counted.
+    pub fn reset(&mut self) {
+        *self = Statistics {
+            vehicle_counter: self.vehicle_counter,
+            base_safe_distance: self.base_safe_distance,
+            safe_distance: self.safe_distance,
+            weather: self.weather,
+            controller: self.controller,
+            grading: self.grading,
+            spawn_settings: self.spawn_settings,
+            ..Statistics::new()
+        };
+    }
+
+    pub fn set_controller(&mut self, name: &'static str) {
+        self.controller = name;
+    }
+
+    pub fn set_grading(&mut self, weights: GradingWeights) {
+        self.grading = weights;
+    }
+
+    pub fn set_spawn_settings(&mut self, settings: SpawnSettings) {
+        self.spawn_settings = Some(settings);
+    }
+
+    /// Sets the close call distance in clear weather. Takes effect with the next
+    /// `set_weather`.
+    pub fn set_safe_distance(&mut self, distance: f32) {
+        self.base_safe_distance = distance;
+    }
+
+    /// Close call distance for the current weather.
+    pub fn get_safe_distance(&self) -> f32 {
+        self.safe_distance
+    }
+
+    pub fn set_weather(&mut self, weather: Weather) {
+        self.weather = weather;
+        self.safe_distance = self.base_safe_distance * weather.safe_distance_scale();
+    }
+
     pub fn add_vehicle(&mut self, direction: Direction) -> usize {
         *self.vehicles_spawned.entry(direction).or_insert(0) += 1;
         self.total_vehicles += 1;
@@ -90,26 +289,49 @@ impl Statistics {
         let vehicle_id = self.vehicle_counter;
         self.vehicle_counter += 1;
 
-        self.vehicle_stats.insert(vehicle_id, VehicleStats::new());
+        self.vehicle_stats.insert(vehicle_id, VehicleStats::new(direction, self.ticks));
         vehicle_id
     }
 
-    pub fn update_vehicle_stats(&mut self, vehicle_id: usize, position: Position, velocity: f32) {
+    /// Updates a vehicle's counters after it moved. Returns whether it has just passed
+    /// through the intersection.
+    pub fn update_vehicle_stats(&mut self, vehicle_id: usize, rect: Rect, velocity: f32) -> bool {
+        let mut just_passed = false;
         if let Some(stats) = self.vehicle_stats.get_mut(&vehicle_id) {
+            let position = Position {
+                x: rect.x(),
+                y: rect.y(),
+            };
             let was_in_intersection = stats.in_intersection;
-            let now_in_intersection = position.is_in_intersection();
+            let now_in_intersection = position.is_in_intersection(rect.width());
 
             if !was_in_intersection && now_in_intersection {
                 self.current_vehicles_in_intersection += 1;
-                self.max_vehicles_in_intersection = self
-                    .max_vehicles_in_intersection
-                    .max(self.current_vehicles_in_intersection);
                 stats.in_intersection = true;
             } else if was_in_intersection && !now_in_intersection {
                 if self.current_vehicles_in_intersection > 0 {
                     self.current_vehicles_in_intersection -= 1;
                 }
                 stats.in_intersection = false;
+
+                // Leaving the core counts as passing; a vehicle only passes once
+                if !stats.passed {
+                    stats.passed = true;
+                    self.total_vehicles_passed += 1;
+                    just_passed = true;
+                }
+            }
+
+            let (width, height) = world_size();
+            let on_screen = rect.has_intersection(Rect::new(0, 0, width, height));
+            if velocity == 0.0 && on_screen {
+                if !stats.stopped {
+                    stats.stopped = true;
+                    stats.stops += 1;
+                }
+                stats.stopped_ticks += 1;
+            } else {
+                stats.stopped = false;
             }
 
             if velocity > 0.0 {
@@ -119,15 +341,48 @@ impl Statistics {
                 self.has_valid_velocities = true;
             }
         }
+        just_passed
     }
 
-    pub fn record_vehicle_exit(&mut self, vehicle_id: usize) {
+    /// Records the route of a vehicle that has just been planned onto the road. Its
+    /// crossing is timed from here, so time spent in the spawn queue does not count.
+    pub fn set_route(&mut self, vehicle_id: usize, route: TurnDirection) {
         if let Some(stats) = self.vehicle_stats.get_mut(&vehicle_id) {
-            stats.record_exit();
+            stats.route = Some(route);
+            stats.entry_tick = self.ticks;
+        }
+    }
 
-            self.total_vehicles_passed += 1;
+    pub fn update_spawn_queue(&mut self, length: usize) {
+        self.spawn_queue = length;
+        self.max_spawn_queue = self.max_spawn_queue.max(length);
+    }
+
+    pub fn record_vehicle_exit(&mut self, vehicle_id: usize, reason: ExitReason) {
+        let bucket = self.throughput_bucket();
+        if let Some(stats) = self.vehicle_stats.get_mut(&vehicle_id) {
+            stats.record_exit(self.ticks);
+            stats.exit_reason = Some(reason);
+            if reason == ExitReason::RemovedStuck {
+                self.total_removed_stuck += 1;
+            }
+            if reason == ExitReason::LeftWindow && stats.passed {
+                self.weather_periods.entry(self.weather).or_default().completed += 1;
+                if self.throughput.len() <= bucket {
+                    self.throughput.resize(bucket + 1, 0);
+                }
+                self.throughput[bucket] += 1;
+            }
+
+            // A vehicle removed without crossing has no crossing time
+            let crossing_time = if stats.passed {
+                stats.get_intersection_time()
+            } else {
+                None
+            };
 
-            if let Some(time) = stats.get_intersection_time() {
+            if let Some(time) = crossing_time {
+                self.crossing_times.push(time);
                 self.max_intersection_time = self.max_intersection_time.max(time);
                 if self.min_intersection_time == f32::MAX {
                     self.min_intersection_time = time;
@@ -136,59 +391,355 @@ impl Statistics {
                 }
             }
 
-            if stats.in_intersection {
-                if self.current_vehicles_in_intersection > 0 {
-                    self.current_vehicles_in_intersection -= 1;
-                }
+            if stats.in_intersection && self.current_vehicles_in_intersection > 0 {
+                self.current_vehicles_in_intersection -= 1;
             }
         }
     }
 
-    pub fn check_close_calls(&mut self, vehicle_positions: &[(usize, (i32, i32))]) {
-        for (i, &(id1, pos1)) in vehicle_positions.iter().enumerate() {
+    /// Records close calls between vehicles, calling `on_close_call` once per new pair.
+    pub fn check_close_calls(
+        &mut self,
+        vehicle_rects: &[(usize, Rect)],
+        mut on_close_call: impl FnMut(usize, usize),
+    ) {
+        let tick = self.ticks;
+        self.recent_close_calls
+            .retain(|event| tick - event.tick < CLOSE_CALL_MARKER_TICKS);
+
+        for (i, &(id1, rect1)) in vehicle_rects.iter().enumerate() {
             let pos = Position {
-                x: pos1.0,
-                y: pos1.1,
+                x: rect1.x(),
+                y: rect1.y(),
             };
 
-            for &(id2, pos2) in vehicle_positions.iter().skip(i + 1) {
+            for &(id2, rect2) in vehicle_rects.iter().skip(i + 1) {
                 let other_pos = Position {
-                    x: pos2.0,
-                    y: pos2.1,
+                    x: rect2.x(),
+                    y: rect2.y(),
                 };
 
-                if !pos.is_in_intersection() && !other_pos.is_in_intersection() {
+                if !pos.is_in_intersection(rect1.width())
+                    && !other_pos.is_in_intersection(rect2.width())
+                {
                     continue;
                 }
 
-                let dx = (pos2.0 - pos1.0) as f32;
-                let dy = (pos2.1 - pos1.1) as f32;
+                // Measured between centers so vehicles of different sizes compare fairly
+                let dx = (rect2.center().x() - rect1.center().x()) as f32;
+                let dy = (rect2.center().y() - rect1.center().y()) as f32;
                 let distance = (dx * dx + dy * dy).sqrt();
 
-                if distance < SAFE_DISTANCE {
+                if distance < self.safe_distance {
                     let pair = if id1 < id2 { (id1, id2) } else { (id2, id1) };
 
                     if self.close_call_pairs.insert(pair) {
                         self.total_close_calls += 1;
+                        self.recent_close_calls.push(CloseCallEvent {
+                            vehicles: pair,
+                            position: (
+                                (rect1.center().x() + rect2.center().x()) / 2,
+                                (rect1.center().y() + rect2.center().y()) / 2,
+                            ),
+                            tick,
+                        });
+                        on_close_call(pair.0, pair.1);
                     }
                 }
             }
         }
     }
 
+    /// Predicts when each pair would collide by walking the rest of their planned paths
+    /// tick by tick, for pairs with a vehicle in the intersection like close calls. Each
+    /// pair predicted to collide within `TTC_WARNING_TICKS` is counted as a warning once.
+    pub fn check_time_to_collision(&mut self, vehicles: &[(usize, Rect, &[TimedPosition])]) {
+        self.min_time_to_collision = None;
+
+        for (i, &(id1, rect1, path1)) in vehicles.iter().enumerate() {
+            for &(id2, rect2, path2) in vehicles.iter().skip(i + 1) {
+                let in_intersection =
+                    |rect: Rect| Position { x: rect.x(), y: rect.y() }.is_in_intersection(rect.width());
+                if !in_intersection(rect1) && !in_intersection(rect2) {
+                    continue;
+                }
+                let Some(ttc) = time_to_collision(rect1, path1, rect2, path2) else {
+                    continue;
+                };
+
+                self.min_time_to_collision =
+                    Some(self.min_time_to_collision.map_or(ttc, |min| min.min(ttc)));
+                self.lowest_time_to_collision =
+                    Some(self.lowest_time_to_collision.map_or(ttc, |lowest| lowest.min(ttc)));
+
+                let pair = if id1 < id2 { (id1, id2) } else { (id2, id1) };
+                if ttc < TTC_WARNING_TICKS && self.ttc_warning_pairs.insert(pair) {
+                    self.total_ttc_warnings += 1;
+                }
+            }
+        }
+    }
+
+    /// Counts a vehicle as an anomaly the first time its last move, `motion`, goes against
+    /// the traffic of the lane its centre is in. Moving across lanes is allowed, and moves
+    /// inside the intersection are not checked.
+    pub fn check_wrong_way(&mut self, id: usize, rect: Rect, motion: (i32, i32)) {
+        let centre = Position {
+            x: rect.center().x(),
+            y: rect.center().y(),
+        };
+        let Some(direction) = travel_direction_at(centre) else {
+            return;
+        };
+        let along = match direction {
+            Direction::Up => -motion.1,
+            Direction::Down => motion.1,
+            Direction::Left => -motion.0,
+            Direction::Right => motion.0,
+        };
+        if along < 0 && self.wrong_way_vehicles.insert(id) {
+            self.total_anomalies += 1;
+            eprintln!(
+                "Warning: vehicle {} moved {:?} in a lane heading {:?} at {:?}",
+                id, motion, direction, centre
+            );
+        }
+    }
+
+    /// Soonest predicted collision on the latest tick, in seconds.
+    pub fn min_time_to_collision(&self) -> Option<f32> {
+        self.min_time_to_collision
+            .map(|ticks| ticks * SIMULATION_STEP.as_secs_f32())
+    }
+
+    /// Close calls from the last `CLOSE_CALL_MARKER_TICKS` ticks, oldest first.
+    pub fn recent_close_calls(&self) -> &[CloseCallEvent] {
+        &self.recent_close_calls
+    }
+
+    /// Simulation ticks counted so far.
+    pub fn get_tick(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Logs every pair of vehicles whose rects overlap, once per pair.
+    pub fn check_collisions(&mut self, vehicle_rects: &[(usize, Rect)]) {
+        for (i, &(id1, rect1)) in vehicle_rects.iter().enumerate() {
+            for &(id2, rect2) in vehicle_rects.iter().skip(i + 1) {
+                if !rect1.has_intersection(rect2) {
+                    continue;
+                }
+
+                let pair = if id1 < id2 { (id1, id2) } else { (id2, id1) };
+                if self.collision_pairs.insert(pair) {
+                    self.collisions.push(CollisionIncident {
+                        tick: self.ticks,
+                        vehicles: [(id1, rect1.x(), rect1.y()), (id2, rect2.x(), rect2.y())],
+                    });
+                }
+            }
+        }
+    }
+
+    /// Adds the resolver work it took to plan one vehicle's path. Paths planned between
+    /// two calls to `update_queues` count towards the same tick.
+    pub fn record_resolver_steps(&mut self, steps: u64) {
+        self.resolver_steps += steps;
+        self.tick_resolver_steps += steps;
+        self.max_vehicle_resolver_steps = self.max_vehicle_resolver_steps.max(steps);
+        self.max_tick_resolver_steps = self.max_tick_resolver_steps.max(self.tick_resolver_steps);
+    }
+
+    /// Counts a rendered frame that took `time` to produce, dropped when over
+    /// `FRAME_DURATION`. Headless runs have no frames.
+    pub fn record_frame(&mut self, time: Duration) {
+        self.frames += 1;
+        if time > FRAME_DURATION {
+            self.dropped_frames += 1;
+        }
+    }
+
+    /// Counts, for each approach, the waiting vehicles backed up from the intersection:
+    /// vehicles that have not entered yet, nearest first, up to the first one moving.
+    /// Also takes the tick's peak intersection occupancy, once every vehicle has moved,
+    /// so one entering before another's exit is counted does not inflate it.
+    pub fn update_queues(&mut self, vehicles: &[(usize, Rect, f32)]) {
+        self.ticks += 1;
+        self.max_vehicles_in_intersection = self
+            .max_vehicles_in_intersection
+            .max(self.current_vehicles_in_intersection);
+        self.tick_resolver_steps = 0;
+        self.weather_periods.entry(self.weather).or_default().ticks += 1;
+        let center = IntersectionBounds::rect().center();
+
+        for direction in Direction::ALL.into_iter().filter(Direction::is_open) {
+            let mut approaching: Vec<(i32, f32)> = vehicles
+                .iter()
+                .filter(|(id, _, _)| {
+                    self.vehicle_stats.get(id).is_some_and(|stats| {
+                        stats.direction == direction && !stats.in_intersection && !stats.passed
+                    })
+                })
+                .map(|(_, rect, velocity)| {
+                    let distance = (rect.center().x() - center.x()).abs() + (rect.center().y() - center.y()).abs();
+                    (distance, *velocity)
+                })
+                .collect();
+            approaching.sort_by_key(|(distance, _)| *distance);
+
+            let length = approaching
+                .iter()
+                .take_while(|(_, velocity)| *velocity < QUEUE_SPEED_THRESHOLD)
+                .count() as u32;
+            self.queues
+                .entry(direction)
+                .or_default()
+                .record(length, self.ticks);
+        }
+    }
+
     pub fn set_end_time(&mut self) {
         let now = Instant::now();
         self.end_time = Some((now - self.simulation_start).as_secs_f32());
     }
 
+    fn throughput_bucket(&self) -> usize {
+        (self.ticks / (TICKS_PER_SECOND * THROUGHPUT_BUCKET_SECONDS)) as usize
+    }
+
+    /// Completed crossings per bucket, up to and including the bucket in progress.
+    pub fn get_throughput(&self) -> Vec<u32> {
+        let mut throughput = self.throughput.clone();
+        throughput.resize(self.throughput_bucket() + 1, 0);
+        throughput
+    }
+
+    /// Nearest-rank percentiles of the completed crossing times, if any crossed.
+    pub fn get_crossing_percentiles(&self) -> Option<CrossingPercentiles> {
+        if self.crossing_times.is_empty() {
+            return None;
+        }
+
+        let mut times = self.crossing_times.clone();
+        times.sort_by(f32::total_cmp);
+        let percentile = |p: f32| {
+            let rank = (p / 100.0 * times.len() as f32).ceil() as usize;
+            times[rank.clamp(1, times.len()) - 1]
+        };
+
+        Some(CrossingPercentiles {
+            p50: percentile(50.0),
+            p90: percentile(90.0),
+            p95: percentile(95.0),
+            p99: percentile(99.0),
+        })
+    }
+
+    /// Mean of the completed crossing times, if any crossed.
+    pub fn get_average_crossing_time(&self) -> Option<f32> {
+        (!self.crossing_times.is_empty())
+            .then(|| self.crossing_times.iter().sum::<f32>() / self.crossing_times.len() as f32)
+    }
+
+    /// Total stops, and the total, average and worst time vehicles spent stopped, in
+    /// seconds. Vehicles that never stopped count towards the average with no wait.
+    pub fn get_stop_summary(&self) -> (u32, f32, f32, f32) {
+        let total_stops = self.vehicle_stats.values().map(|stats| stats.stops).sum();
+        let stopped_ticks = self.vehicle_stats.values().map(|stats| stats.stopped_ticks);
+        let total_ticks: u32 = stopped_ticks.clone().sum();
+        let worst_ticks = stopped_ticks.max().unwrap_or(0);
+
+        let to_seconds = |ticks: f32| ticks / TICKS_PER_SECOND as f32;
+        let average = total_ticks as f32 / self.vehicle_stats.len().max(1) as f32;
+        (
+            total_stops,
+            to_seconds(total_ticks as f32),
+            to_seconds(average),
+            to_seconds(worst_ticks as f32),
+        )
+    }
+
+    /// Time spent and crossings completed in each weather the run has seen, so runs in
+    /// rain and in the dry can be compared.
+    pub fn get_weather_summaries(&self) -> Vec<WeatherSummary> {
+        Weather::ALL
+            .into_iter()
+            .filter_map(|weather| {
+                let period = self.weather_periods.get(&weather)?;
+                let seconds = period.ticks as f32 / TICKS_PER_SECOND as f32;
+                Some(WeatherSummary {
+                    weather: format!("{:?}", weather),
+                    seconds,
+                    completed: period.completed,
+                    per_minute: if seconds > 0.0 {
+                        period.completed as f32 * 60.0 / seconds
+                    } else {
+                        0.0
+                    },
+                })
+            })
+            .collect()
+    }
+
+    /// Spawned and completed vehicles and crossing times, for each route.
+    pub fn get_route_summaries(&self) -> Vec<RouteSummary> {
+        TurnDirection::ALL
+            .into_iter()
+            .map(|route| {
+                let vehicles: Vec<&VehicleStats> = self
+                    .vehicle_stats
+                    .values()
+                    .filter(|stats| stats.route == Some(route))
+                    .collect();
+                let times: Vec<f32> = vehicles
+                    .iter()
+                    .filter(|stats| stats.passed && stats.exit_reason == Some(ExitReason::LeftWindow))
+                    .filter_map(|stats| stats.get_intersection_time())
+                    .collect();
+
+                RouteSummary {
+                    route: route.name(),
+                    spawned: vehicles.len() as u32,
+                    completed: times.len() as u32,
+                    min_time: times.iter().copied().reduce(f32::min),
+                    average_time: (!times.is_empty())
+                        .then(|| times.iter().sum::<f32>() / times.len() as f32),
+                    max_time: times.iter().copied().reduce(f32::max),
+                }
+            })
+            .collect()
+    }
+
+    /// Grades the run so far from its close calls, collisions, slow crossings and
+    /// vehicles removed while stuck.
+    pub fn get_grade(&self) -> Grade {
+        let slow_crossings = self
+            .crossing_times
+            .iter()
+            .filter(|&&time| time > self.grading.slow_crossing_seconds)
+            .count();
+        grade(
+            &self.grading,
+            &Incidents {
+                close_calls: self.total_close_calls,
+                collisions: self.collisions.len() as u32,
+                slow_crossings: slow_crossings as u32,
+                gridlocks: self.total_removed_stuck,
+            },
+        )
+    }
+
     pub fn get_duration(&self) -> f32 {
-        let now = Instant::now();
-        let new = Some((now - self.simulation_start).as_secs_f32());
-        self.end_time.unwrap_or_else(|| new.unwrap_or(0.0))
+        self.end_time
+            .unwrap_or_else(|| self.simulation_start.elapsed().as_secs_f32())
     }
 
     pub fn get_summary(&self) -> StatisticsSummary {
+        let (total_stops, total_stopped_time, average_stopped_time, worst_stopped_time) =
+            self.get_stop_summary();
         StatisticsSummary {
+            controller: self.controller,
+            spawn: self.spawn_settings,
             total_vehicles: self.total_vehicles,
             total_vehicles_passed: self.total_vehicles_passed,
             max_velocity: if self.has_valid_velocities {
@@ -211,23 +762,399 @@ impl Statistics {
             } else {
                 0.0
             },
+            crossing_percentiles: self.get_crossing_percentiles(),
+            total_stops,
+            total_stopped_time,
+            average_stopped_time,
+            worst_stopped_time,
             total_close_calls: self.total_close_calls,
+            total_ttc_warnings: self.total_ttc_warnings,
+            total_anomalies: self.total_anomalies,
+            lowest_time_to_collision: self
+                .lowest_time_to_collision
+                .map(|ticks| ticks * SIMULATION_STEP.as_secs_f32()),
+            total_collisions: self.collisions.len() as u32,
+            collisions: self.collisions.clone(),
+            total_removed_stuck: self.total_removed_stuck,
             duration: self.get_duration(),
             max_vehicles_in_intersection: self.max_vehicles_in_intersection,
+            spawn_queue: self.spawn_queue,
+            max_spawn_queue: self.max_spawn_queue,
+            resolver_steps: self.resolver_steps,
+            max_vehicle_resolver_steps: self.max_vehicle_resolver_steps,
+            max_tick_resolver_steps: self.max_tick_resolver_steps,
+            frames: self.frames,
+            dropped_frames: self.dropped_frames,
             has_valid_data: self.has_valid_velocities && self.total_vehicles_passed > 0,
+            queues: Direction::ALL
+                .into_iter()
+                .filter(Direction::is_open)
+                .map(|direction| {
+                    let queue = self.queues.get(&direction);
+                    QueueSummary {
+                        direction: direction.compass_name(),
+                        max: queue.map_or(0, |queue| queue.max),
+                        average: queue.map_or(0.0, |queue| {
+                            queue.total as f32 / self.ticks.max(1) as f32
+                        }),
+                        per_second: queue.map_or_else(Vec::new, |queue| queue.per_second.clone()),
+                    }
+                })
+                .collect(),
+            throughput: self.get_throughput(),
+            routes: self.get_route_summaries(),
+            weather: self.get_weather_summaries(),
+            grade: self.get_grade(),
         }
     }
+
+    /// Writes the summary, with the heatmap's matrices alongside it, as JSON.
+    pub fn export_json(&self, path: &str, heatmap: &Heatmap) -> Result<(), String> {
+        let export = StatisticsExport {
+            summary: self.get_summary(),
+            heatmap: heatmap.export(),
+        };
+        let json = serde_json::to_string_pretty(&export).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| e.to_string())
+    }
+}
+
+/// Ticks until `a` and `b` first overlap as they follow their paths, which hold their
+/// positions from the next tick on. Vehicles already overlapping, or not meeting within
+/// `TTC_HORIZON_TICKS` or before either path ends, have no time to collision.
+fn time_to_collision(a: Rect, a_path: &[TimedPosition], b: Rect, b_path: &[TimedPosition]) -> Option<f32> {
+    if a.has_intersection(b) {
+        return None;
+    }
+    let at = |rect: Rect, step: &TimedPosition| Rect::new(step.position.x, step.position.y, rect.width(), rect.height());
+    a_path
+        .iter()
+        .zip(b_path)
+        .take(TTC_HORIZON_TICKS)
+        .position(|(a_step, b_step)| at(a, a_step).has_intersection(at(b, b_step)))
+        .map(|ticks| (ticks + 1) as f32)
+}
+
+#[derive(Serialize)]
+struct StatisticsExport {
+    #[serde(flatten)]
+    summary: StatisticsSummary,
+    heatmap: HeatmapExport,
+}
+
+#[derive(Serialize, Clone, Copy)]
+pub struct CrossingPercentiles {
+    pub p50: f32,
+    pub p90: f32,
+    pub p95: f32,
+    pub p99: f32,
+}
+
+#[derive(Serialize)]
+pub struct QueueSummary {
+    pub direction: &'static str,
+    pub max: u32,
+    pub average: f32,
+    pub per_second: Vec<u32>,
 }
 
+#[derive(Serialize)]
 pub struct StatisticsSummary {
+    pub controller: &'static str,
+    pub spawn: Option<SpawnSettings>,
     pub total_vehicles: u32,
     pub total_vehicles_passed: u32,
     pub max_velocity: f32,
     pub min_velocity: f32,
     pub max_intersection_time: f32,
     pub min_intersection_time: f32,
+    pub crossing_percentiles: Option<CrossingPercentiles>,
+    pub total_stops: u32,
+    // Seconds spent stopped, summed over every vehicle
+    pub total_stopped_time: f32,
+    pub average_stopped_time: f32,
+    pub worst_stopped_time: f32,
     pub total_close_calls: u32,
+    pub total_ttc_warnings: u32,
+    pub total_anomalies: u32,
+    // In seconds
+    pub lowest_time_to_collision: Option<f32>,
+    pub total_collisions: u32,
+    pub collisions: Vec<CollisionIncident>,
+    pub total_removed_stuck: u32,
     pub duration: f32,
     pub max_vehicles_in_intersection: u32,
+    pub spawn_queue: usize,
+    pub max_spawn_queue: usize,
+    // Passes of the collision resolver's loops while planning paths
+    pub resolver_steps: u64,
+    pub max_vehicle_resolver_steps: u64,
+    pub max_tick_resolver_steps: u64,
+    pub frames: u32,
+    // Frames that went over the frame budget
+    pub dropped_frames: u32,
     pub has_valid_data: bool,
+    pub queues: Vec<QueueSummary>,
+    pub throughput: Vec<u32>,
+    pub routes: Vec<RouteSummary>,
+    pub weather: Vec<WeatherSummary>,
+    pub grade: Grade,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct WeatherPeriod {
+    ticks: u64,
+    completed: u32,
+}
+
+#[derive(Serialize)]
+pub struct WeatherSummary {
+    pub weather: String,
+    pub seconds: f32,
+    pub completed: u32,
+    pub per_minute: f32,
+}
+
+#[derive(Serialize)]
+pub struct RouteSummary {
+    pub route: &'static str,
+    pub spawned: u32,
+    pub completed: u32,
+    pub min_time: Option<f32>,
+    pub average_time: Option<f32>,
+    pub max_time: Option<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{LINE_SPACING, VEHICLE_SIZE, WORLD_SIZE};
+    use crate::geometry::layout::road_center;
+
+    fn with_crossing_times(times: &[f32]) -> Statistics {
+        let mut statistics = Statistics::new();
+        statistics.crossing_times = times.to_vec();
+        statistics
+    }
+
+    fn percentiles(times: &[f32]) -> Option<(f32, f32, f32)> {
+        with_crossing_times(times)
+            .get_crossing_percentiles()
+            .map(|percentiles| (percentiles.p50, percentiles.p90, percentiles.p99))
+    }
+
+    #[test]
+    fn crossing_percentiles_need_a_crossing() {
+        assert_eq!(percentiles(&[]), None);
+    }
+
+    #[test]
+    fn crossing_percentiles_of_one_crossing_are_that_crossing() {
+        assert_eq!(percentiles(&[2.5]), Some((2.5, 2.5, 2.5)));
+    }
+
+    #[test]
+    fn crossing_percentiles_use_the_nearest_rank() {
+        let hundred: Vec<f32> = (1..=100).rev().map(|time| time as f32).collect();
+        assert_eq!(percentiles(&hundred), Some((50.0, 90.0, 99.0)));
+        // Ranks round up: p50 of ten is the 5th, p99 the 10th
+        let ten: Vec<f32> = (1..=10).map(|time| time as f32).collect();
+        assert_eq!(percentiles(&ten), Some((5.0, 9.0, 10.0)));
+        assert_eq!(percentiles(&[4.0, 1.0]), Some((1.0, 4.0, 4.0)));
+    }
+
+    // Positions from the next tick on for a rect at `rect` moving `step` each tick
+    fn path(rect: Rect, step: (i32, i32), ticks: i32) -> Vec<TimedPosition> {
+        (1..=ticks)
+            .map(|tick| TimedPosition {
+                position: Position {
+                    x: rect.x() + step.0 * tick,
+                    y: rect.y() + step.1 * tick,
+                },
+                time: tick as u64,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn time_to_collision_is_when_the_paths_first_touch() {
+        let (a, b) = (Rect::new(0, 0, 10, 10), Rect::new(90, 0, 10, 10));
+        // Closing 2 px a tick, the 80 px gap is gone after 40 ticks and they overlap on the next
+        assert_eq!(time_to_collision(a, &path(a, (2, 0), 100), b, &path(b, (0, 0), 100)), Some(41.0));
+    }
+
+    #[test]
+    fn time_to_collision_ignores_vehicles_that_never_meet_in_time() {
+        let a = Rect::new(0, 0, 10, 10);
+        let ttc = |a_step: (i32, i32), b: Rect, b_step: (i32, i32), ticks: i32| {
+            time_to_collision(a, &path(a, a_step, ticks), b, &path(b, b_step, ticks))
+        };
+        // Moving apart, side by side, and too far out for the horizon
+        assert_eq!(ttc((-2, 0), Rect::new(90, 0, 10, 10), (0, 0), 200), None);
+        assert_eq!(ttc((2, 0), Rect::new(0, 20, 10, 10), (2, 0), 200), None);
+        let far = Rect::new(10 + 2 * TTC_HORIZON_TICKS as i32 + 20, 0, 10, 10);
+        assert_eq!(ttc((2, 0), far, (0, 0), 400), None);
+        // Or a path that ends first, as it leaves the road
+        assert_eq!(ttc((2, 0), Rect::new(90, 0, 10, 10), (0, 0), 30), None);
+        // Overlapping already is a collision, not a time to one
+        assert_eq!(ttc((2, 0), Rect::new(5, 5, 10, 10), (0, 0), 200), None);
+    }
+
+    #[test]
+    fn a_reset_starts_the_counts_over() {
+        let mut statistics = Statistics::new();
+        let first = statistics.add_vehicle(Direction::Up);
+        let second = statistics.add_vehicle(Direction::Left);
+        // Side by side in the middle of the intersection
+        let centre = road_center();
+        let close = [
+            (first, Rect::new(centre.x - 10, centre.y, 10, 10)),
+            (second, Rect::new(centre.x + 5, centre.y, 10, 10)),
+        ];
+        statistics.check_close_calls(&close, |_, _| {});
+        assert_eq!(statistics.get_summary().total_close_calls, 1);
+
+        statistics.reset();
+        let summary = statistics.get_summary();
+        assert_eq!(summary.total_vehicles, 0);
+        assert_eq!(summary.total_close_calls, 0);
+        // New vehicles get new ids, and an old pair counts again
+        assert!(statistics.add_vehicle(Direction::Up) > second);
+        statistics.check_close_calls(&close, |_, _| {});
+        assert_eq!(statistics.get_summary().total_close_calls, 1);
+    }
+
+    #[test]
+    fn the_close_call_callback_fires_once_per_pair() {
+        let mut statistics = Statistics::new();
+        let [a, b, c] = [Direction::Up, Direction::Left, Direction::Down].map(|arm| statistics.add_vehicle(arm));
+        let centre = road_center();
+        // a is close to both b and c, which are too far apart to be close to each other
+        let rects = [
+            (a, Rect::new(centre.x, centre.y, 10, 10)),
+            (b, Rect::new(centre.x - 40, centre.y, 10, 10)),
+            (c, Rect::new(centre.x + 40, centre.y, 10, 10)),
+        ];
+        let mut calls = Vec::new();
+        // Still close for several ticks, and listed in another order
+        for tick in 0..5 {
+            let mut rects = rects;
+            if tick % 2 == 1 {
+                rects.reverse();
+            }
+            statistics.check_close_calls(&rects, |first, second| calls.push((first, second)));
+        }
+        calls.sort();
+        assert_eq!(calls, [(a, b), (a, c)]);
+        assert_eq!(statistics.total_close_calls, 2);
+    }
+
+    #[test]
+    fn cars_held_behind_a_blocked_one_make_a_queue_of_three() {
+        let mut statistics = Statistics::new();
+        let (_, top, _, _) = IntersectionBounds::edges();
+        let x = road_center().x - LINE_SPACING;
+        let size = VEHICLE_SIZE;
+        // Stalled in the intersection, so nobody behind it can go
+        let blocked = statistics.add_vehicle(Direction::Up);
+        let blocked_at = Rect::new(x, top + LINE_SPACING, size, size);
+        statistics.update_vehicle_stats(blocked, blocked_at, 0.0);
+        // Nose to tail back from the intersection, with one more still driving up
+        let waiting: Vec<(usize, Rect)> = (1..=3)
+            .map(|place| (statistics.add_vehicle(Direction::Up), Rect::new(x, top - place * LINE_SPACING, size, size)))
+            .collect();
+        let arriving = (statistics.add_vehicle(Direction::Up), Rect::new(x, top - 5 * LINE_SPACING, size, size));
+
+        for arrived in 1..=waiting.len() {
+            let mut vehicles = vec![(blocked, blocked_at, 0.0)];
+            vehicles.extend(waiting[..arrived].iter().map(|&(id, rect)| (id, rect, 0.0)));
+            vehicles.push((arriving.0, arriving.1, 2.0));
+            statistics.update_queues(&vehicles);
+            assert_eq!(statistics.queues[&Direction::Up].current, arrived as u32);
+        }
+        // Once the way is clear the queue drives off, and the longest one is kept
+        let moving: Vec<(usize, Rect, f32)> = waiting.iter().map(|&(id, rect)| (id, rect, 2.0)).collect();
+        statistics.update_queues(&moving);
+        let queue = &statistics.queues[&Direction::Up];
+        assert_eq!((queue.current, queue.max), (0, 3));
+        assert_eq!(statistics.queues[&Direction::Down].max, 0);
+    }
+
+    #[test]
+    fn a_blocked_car_waits_for_as_long_as_it_stood_still() {
+        const BLOCKED_TICKS: u32 = 45;
+        let mut statistics = Statistics::new();
+        let blocked = statistics.add_vehicle(Direction::Up);
+        let moving = statistics.add_vehicle(Direction::Down);
+        let rect = Rect::new(100, 100, 10, 10);
+        for _ in 0..BLOCKED_TICKS {
+            statistics.update_vehicle_stats(blocked, rect, 0.0);
+            statistics.update_vehicle_stats(moving, rect, 2.0);
+        }
+        statistics.update_vehicle_stats(blocked, rect, 2.0);
+
+        let (stops, total, average, worst) = statistics.get_stop_summary();
+        let waited = BLOCKED_TICKS as f32 / TICKS_PER_SECOND as f32;
+        assert_eq!(stops, 1);
+        assert!((total - waited).abs() < 1e-4, "waited {} s", total);
+        assert!((worst - waited).abs() < 1e-4, "waited {} s", worst);
+        // The car that never waited counts, with no wait
+        assert!((average - waited / 2.0).abs() < 1e-4, "waited {} s on average", average);
+    }
+
+    #[test]
+    fn a_crossing_is_timed_in_simulated_ticks() {
+        const CROSSING_TICKS: u64 = 120;
+        let mut statistics = Statistics::new();
+        let id = statistics.add_vehicle(Direction::Up);
+        statistics.set_route(id, TurnDirection::Straight);
+        let center = road_center();
+        statistics.update_vehicle_stats(id, Rect::new(center.x, center.y, 10, 10), 2.0);
+        // However long the ticks take on the wall clock
+        for _ in 0..CROSSING_TICKS {
+            statistics.update_queues(&[]);
+        }
+        assert!(statistics.update_vehicle_stats(id, Rect::new(0, 0, 10, 10), 2.0), "it has passed");
+        statistics.record_vehicle_exit(id, ExitReason::LeftWindow);
+
+        let expected = CROSSING_TICKS as f32 * SIMULATION_STEP.as_secs_f32();
+        assert_eq!(statistics.get_average_crossing_time(), Some(expected));
+    }
+
+    #[test]
+    fn a_straight_through_car_passes_exactly_once() {
+        let mut statistics = Statistics::new();
+        let center = road_center();
+        let x = center.x - LINE_SPACING;
+        let car = statistics.add_vehicle(Direction::Up);
+        let mut passes = 0;
+        for y in (-(VEHICLE_SIZE as i32)..WORLD_SIZE as i32).step_by(2) {
+            if statistics.update_vehicle_stats(car, Rect::new(x, y, VEHICLE_SIZE, VEHICLE_SIZE), 2.0) {
+                passes += 1;
+            }
+        }
+        statistics.record_vehicle_exit(car, ExitReason::LeftWindow);
+        assert_eq!((passes, statistics.total_vehicles_passed), (1, 1));
+
+        // One taken off the road halfway across has not passed
+        let stuck = statistics.add_vehicle(Direction::Up);
+        for y in (-(VEHICLE_SIZE as i32)..center.y).step_by(2) {
+            assert!(!statistics.update_vehicle_stats(stuck, Rect::new(x, y, VEHICLE_SIZE, VEHICLE_SIZE), 2.0));
+        }
+        assert_eq!(statistics.current_vehicles_in_intersection, 1);
+        statistics.record_vehicle_exit(stuck, ExitReason::RemovedStuck);
+        assert_eq!(statistics.total_vehicles_passed, 1);
+        assert_eq!(statistics.current_vehicles_in_intersection, 0);
+    }
+
+    #[test]
+    fn frames_over_the_budget_are_counted_as_dropped() {
+        let mut statistics = Statistics::new();
+        for time in [FRAME_DURATION / 2, FRAME_DURATION, FRAME_DURATION * 3, FRAME_DURATION + Duration::from_micros(1)] {
+            statistics.record_frame(time);
+        }
+        let summary = statistics.get_summary();
+        assert_eq!(summary.frames, 4);
+        assert_eq!(summary.dropped_frames, 2);
+    }
 }
@@ -0,0 +1,135 @@
+use crate::simulation::VehicleManager;
+use std::time::Duration;
+
+/// Keeps the road near a target number of vehicles and times every frame, as a
+/// harness for measuring how the simulation scales with traffic.
+pub struct StressTest {
+    target: usize,
+    // How long each frame took, or each tick when there is no window
+    frame_times: Vec<Duration>,
+    population_total: u64,
+}
+
+impl StressTest {
+    pub fn new(target: usize) -> Self {
+        Self {
+            target,
+            frame_times: Vec::new(),
+            population_total: 0,
+        }
+    }
+
+    /// Requests spawns from open arms in random order while fewer than the target are
+    /// on the road or waiting to enter. Arms in their spawn cooldown, or without an open
+    /// lane, are left for a later tick.
+    pub fn top_up(&self, manager: &mut VehicleManager) {
+        for direction in manager.shuffled_open_arms() {
+            if manager.population() >= self.target {
+                break;
+            }
+            let _ = manager.try_spawn_on_tick(direction, None);
+        }
+    }
+
+    pub fn record_frame(&mut self, time: Duration, population: usize) {
+        self.frame_times.push(time);
+        self.population_total += population as u64;
+    }
+
+    pub fn report(&self, manager: &VehicleManager) -> StressReport {
+        let mut times = self.frame_times.clone();
+        times.sort();
+        let percentile = |p: u32| {
+            let rank = (p as usize * times.len()).div_ceil(100);
+            times.get(rank.clamp(1, times.len().max(1)) - 1).copied().unwrap_or_default()
+        };
+
+        let statistics = manager.get_statistics();
+        StressReport {
+            target: self.target,
+            frames: times.len(),
+            average_population: self.population_total as f64 / times.len().max(1) as f64,
+            p50: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+            worst: times.last().copied().unwrap_or_default(),
+            vehicles_spawned: statistics.total_vehicles,
+            vehicles_passed: statistics.total_vehicles_passed,
+            close_calls: statistics.total_close_calls,
+            collisions: statistics.collisions.len() as u32,
+        }
+    }
+}
+
+/// Frame-time percentiles of a stress run next to its traffic.
+pub struct StressReport {
+    pub target: usize,
+    pub frames: usize,
+    pub average_population: f64,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub worst: Duration,
+    pub vehicles_spawned: u32,
+    pub vehicles_passed: u32,
+    pub close_calls: u32,
+    pub collisions: u32,
+}
+
+impl StressReport {
+    pub fn print(&self) {
+        let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+        println!(
+            "Stress test: {} frames, {:.1} vehicles on average for a target of {}",
+            self.frames, self.average_population, self.target
+        );
+        println!(
+            "Frame time: p50 {:.2} ms, p95 {:.2} ms, p99 {:.2} ms, worst {:.2} ms",
+            ms(self.p50),
+            ms(self.p95),
+            ms(self.p99),
+            ms(self.worst)
+        );
+        println!("Vehicles spawned: {}", self.vehicles_spawned);
+        println!("Vehicles processed (passed the intersection): {}", self.vehicles_passed);
+        println!("Close calls: {}", self.close_calls);
+        println!("Collisions: {}", self.collisions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::AudioEngine;
+    use crate::config::SimulationConfig;
+    use std::rc::Rc;
+
+    // Where and when each vehicle of a short stress run from `seed` entered the road
+    fn spawns(seed: u64) -> Vec<(usize, u64, i32, i32)> {
+        let config = SimulationConfig {
+            spawn_seed: seed,
+            ..SimulationConfig::default()
+        };
+        let mut manager = VehicleManager::new_with_config(Rc::new(AudioEngine::silent()), &config);
+        let stress = StressTest::new(12);
+        let mut spawns: Vec<(usize, u64, i32, i32)> = Vec::new();
+        for _ in 0..300 {
+            stress.top_up(&mut manager);
+            manager.update_vehicles();
+            for vehicle in manager.get_vehicles() {
+                if !spawns.iter().any(|&(id, ..)| id == vehicle.id) {
+                    spawns.push((vehicle.id, manager.get_tick(), vehicle.rect.x(), vehicle.rect.y()));
+                }
+            }
+        }
+        spawns
+    }
+
+    #[test]
+    fn a_stress_run_repeats_with_its_seed() {
+        let first = spawns(7);
+        assert!(first.len() > 12);
+        assert_eq!(first, spawns(7));
+        assert_ne!(first, spawns(8));
+    }
+}
@@ -0,0 +1,75 @@
+use crate::constants::SIMULATION_STEP;
+use crate::simulation::events::SimEvent;
+use crate::simulation::statistics::ExitReason;
+use std::fmt::Write;
+
+/// One row of the timeline. Events between two vehicles name the second as `other`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimedEvent {
+    pub tick: u64,
+    pub kind: &'static str,
+    pub vehicle_id: usize,
+    pub other: Option<usize>,
+}
+
+/// Every event of a run in the order it happened, for working out afterwards when
+/// traffic built up.
+#[derive(Debug, Default)]
+pub struct Timeline {
+    events: Vec<TimedEvent>,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, tick: u64, event: &SimEvent) {
+        let (kind, vehicle_id, other) = match *event {
+            SimEvent::VehicleSpawned { id } => ("spawned", id, None),
+            SimEvent::SpawnDelayed { id, .. } => ("spawn_delayed", id, None),
+            SimEvent::PassageGranted { id, .. } => ("passage_granted", id, None),
+            SimEvent::IntersectionCrossed { id } => ("crossed", id, None),
+            SimEvent::CloseCall { a, b, .. } => ("close_call", a, Some(b)),
+            SimEvent::Collision { a, b, .. } => ("collision", a, Some(b)),
+            SimEvent::VehicleExited {
+                id,
+                reason: ExitReason::LeftWindow,
+            } => ("left_window", id, None),
+            SimEvent::VehicleExited {
+                id,
+                reason: ExitReason::RemovedStuck,
+            } => ("removed_stuck", id, None),
+        };
+        self.events.push(TimedEvent {
+            tick,
+            kind,
+            vehicle_id,
+            other,
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Writes the timeline as CSV, one event per line with its tick and the simulated
+    /// time in seconds.
+    pub fn export_csv(&self, path: &str) -> Result<(), String> {
+        let seconds_per_tick = SIMULATION_STEP.as_secs_f64();
+        let mut csv = String::from("tick,seconds,event,vehicle,other\n");
+        for event in &self.events {
+            let other = event.other.map_or_else(String::new, |other| other.to_string());
+            let _ = writeln!(
+                csv,
+                "{},{:.3},{},{},{}",
+                event.tick,
+                event.tick as f64 * seconds_per_tick,
+                event.kind,
+                event.vehicle_id,
+                other
+            );
+        }
+        std::fs::write(path, csv).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+/// Time left to sleep after a frame that took `elapsed`, to hold `frame_duration`.
+/// Frames that already took longer sleep not at all.
+pub fn frame_remainder(frame_duration: Duration, elapsed: Duration) -> Duration {
+    frame_duration.saturating_sub(elapsed)
+}
+
+/// Turns real elapsed time into a whole number of fixed simulation steps.
+pub struct FixedTimestep {
+    step: Duration,
+    max_steps: u32,
+    accumulator: Duration,
+}
+
+impl FixedTimestep {
+    pub fn new(step: Duration, max_steps: u32) -> Self {
+        Self {
+            step,
+            max_steps,
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    /// Adds `elapsed` to the accumulator and returns how many steps are due.
+    /// After a long stall at most `max_steps` are returned and the backlog is dropped,
+    /// so the simulation slows down instead of trying to catch up forever.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulator += elapsed;
+
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            steps += 1;
+
+            if steps == self.max_steps {
+                self.accumulator = self.accumulator.min(self.step);
+                break;
+            }
+        }
+
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(16);
+
+    #[test]
+    fn frame_remainder_sleeps_what_is_left_of_the_frame() {
+        assert_eq!(frame_remainder(FRAME, Duration::ZERO), FRAME);
+        assert_eq!(frame_remainder(FRAME, Duration::from_millis(10)), Duration::from_millis(6));
+    }
+
+    #[test]
+    fn frame_remainder_does_not_sleep_after_a_slow_frame() {
+        assert_eq!(frame_remainder(FRAME, FRAME), Duration::ZERO);
+        assert_eq!(frame_remainder(FRAME, Duration::from_millis(40)), Duration::ZERO);
+    }
+
+    #[test]
+    fn advance_turns_elapsed_time_into_whole_steps() {
+        let mut timestep = FixedTimestep::new(FRAME, 100);
+        assert_eq!(timestep.advance(Duration::from_millis(10)), 0);
+        // The 10 ms left over carry into the next frame
+        assert_eq!(timestep.advance(Duration::from_millis(10)), 1);
+        assert_eq!(timestep.advance(Duration::from_millis(160)), 10);
+    }
+
+    #[test]
+    fn advance_caps_the_steps_after_a_long_stall() {
+        let mut timestep = FixedTimestep::new(FRAME, 5);
+        assert_eq!(timestep.advance(Duration::from_secs(1)), 5);
+        // The backlog is dropped, keeping at most one step's worth
+        assert_eq!(timestep.advance(Duration::ZERO), 1);
+        assert_eq!(timestep.advance(Duration::ZERO), 0);
+    }
+}
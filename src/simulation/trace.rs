@@ -0,0 +1,159 @@
+use crate::constants::SIMULATION_STEP;
+use crate::direction::Direction;
+use std::collections::VecDeque;
+
+/// A spawn taken from a trace: a vehicle entering from `direction` heading for `target`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceEntry {
+    pub tick: u64,
+    pub direction: Direction,
+    pub target: Direction,
+}
+
+/// Spawns read from a trace of `timestamp,direction,target` lines, with the timestamp
+/// in simulated seconds and directions given as compass names (`north`, `east`, ...).
+/// Scenarios are the same with the timestamp in milliseconds.
+pub struct TraceSource {
+    entries: VecDeque<TraceEntry>,
+}
+
+impl TraceSource {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Ok(Self::parse(&contents))
+    }
+
+    /// Reads a scenario of `time_ms,from,target` lines. Unlike a trace, a line that
+    /// cannot be used is an error, so a checked-in scenario never silently loses spawns.
+    pub fn scenario_from_file(path: &str) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::parse_scenario(&contents).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse_scenario(contents: &str) -> Result<Self, String> {
+        let mut entries = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(index, line)| {
+                Self::parse_line(line, 0.001).map_err(|e| format!("line {}: {}", index + 1, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.tick);
+
+        Ok(Self {
+            entries: entries.into(),
+        })
+    }
+
+    /// Parses a trace, skipping blank lines, `#` comments and lines that cannot be
+    /// used with a warning.
+    pub fn parse(contents: &str) -> Self {
+        let mut entries: Vec<TraceEntry> = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .filter_map(|(index, line)| match Self::parse_line(line, 1.0) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    eprintln!("Warning: skipping trace line {}: {}", index + 1, e);
+                    None
+                }
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.tick);
+
+        Self {
+            entries: entries.into(),
+        }
+    }
+
+    /// Parses one line, its timestamp counted in units of `seconds_per_unit`.
+    fn parse_line(line: &str, seconds_per_unit: f64) -> Result<TraceEntry, String> {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let [timestamp, direction, target] = fields[..] else {
+            return Err(format!("expected 3 fields, found {}", fields.len()));
+        };
+
+        let time: f64 = timestamp
+            .parse()
+            .map_err(|_| format!("invalid timestamp '{}'", timestamp))?;
+        if !time.is_finite() || time < 0.0 {
+            return Err(format!("invalid timestamp '{}'", timestamp));
+        }
+        let seconds = time * seconds_per_unit;
+        let direction = Self::parse_arm(direction)?;
+        let target = Self::parse_arm(target)?;
+
+        Ok(TraceEntry {
+            tick: (seconds / SIMULATION_STEP.as_secs_f64()).round() as u64,
+            direction,
+            target,
+        })
+    }
+
+    fn parse_arm(name: &str) -> Result<Direction, String> {
+        let direction = Direction::ALL
+            .into_iter()
+            .find(|direction| direction.compass_name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("unknown direction '{}'", name))?;
+        if !direction.is_open() {
+            return Err(format!("the {} arm is closed", name));
+        }
+        Ok(direction)
+    }
+
+    /// Spawns given directly rather than read, such as a schedule generated once to be
+    /// played more than once.
+    pub fn from_entries(mut entries: Vec<TraceEntry>) -> Self {
+        entries.sort_by_key(|entry| entry.tick);
+        Self {
+            entries: entries.into(),
+        }
+    }
+
+    /// Removes and returns the entries due at or before `tick`.
+    pub fn take_due(&mut self, tick: u64) -> Vec<TraceEntry> {
+        let due = self.entries.partition_point(|entry| entry.tick <= tick);
+        self.entries.drain(..due).collect()
+    }
+
+    /// Puts back entries that could not be spawned yet, to be retried first.
+    pub fn defer(&mut self, entries: Vec<TraceEntry>) {
+        for entry in entries.into_iter().rev() {
+            self.entries.push_front(entry);
+        }
+    }
+
+    /// Whether every entry has been spawned.
+    pub fn is_finished(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_scenario_is_read_in_milliseconds_and_sorted() {
+        let mut scenario = TraceSource::parse_scenario("# time_ms,from,target\n500,north,east\n\n0, West ,south\n").unwrap();
+        let tick = |ms: u64| ms / SIMULATION_STEP.as_millis() as u64;
+        assert_eq!(
+            scenario.take_due(tick(500)),
+            [
+                TraceEntry { tick: 0, direction: Direction::Left, target: Direction::Down },
+                TraceEntry { tick: tick(500), direction: Direction::Up, target: Direction::Right },
+            ]
+        );
+        assert!(scenario.is_finished());
+    }
+
+    #[test]
+    fn a_scenario_line_that_cannot_be_used_is_an_error() {
+        let error = |contents: &str| TraceSource::parse_scenario(contents).err().expect("rejected");
+        assert_eq!(error("0,north,east\n10,up,east\n"), "line 2: unknown direction 'up'");
+        assert_eq!(error("-5,north,east"), "line 1: invalid timestamp '-5'");
+        assert_eq!(error("0,north"), "line 1: expected 3 fields, found 2");
+    }
+}
@@ -0,0 +1,76 @@
+use crate::constants::{APPROACH_SPEED, EXIT_SPEED, LINE_SPACING};
+use sdl2::pixels::Color;
+
+/// Driving conditions. Bad weather makes vehicles keep longer gaps and raises
+/// the distance at which a near miss still counts as a close call. On wet roads
+/// vehicles also leave the intersection at approach speed, and in fog they wait at
+/// the stop line for the crossing traffic they can see to clear.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Weather {
+    Clear,
+    Rain,
+    Fog,
+}
+
+impl Weather {
+    pub const ALL: [Weather; 3] = [Weather::Clear, Weather::Rain, Weather::Fog];
+
+    pub fn next(&self) -> Weather {
+        match self {
+            Weather::Clear => Weather::Rain,
+            Weather::Rain => Weather::Fog,
+            Weather::Fog => Weather::Clear,
+        }
+    }
+
+    /// Multiplier for the distance under which two vehicles count as a close call.
+    pub fn safe_distance_scale(&self) -> f32 {
+        match self {
+            Weather::Clear => 1.0,
+            Weather::Rain => 1.5,
+            Weather::Fog => 1.25,
+        }
+    }
+
+    /// Extra space kept to the vehicle ahead in the same lane.
+    pub fn following_gap(&self) -> i32 {
+        match self {
+            Weather::Clear => 0,
+            Weather::Rain => LINE_SPACING / 2,
+            Weather::Fog => LINE_SPACING / 4,
+        }
+    }
+
+    /// Extra margin kept around crossing traffic in the intersection.
+    pub fn crossing_gap(&self) -> i32 {
+        match self {
+            Weather::Clear => 0,
+            Weather::Rain => LINE_SPACING / 5,
+            Weather::Fog => LINE_SPACING / 10,
+        }
+    }
+
+    /// Pixels per tick once out of the intersection.
+    pub fn exit_speed(&self) -> i32 {
+        match self {
+            Weather::Rain => APPROACH_SPEED,
+            _ => EXIT_SPEED,
+        }
+    }
+
+    /// How far a driver can see crossing traffic in the intersection, if limited.
+    pub fn sight_range(&self) -> Option<i32> {
+        match self {
+            Weather::Fog => Some(4 * LINE_SPACING),
+            _ => None,
+        }
+    }
+
+    pub fn tint(&self) -> Option<Color> {
+        match self {
+            Weather::Clear => None,
+            Weather::Rain => Some(Color::RGBA(40, 60, 120, 70)),
+            Weather::Fog => Some(Color::RGBA(200, 200, 200, 110)),
+        }
+    }
+}